@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::{
+    board::Board,
+    pieces::Color,
+    search::{mate_in, search_lines, Limits, SearchResult},
+    syzygy::Tablebase,
+};
+
+/// Analysis of a position on a background thread, keeping the newest lines it reports
+pub struct Analysis {
+    /// Position being analysed
+    pub board: Board,
+    /// Best lines of the deepest search finished so far, best first
+    pub lines: Vec<SearchResult>,
+    receiver: Receiver<Vec<SearchResult>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Analysis {
+    /// Starts looking for the best `lines` moves of `board`, searching until `limits` run out, or until stopped with `Limits::infinite`
+    pub fn start(board: &Board, lines: usize, limits: Limits, tablebase: Option<Arc<Tablebase>>) -> Self {
+        let position = board.position();
+        let (sender, receiver) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            search_lines(&position, limits, lines, tablebase.as_deref(), Some(&stopped), |lines| {
+                // Nobody is listening once the analysis is dropped
+                let _ = sender.send(lines.to_vec());
+            });
+        });
+        Analysis { board: board.position(), lines: vec![], receiver, stop, handle: Some(handle) }
+    }
+
+    /// Takes the lines reported since the last call, returning whether there were any
+    pub fn poll(&mut self) -> bool {
+        let mut updated = false;
+        while let Ok(lines) = self.receiver.try_recv() {
+            self.lines = lines;
+            updated = true;
+        }
+        updated
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.handle.as_ref().is_some_and(|handle| !handle.is_finished())
+    }
+
+    /// Ends the search, keeping the lines of the last depth it finished
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        self.poll();
+    }
+
+    /// Score of the best line for White, in centipawns or as a mate score
+    pub fn score_white(&self) -> Option<i32> {
+        let score = self.lines.first()?.score;
+        Some(if self.board.turn == Color::White { score } else { -score })
+    }
+}
+
+impl Drop for Analysis {
+    fn drop(&mut self) {
+        // The thread winds down by itself, without holding up whoever moved on
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Share of an evaluation bar filled for White given `score` for White, half for equality and all or nothing for mates
+pub fn white_share(score: i32) -> f32 {
+    match mate_in(score) {
+        Some(_) if score > 0 => 1.0,
+        Some(_) => 0.0,
+        None => 1.0 / (1.0 + (-score as f32 / 400.0).exp()),
+    }
+}
+
+/// The best line of every position in the game behind `board`, from the start to the current
+/// position, for going over a game move by move
+pub fn analyse_game(board: &Board, limits: Limits, tablebase: Option<&Tablebase>) -> Vec<SearchResult> {
+    (0..=board.moves.len()).map(|ply| search_lines(&board.replay(ply), limits, 1, tablebase, None, |_| ()).into_iter().next().unwrap_or_default()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    #[test]
+    fn test_analysis() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1").unwrap();
+        let mut analysis = Analysis::start(&board, 2, Limits::infinite(), None);
+        while analysis.lines.iter().all(|line| line.depth < 3) {
+            analysis.poll();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert!(analysis.is_running());
+        analysis.stop();
+        assert!(!analysis.is_running());
+        assert_eq!(analysis.lines.len(), 2);
+        // Black to move has time to make room for the king
+        assert!(analysis.score_white().unwrap() < MATE - 1000);
+        assert!(analysis.lines[0].best_move.is_some());
+    }
+
+    #[test]
+    fn test_analyse_game() {
+        let mut board = Board::new();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            board.apply(&board.parse_san(san).unwrap());
+        }
+        let lines = analyse_game(&board, Limits::depth(2), None);
+        assert_eq!(lines.len(), 5);
+        // After g4 Black mates in one, and the final position has no moves left
+        assert_eq!(lines[3].best_move.unwrap().uci(), "d8h4");
+        assert_eq!(mate_in(lines[3].score), Some(1));
+        assert_eq!(lines[4].best_move, None);
+        assert_eq!(white_share(lines[4].score), 0.0);
+        assert_eq!(white_share(0), 0.5);
+        assert!(white_share(300) > 0.6 && white_share(-300) < 0.4);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    analysis::analyse_game,
+    board::Board,
+    pgn::Pgn,
+    pieces::Color,
+    search::{format_score, Limits},
+    syzygy::Tablebase,
+};
+
+/// Scores are capped here before comparing, so choosing a slower win or a winning endgame over mate isn't an error
+const DECISIVE: i32 = 1000;
+
+/// How much worse a move was than the engine's choice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Judgement {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Judgement {
+    /// Classifies a move losing `loss` centipawns for the side that played it
+    pub fn from_loss(loss: i32) -> Option<Judgement> {
+        match loss {
+            300.. => Some(Judgement::Blunder),
+            100.. => Some(Judgement::Mistake),
+            50.. => Some(Judgement::Inaccuracy),
+            _ => None,
+        }
+    }
+
+    /// NAG written after the move, `?!`, `?` or `??`
+    pub fn nag(self) -> u8 {
+        match self {
+            Judgement::Inaccuracy => 6,
+            Judgement::Mistake => 2,
+            Judgement::Blunder => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for Judgement {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Judgement::Inaccuracy => write!(f, "Inaccuracy"),
+            Judgement::Mistake => write!(f, "Mistake"),
+            Judgement::Blunder => write!(f, "Blunder"),
+        }
+    }
+}
+
+/// A move the engine would not have played
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlaggedMove {
+    /// Index into `Board::moves`
+    pub ply: usize,
+    pub judgement: Judgement,
+    /// Scores for White before and after the move
+    pub before: i32,
+    pub after: i32,
+}
+
+/// Searches every position of the game within `limits` and marks the moves which gave away
+/// enough of the evaluation, with the engine's line in their place as a variation
+pub fn annotate(board: &Board, limits: Limits, tablebase: Option<&Tablebase>) -> (Pgn, Vec<FlaggedMove>) {
+    let analysis = analyse_game(board, limits, tablebase);
+    let mut pgn = Pgn::new(board.clone());
+    let mut errors = vec![];
+    let mut position = board.replay(0);
+    for (ply, mc) in board.moves.iter().enumerate() {
+        let (best, reply) = (&analysis[ply], &analysis[ply + 1]);
+        let white = |score: i32| if position.turn == Color::White { score } else { -score };
+        // Both scores for the side making the move
+        let loss = best.score.clamp(-DECISIVE, DECISIVE) - (-reply.score).clamp(-DECISIVE, DECISIVE);
+        if let Some(judgement) = Judgement::from_loss(loss).filter(|_| best.best_move != Some(*mc) && !best.pv.is_empty()) {
+            let (before, after) = (white(best.score), white(-reply.score));
+            let annotation = pgn.annotation_mut(ply);
+            annotation.nags.push(judgement.nag());
+            annotation.comment = Some(format!("{} ({} to {}). {} was best.", judgement, format_score(before), format_score(after), position.san(&best.pv[0])));
+            annotation.variations.push(best.pv.clone());
+            errors.push(FlaggedMove { ply, judgement, before, after });
+        }
+        position.apply(mc);
+    }
+    (pgn, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::MATE;
+
+    #[test]
+    fn test_judgement() {
+        assert_eq!(Judgement::from_loss(20), None);
+        assert_eq!(Judgement::from_loss(60), Some(Judgement::Inaccuracy));
+        assert_eq!(Judgement::from_loss(150), Some(Judgement::Mistake));
+        assert_eq!(Judgement::from_loss(900), Some(Judgement::Blunder));
+        assert_eq!(Judgement::Blunder.nag(), 4);
+    }
+
+    #[test]
+    fn test_annotate() {
+        // Only g4 lets Black mate at once
+        let mut board = Board::new();
+        for san in ["f3", "e5", "g4", "Qh4#"] {
+            board.apply(&board.parse_san(san).unwrap());
+        }
+        let (pgn, errors) = annotate(&board, Limits::depth(2), None);
+        assert_eq!(errors.iter().map(|error| (error.ply, error.judgement)).collect::<Vec<_>>(), vec![(2, Judgement::Blunder)]);
+        let written = pgn.to_string();
+        assert!(written.contains("1. f3 e5 2. g4?? {Blunder ("), "{}", written);
+        assert!(written.contains(" to #-1). "), "{}", written);
+        assert!(written.contains(") 2... Qh4#"), "{}", written);
+        assert_eq!(errors[0].after, -MATE + 1);
+    }
+}
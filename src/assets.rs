@@ -1,46 +1,214 @@
-use std::{collections::HashMap, io::Read, path::PathBuf};
+use std::{collections::{hash_map::Entry, HashMap}, path::PathBuf};
 
 use enum_iterator::all;
-use ggez::{context::Has, graphics::{GraphicsContext, Image}};
+use ggez::{context::Has, graphics::{GraphicsContext, Image}, GameResult};
 
 use crate::pieces::{Color as PieceColor, Piece, PieceType};
 
+/// Overrides the piece set directory when no `--assets` argument is given
+pub const ASSETS_ENV: &str = "CHESS_ASSETS";
 
+const EMBEDDED: [((PieceType, PieceColor), &[u8]); 12] = [
+    ((PieceType::Pawn, PieceColor::White), include_bytes!("../assets/chess/white_pawn.png")),
+    ((PieceType::Knight, PieceColor::White), include_bytes!("../assets/chess/white_knight.png")),
+    ((PieceType::Bishop, PieceColor::White), include_bytes!("../assets/chess/white_bishop.png")),
+    ((PieceType::Rook, PieceColor::White), include_bytes!("../assets/chess/white_rook.png")),
+    ((PieceType::Queen, PieceColor::White), include_bytes!("../assets/chess/white_queen.png")),
+    ((PieceType::King, PieceColor::White), include_bytes!("../assets/chess/white_king.png")),
+    ((PieceType::Pawn, PieceColor::Black), include_bytes!("../assets/chess/black_pawn.png")),
+    ((PieceType::Knight, PieceColor::Black), include_bytes!("../assets/chess/black_knight.png")),
+    ((PieceType::Bishop, PieceColor::Black), include_bytes!("../assets/chess/black_bishop.png")),
+    ((PieceType::Rook, PieceColor::Black), include_bytes!("../assets/chess/black_rook.png")),
+    ((PieceType::Queen, PieceColor::Black), include_bytes!("../assets/chess/black_queen.png")),
+    ((PieceType::King, PieceColor::Black), include_bytes!("../assets/chess/black_king.png")),
+];
 
-pub struct AssetManager {
-    asset_dir: PathBuf,
+#[derive(Debug)]
+pub enum AssetError {
+    /// The piece set directory doesn't exist
+    NotFound(PathBuf),
+    /// The directory is missing some of the twelve piece images
+    Incomplete { asset_dir: PathBuf, missing: Vec<String> },
+    Io(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AssetError::NotFound(dir) => write!(f, "piece set directory {} not found", dir.display()),
+            AssetError::Incomplete { asset_dir, missing } => {
+                write!(f, "piece set {} is missing {}", asset_dir.display(), missing.join(", "))
+            }
+            AssetError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {}
+
+pub struct PieceSet {
+    pub name: String,
+    /// `None` for the piece set built into the binary
+    asset_dir: Option<PathBuf>,
     piece_images: HashMap<(PieceType, PieceColor), Vec<u8>>,
 }
 
-impl AssetManager {
-    pub fn new(asset_dir: PathBuf) -> Self {
-        let mut this = Self { asset_dir, piece_images: HashMap::new() };
+impl PieceSet {
+    /// Reads a piece set of `<color>_<piece>.png` files, e.g. `white_knight.png`
+    pub fn from_dir(asset_dir: PathBuf) -> Result<Self, AssetError> {
+        if !asset_dir.is_dir() {
+            return Err(AssetError::NotFound(asset_dir));
+        }
         let mut piece_images = HashMap::new();
+        let mut missing = vec![];
         for piece_type in all::<PieceType>() {
             if piece_type == PieceType::Empty {
                 continue;
             }
             for color in all::<PieceColor>() {
-                let file_name = this.file_name(piece_type, color);
-                // Open files as bytes
-                let file = std::fs::File::open(&file_name).unwrap();
-                let bytes = file.bytes().map(|v| v.unwrap()).collect::<Vec<_>>();
-                piece_images.insert((piece_type, color), bytes);
+                let file_name = asset_dir.join(Self::file_name(piece_type, color));
+                match std::fs::read(&file_name) {
+                    Ok(bytes) => {
+                        piece_images.insert((piece_type, color), bytes);
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => missing.push(Self::file_name(piece_type, color)),
+                    Err(e) => return Err(AssetError::Io(file_name, e)),
+                }
             }
         }
-        this.piece_images = piece_images;
-        this
-    }   
-    pub fn image(&self, piece: &Piece, gfx: &impl Has<GraphicsContext>) -> Image {
-        let bytes = self.piece_images.get(&(piece.piece_type, piece.color)).unwrap();
-        Image::from_bytes(gfx, bytes).unwrap()
+        if !missing.is_empty() {
+            return Err(AssetError::Incomplete { asset_dir, missing });
+        }
+        let name = asset_dir.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| asset_dir.display().to_string());
+        Ok(Self { name, asset_dir: Some(asset_dir), piece_images })
+    }
+    /// The default piece set, compiled into the binary
+    pub fn embedded() -> Self {
+        let piece_images = EMBEDDED.iter().map(|(key, bytes)| (*key, bytes.to_vec())).collect();
+        Self { name: "default".to_string(), asset_dir: None, piece_images }
     }
-    fn file_name(&self, piece_type: PieceType, color: PieceColor) -> PathBuf {
+    pub fn asset_dir(&self) -> Option<&PathBuf> {
+        self.asset_dir.as_ref()
+    }
+    fn file_name(piece_type: PieceType, color: PieceColor) -> String {
         let color_str = match color {
             PieceColor::White => "white",
             PieceColor::Black => "black",
         };
-        let filename = format!("{}_{:#}.png", color_str, piece_type);
-        self.asset_dir.join(filename)
+        format!("{}_{:#}.png", color_str, piece_type)
+    }
+}
+
+pub struct AssetManager {
+    sets: Vec<PieceSet>,
+    current: usize,
+    /// Decoded images, created once per piece set and reused every frame
+    images: HashMap<(usize, PieceType, PieceColor), Image>,
+}
+
+impl AssetManager {
+    /// The embedded set plus the one in `asset_dir`, which is selected
+    pub fn new(asset_dir: PathBuf) -> Result<Self, AssetError> {
+        Self::load(vec![asset_dir])
+    }
+    pub fn embedded() -> Self {
+        Self { sets: vec![PieceSet::embedded()], current: 0, images: HashMap::new() }
     }
-}
\ No newline at end of file
+    /// The embedded set plus each of `asset_dirs`, or of the `CHESS_ASSETS` directories when none are given.
+    /// The first extra set starts selected.
+    pub fn load(asset_dirs: Vec<PathBuf>) -> Result<Self, AssetError> {
+        let asset_dirs = if asset_dirs.is_empty() {
+            std::env::var_os(ASSETS_ENV).map(|dirs| std::env::split_paths(&dirs).collect()).unwrap_or_default()
+        } else {
+            asset_dirs
+        };
+        let mut assets = Self::embedded();
+        for asset_dir in asset_dirs {
+            assets.sets.push(PieceSet::from_dir(asset_dir)?);
+        }
+        if assets.sets.len() > 1 {
+            assets.current = 1;
+        }
+        Ok(assets)
+    }
+    pub fn sets(&self) -> &[PieceSet] {
+        &self.sets
+    }
+    pub fn current_set(&self) -> &PieceSet {
+        &self.sets[self.current]
+    }
+    pub fn add_set(&mut self, set: PieceSet) -> usize {
+        self.sets.push(set);
+        self.sets.len() - 1
+    }
+    pub fn select(&mut self, index: usize) {
+        if index < self.sets.len() {
+            self.current = index;
+        }
+    }
+    /// Switches to the next piece set, returning its name
+    pub fn next_set(&mut self) -> &str {
+        self.current = (self.current + 1) % self.sets.len();
+        &self.sets[self.current].name
+    }
+    /// Decodes the current piece set, if it hasn't been already
+    pub fn prepare(&mut self, gfx: &impl Has<GraphicsContext>) -> GameResult {
+        let set = &self.sets[self.current];
+        for (&(piece_type, color), bytes) in set.piece_images.iter() {
+            if let Entry::Vacant(entry) = self.images.entry((self.current, piece_type, color)) {
+                entry.insert(Image::from_bytes(gfx, bytes)?);
+            }
+        }
+        Ok(())
+    }
+    /// Image for `piece` in the current set, once `prepare` has run
+    pub fn image(&self, piece: &Piece) -> Option<&Image> {
+        self.images.get(&(self.current, piece.piece_type, piece.color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_set_is_complete() {
+        let set = PieceSet::embedded();
+        assert_eq!(set.piece_images.len(), 12);
+        assert!(set.asset_dir().is_none());
+    }
+
+    #[test]
+    fn test_asset_dir() {
+        let assets = AssetManager::new(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/chess"))).unwrap();
+        assert_eq!(assets.sets().len(), 2);
+        assert_eq!(assets.current_set().name, "chess");
+        assert_eq!(assets.current_set().piece_images.len(), 12);
+        assert!(matches!(AssetManager::new(PathBuf::from("does/not/exist")), Err(AssetError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_next_set() {
+        let mut assets = AssetManager::embedded();
+        let index = assets.add_set(PieceSet::from_dir(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/chess"))).unwrap());
+        assert_eq!(index, 1);
+        assert_eq!(assets.next_set(), "chess");
+        assert_eq!(assets.next_set(), "default");
+    }
+
+    #[test]
+    fn test_incomplete_asset_dir() {
+        let dir = std::env::temp_dir().join(format!("chess-assets-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("white_king.png"), include_bytes!("../assets/chess/white_king.png")).unwrap();
+        let result = PieceSet::from_dir(dir.clone());
+        std::fs::remove_dir_all(&dir).unwrap();
+        match result {
+            Err(AssetError::Incomplete { missing, .. }) => {
+                assert_eq!(missing.len(), 11);
+                assert!(missing.contains(&"black_queen.png".to_string()));
+            }
+            _ => panic!("expected an incomplete piece set"),
+        }
+    }
+}
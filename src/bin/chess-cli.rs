@@ -0,0 +1,13 @@
+use std::io;
+
+use chess_engine::cli;
+
+fn main() {
+    env_logger::init();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let stdin = io::stdin();
+    if let Err(e) = cli::run(&args, stdin.lock(), &mut io::stdout().lock()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
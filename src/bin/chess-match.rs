@@ -0,0 +1,12 @@
+use std::io;
+
+use chess_engine::tournament;
+
+fn main() {
+    env_logger::init();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Err(e) = tournament::run(&args, &mut io::stdout().lock()) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,64 @@
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use chess_engine::{board::Board, book::Book, pieces::Color, search::Limits, tui::Tui, variant};
+
+const USAGE: &str = "Usage: chess-tui [--engine white|black] [--depth N | --time SECONDS] [--variant NAME] [--fen FEN | --chess960 N|random] [--book FILE.bin] [--flip] [--no-color] [--ascii]";
+
+fn main() -> Result<(), String> {
+    env_logger::init();
+    let mut tui = Tui::new();
+    // Plain output when piped or when the terminal asks for it
+    tui.style.color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let mut fen = None;
+    let mut rules = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE));
+        match arg.as_str() {
+            "--engine" => {
+                tui.engine = match value()?.as_str() {
+                    "white" | "w" => Some(Color::White),
+                    "black" | "b" => Some(Color::Black),
+                    "off" | "none" => None,
+                    side => return Err(format!("Unknown side {}\n{}", side, USAGE)),
+                }
+            }
+            "--depth" => tui.limits = Limits::depth(value()?.parse().map_err(|_| format!("Invalid depth\n{}", USAGE))?),
+            "--time" => {
+                let secs = value()?.parse::<f64>().map_err(|_| format!("Invalid time\n{}", USAGE))?;
+                tui.limits = Limits::time(Duration::from_secs_f64(secs));
+            }
+            "--fen" => fen = Some(value()?),
+            "--variant" => {
+                let name = value()?;
+                rules = Some(variant::from_name(&name).ok_or_else(|| format!("Unknown variant {}\n{}", name, USAGE))?);
+            }
+            "--chess960" => {
+                tui.board = match value()?.as_str() {
+                    "random" => Board::random_chess960().1,
+                    index => Board::chess960(index.parse().ok().filter(|index| *index < 960).ok_or_else(|| format!("Chess960 positions are numbered 0 to 959\n{}", USAGE))?),
+                }
+            }
+            "--book" => tui.book = Some(Book::open(value()?).map_err(|e| e.to_string())?),
+            "--flip" => tui.style.flipped = true,
+            "--no-color" => tui.style.color = false,
+            "--ascii" => tui.style.unicode = false,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(format!("Unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+
+    // The FEN is read by the variant's rules, whichever order they were given in
+    match (fen, rules) {
+        (Some(fen), rules) => tui.board = Board::from_fen_variant(&fen, rules.unwrap_or(tui.board.variant)).map_err(|e| format!("Invalid FEN: {}", e))?,
+        (None, Some(rules)) => tui.board = Board::new_variant(rules),
+        (None, None) => (),
+    }
+
+    let stdin = io::stdin();
+    tui.run(stdin.lock(), &mut io::stdout()).map_err(|e| e.to_string())
+}
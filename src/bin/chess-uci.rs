@@ -0,0 +1,9 @@
+use std::io;
+
+use chess_engine::uci::Uci;
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let stdin = io::stdin();
+    Uci::new(io::stdout()).run(stdin.lock())
+}
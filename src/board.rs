@@ -1,8 +1,78 @@
 use crate::{
-    coords::{Coords, MoveCoords, KING_SIDE_BLACK_ROOK, KING_SIDE_WHITE_ROOK, QUEEN_SIDE_BLACK_ROOK, QUEEN_SIDE_WHITE_ROOK},
+    chess_move::{Move, MoveList},
+    coords::{Coords, MoveCoords},
     pieces::{Color, Piece, PieceType},
+    variant::{Standard, Variant},
 };
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+    /// Files of the castling rooks in the same order, the a and h files except in Chess960
+    pub rook_files: [u8; 4],
+}
+
+impl CastlingRights {
+    pub fn all() -> Self {
+        CastlingRights { white_king_side: true, white_queen_side: true, black_king_side: true, black_queen_side: true, rook_files: [7, 0, 7, 0] }
+    }
+    pub fn none() -> Self {
+        CastlingRights { white_king_side: false, white_queen_side: false, black_king_side: false, black_queen_side: false, rook_files: [7, 0, 7, 0] }
+    }
+    pub fn get(&self, color: Color, king_side: bool) -> bool {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side,
+            (Color::White, false) => self.white_queen_side,
+            (Color::Black, true) => self.black_king_side,
+            (Color::Black, false) => self.black_queen_side,
+        }
+    }
+    pub fn set(&mut self, color: Color, king_side: bool, allowed: bool) {
+        match (color, king_side) {
+            (Color::White, true) => self.white_king_side = allowed,
+            (Color::White, false) => self.white_queen_side = allowed,
+            (Color::Black, true) => self.black_king_side = allowed,
+            (Color::Black, false) => self.black_queen_side = allowed,
+        }
+    }
+    fn index(color: Color, king_side: bool) -> usize {
+        (color == Color::Black) as usize * 2 + !king_side as usize
+    }
+    /// File of the rook `color` castles with on `king_side`
+    pub fn rook_file(&self, color: Color, king_side: bool) -> u8 {
+        self.rook_files[CastlingRights::index(color, king_side)]
+    }
+    pub fn set_rook_file(&mut self, color: Color, king_side: bool, file: u8) {
+        self.rook_files[CastlingRights::index(color, king_side)] = file;
+    }
+    /// Square the rook `color` castles with on `king_side` starts from
+    pub fn rook_square(&self, color: Color, king_side: bool) -> Coords {
+        Coords::new(self.rook_file(color, king_side), back_rank(color))
+    }
+    /// Drops the rights that depend on the rook which starts on `square`
+    pub(crate) fn touch(&mut self, square: Coords) {
+        for color in [Color::White, Color::Black] {
+            for king_side in [true, false] {
+                if square == self.rook_square(color, king_side) {
+                    self.set(color, king_side, false);
+                }
+            }
+        }
+    }
+}
+
+/// Row of `color`'s pieces at the start, 7 for White as rank 1 is the last row of `Board::state`
+pub fn back_rank(color: Color) -> u8 {
+    match color {
+        Color::White => 7,
+        Color::Black => 0,
+    }
+}
+
+#[derive(Clone)]
 pub struct Board {
     // ♜  ♞  ♝  ♛  ♚  ♝  ♞  ♜
     // ♟  ♟  ♟  ♟  ♟  ♟  ♟  ♟
@@ -16,6 +86,110 @@ pub struct Board {
     pub state: [[Piece; 8]; 8],
     pub turn: Color,
     pub moves: Vec<MoveCoords>,
+    /// Moves taken back with `undo_move`, the next one to redo last
+    pub undone: Vec<MoveCoords>,
+    pub castling: CastlingRights,
+    /// Square skipped by a pawn's double step on the last move
+    pub en_passant: Option<Coords>,
+    /// Half moves since the last capture or pawn move
+    pub halfmove_clock: u32,
+    pub fullmove_number: u32,
+    /// FEN the game started from, `None` for the standard start position
+    pub start_fen: Option<String>,
+    /// Rules of the game, standard chess unless set up with `new_variant` or `from_fen_variant`
+    pub variant: &'static dyn Variant,
+    /// Checks given by White and Black, for variants where they count
+    pub checks: [u8; 2],
+    /// Pieces White and Black hold in hand to drop, counted by `PieceType as usize`
+    pub pockets: [[u8; 6]; 2],
+    /// Squares of pieces which were pawns before promoting, by `Coords::index`, as they go back to the pocket as pawns
+    pub promoted: u64,
+}
+
+/// Why a requested move can't be played
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveError {
+    NoPiece(Coords),
+    /// The piece belongs to the side not to move
+    WrongTurn(Color),
+    IllegalMove { from: Coords, to: Coords },
+    LeavesKingInCheck,
+    /// A pawn reaching the last rank needs a piece to promote to
+    NeedsPromotion,
+    InvalidPromotion(PieceType),
+    /// The side to move has no such piece in hand, or it can't be dropped there
+    IllegalDrop { piece_type: PieceType, to: Coords },
+}
+
+impl std::fmt::Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MoveError::NoPiece(square) => write!(f, "There is no piece on {}", square),
+            MoveError::WrongTurn(color) => write!(f, "It is not {}'s turn", if *color == Color::White { "White" } else { "Black" }),
+            MoveError::IllegalMove { from, to } => write!(f, "The piece on {} can't move to {}", from, to),
+            MoveError::LeavesKingInCheck => write!(f, "That move leaves the king in check"),
+            MoveError::NeedsPromotion => write!(f, "Choose a piece to promote to"),
+            MoveError::InvalidPromotion(piece_type) => write!(f, "Can't promote to a {:#}", piece_type),
+            MoveError::IllegalDrop { piece_type, to } => write!(f, "Can't drop a {:#} on {}", piece_type, to),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Checkmate { winner: Color },
+    Timeout { winner: Color },
+    Stalemate,
+    /// Flag fall while the opponent has no mating material
+    TimeoutVsInsufficientMaterial,
+    /// Won or drawn by a variant's own rules
+    Variant { winner: Option<Color>, reason: &'static str },
+}
+
+impl Outcome {
+    pub fn winner(&self) -> Option<Color> {
+        match self {
+            Outcome::Checkmate { winner } | Outcome::Timeout { winner } => Some(*winner),
+            Outcome::Stalemate | Outcome::TimeoutVsInsufficientMaterial => None,
+            Outcome::Variant { winner, .. } => *winner,
+        }
+    }
+    /// PGN result tag
+    pub fn result(&self) -> &'static str {
+        match self.winner() {
+            Some(Color::White) => "1-0",
+            Some(Color::Black) => "0-1",
+            None => "1/2-1/2",
+        }
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = |color: &Color| match color {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        match self {
+            Outcome::Checkmate { winner } => write!(f, "{} wins by checkmate", name(winner)),
+            Outcome::Timeout { winner } => write!(f, "{} wins on time", name(winner)),
+            Outcome::Stalemate => write!(f, "Draw by stalemate"),
+            Outcome::TimeoutVsInsufficientMaterial => write!(f, "Draw, timeout vs insufficient material"),
+            Outcome::Variant { winner: Some(winner), reason } => write!(f, "{} wins, {}", name(winner), reason),
+            Outcome::Variant { winner: None, reason } => write!(f, "Draw, {}", reason),
+        }
+    }
+}
+
+/// Files the king and rook end on after castling on `king_side`
+pub fn castling_files(king_side: bool) -> (u8, u8) {
+    if king_side {
+        (6, 5)
+    } else {
+        (2, 3)
+    }
 }
 
 impl Board {
@@ -53,7 +227,70 @@ impl Board {
             state,
             turn: Color::White,
             moves: vec![],
+            undone: vec![],
+            castling: CastlingRights::all(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
+            start_fen: None,
+            variant: &Standard,
+            checks: [0, 0],
+            pockets: [[0; 6]; 2],
+            promoted: 0,
+        }
+    }
+    /// Checkmate, stalemate or a variant's win condition in the current position
+    pub fn outcome(&self) -> Option<Outcome> {
+        if let Some(outcome) = self.variant.outcome(self) {
+            return Some(outcome);
+        }
+        if !self.legal_moves().is_empty() {
+            return None;
         }
+        Some(self.variant.no_moves(self))
+    }
+    /// Result of `flagged` running out of time in the current position
+    pub fn timeout(&self, flagged: Color) -> Outcome {
+        if self.has_mating_material(flagged.opposite()) {
+            Outcome::Timeout { winner: flagged.opposite() }
+        } else {
+            Outcome::TimeoutVsInsufficientMaterial
+        }
+    }
+    /// Anything beyond a lone king or king and single minor piece
+    pub fn has_mating_material(&self, color: Color) -> bool {
+        let pieces = self.state.iter().flatten().filter(|p| p.color == color && p.piece_type != PieceType::Empty && p.piece_type != PieceType::King);
+        let mut minors = 0;
+        for piece in pieces {
+            match piece.piece_type {
+                PieceType::Knight | PieceType::Bishop => minors += 1,
+                _ => return true,
+            }
+        }
+        minors > 1
+    }
+    pub fn replay(&self, ply: usize) -> Board {
+        let mut board = match &self.start_fen {
+            Some(fen) => Board::from_fen_variant(fen, self.variant).expect("start position was parsed before"),
+            None => Board::new_variant(self.variant),
+        };
+        for mc in self.moves.iter().take(ply) {
+            board.apply(mc);
+        }
+        board
+    }
+    pub fn undo_move(&mut self) -> Option<MoveCoords> {
+        let mc = *self.moves.last()?;
+        let mut undone = std::mem::take(&mut self.undone);
+        *self = self.replay(self.moves.len() - 1);
+        undone.push(mc);
+        self.undone = undone;
+        Some(mc)
+    }
+    pub fn redo_move(&mut self) -> Option<MoveCoords> {
+        let mc = self.undone.pop()?;
+        self.apply(&mc);
+        Some(mc)
     }
     pub fn piece_present(&self, piece: Piece, coord: impl Into<Coords>) -> bool {
         let coord: Coords = coord.into();
@@ -68,70 +305,39 @@ impl Board {
         let to: Coords = to.into();
         self.possible_moves(from).iter().any(|mc| mc.to == to && mc.promotion.is_some())
     }
-    fn can_take(&self, from: impl Into<Coords>, to: impl Into<Coords>) -> bool {
-        let to: Coords = to.into();
-        let moves = self.possible_moves(from);
-        moves.iter().any(|mc| mc.to == to && mc.takes)
-    }
+    /// Whether `color` may castle on `king_side` now. The king ends on the g or c file and the rook next to it
+    /// on the inside, as in Chess960, so every square either crosses must be empty but for the two of them
     pub fn can_castle(&self, color: Color, king_side: bool) -> bool {
-        if self.king_has_moved(color) {
+        if !self.castling.get(color, king_side) {
             return false;
         }
-        if self.rook_has_moved(color, king_side) {
+        let y = back_rank(color);
+        let Some(king_x) = (0..8).find(|x| self.get_piece(Coords::new(*x, y)) == Piece { piece_type: PieceType::King, color }) else {
             return false;
-        }
-        let y = match color {
-            Color::White => 7,
-            Color::Black => 0,
         };
-        let x = match king_side {
-            true => 7,
-            false => 0,
-        };
-        let rook = self.state[y][x];
-        if rook.piece_type != PieceType::Rook {
+        let rook_x = self.castling.rook_file(color, king_side);
+        if self.get_piece(Coords::new(rook_x, y)) != (Piece { piece_type: PieceType::Rook, color }) {
             // Should never happen
-            println!("Rook not present but hasn't moved");
+            log::warn!("Rook not present but hasn't moved");
             return false;
         }
-        let mut x = match king_side {
-            true => 6,
-            false => 1,
-        };
-        while x != 4 {
-            if !self.is_empty(Coords::new(x, y as u8)) {
-                return false;
-            }
-            x = match king_side {
-                true => x - 1,
-                false => x + 1,
-            };
+        let (king_to, rook_to) = castling_files(king_side);
+        let span = |a: u8, b: u8| a.min(b)..=a.max(b);
+        if span(king_x, king_to).chain(span(rook_x, rook_to)).any(|x| x != king_x && x != rook_x && !self.is_empty(Coords::new(x, y))) {
+            return false;
         }
-        true
+        // The king may not castle out of or through check
+        !span(king_x, king_to).any(|x| self.is_attacked(Coords::new(x, y), color.opposite()))
     }
 
-    pub fn king_has_moved(&self, color: Color) -> bool {
-        self.moves.iter().any(|mc| mc.piece.piece_type == PieceType::King && mc.piece.color == color)
-    }
-
-    pub fn rook_has_moved(&self, color: Color, king_side: bool) -> bool {
-        match color {
-            Color::White => {
-                if king_side {
-                    self.moves.iter().any(|mc| mc.piece.piece_type == PieceType::Rook && mc.piece.color == color && mc.from == KING_SIDE_WHITE_ROOK)
-                } else {
-                    self.moves.iter().any(|mc| mc.piece.piece_type == PieceType::Rook && mc.piece.color == color && mc.from == QUEEN_SIDE_WHITE_ROOK)
-                }
-            }
-            Color::Black => {
-                if king_side {
-                    self.moves.iter().any(|mc| mc.piece.piece_type == PieceType::Rook && mc.piece.color == color && mc.from == KING_SIDE_BLACK_ROOK)
-                } else {
-                    self.moves.iter().any(|mc| mc.piece.piece_type == PieceType::Rook && mc.piece.color == color && mc.from == QUEEN_SIDE_BLACK_ROOK)
-                }
-            }
+    /// Square of the rook taking part in `mc`, if it castles
+    pub fn castling_rook(&self, mc: &MoveCoords) -> Option<Coords> {
+        if !mc.king_side_castle && !mc.queen_side_castle {
+            return None;
         }
+        Some(self.castling.rook_square(mc.piece.color, mc.king_side_castle))
     }
+
     pub fn get_piece(&self, coord: impl Into<Coords>) -> Piece {
         let coord: Coords = coord.into();
         let x = coord.x as usize;
@@ -159,7 +365,7 @@ impl Board {
         }
         Some(self.state[y][x].color)
     }
-    fn possible_moves(&self, coord: impl Into<Coords>) -> Vec<MoveCoords> {
+    pub(crate) fn possible_moves(&self, coord: impl Into<Coords>) -> Vec<MoveCoords> {
         let coord: Coords = coord.into();
         let x = coord.x;
         let y = coord.y;
@@ -174,69 +380,42 @@ impl Board {
             _ => vec![],
         }
     }
-    fn valid_move(&self, mc: &MoveCoords) -> bool {
-        // Check if the piece is present
-        if !self.piece_present(mc.piece, mc.from) {
-            return false;
-        }
-        println!("Piece present at from");
-        // Check if it's the correct turn
-        if mc.piece.color != self.turn {
-            return false;
-        }
-        
-        self.possible_moves(mc.from).iter().any(|m| m == mc)
-    }
-
-    fn parse_request(&self, from: impl Into<Coords>, to: impl Into<Coords>, promotion: Option<PieceType>) -> Option<MoveCoords> {
+    /// Checks that moving `from` to `to` is legal for the side to move, the promotion piece being needed only when a pawn reaches the last rank
+    pub fn legal_move(&self, from: impl Into<Coords>, to: impl Into<Coords>, promotion: Option<PieceType>) -> Result<MoveCoords, MoveError> {
         let from: Coords = from.into();
         let to: Coords = to.into();
         let piece = self.get_piece(from);
-        let dx = to.x as i8 - from.x as i8;
-        let color = piece.color;
-        if dx == 2 && self.can_castle(color, true) {
-            return Some(MoveCoords {
-                piece,
-                from,
-                to,
-                takes: false,
-                king_side_castle: true,
-                queen_side_castle: false,
-                promotion: None,
-            });
-        } else if dx == -2 && self.can_castle(color, false) {
-            return Some(MoveCoords {
-                piece,
-                from,
-                to,
-                takes: false,
-                king_side_castle: false,
-                queen_side_castle: true,
-                promotion: None,
-            });
+        if piece.piece_type == PieceType::Empty {
+            return Err(MoveError::NoPiece(from));
         }
-        
-        let takes = self.can_take(from, to);
-        let promotion = if self.can_promote(from) {
-            promotion
-        } else {
-            None
+        if piece.color != self.turn {
+            return Err(MoveError::WrongTurn(piece.color));
+        }
+        // Dropping the king on its own rook castles with it, as Chess960 GUIs do
+        let candidates = self.possible_moves(from).into_iter().filter(|mc| mc.to == to || self.castling_rook(mc) == Some(to)).collect::<Vec<_>>();
+        let mc = match (candidates.first(), promotion) {
+            (None, _) => return Err(MoveError::IllegalMove { from, to }),
+            (Some(mc), _) if mc.promotion.is_none() => *mc,
+            (Some(_), None) => return Err(MoveError::NeedsPromotion),
+            (Some(_), Some(promotion)) => *candidates.iter().find(|mc| mc.promotion == Some(promotion)).ok_or(MoveError::InvalidPromotion(promotion))?,
         };
-        Some(MoveCoords {
-            piece,
-            from,
-            to,
-            takes,
-            promotion,
-            ..Default::default()
-        })
+        let mv = self.encode_move(&mc);
+        let mut after = self.position();
+        after.make_move(mv);
+        if !self.variant.is_legal(self, mv, &after) {
+            return Err(MoveError::LeavesKingInCheck);
+        }
+        // Variants may rule out moves which are fine on their own, as when captures are forced
+        let mut legal = MoveList::new();
+        self.generate_legal_moves(&mut legal);
+        if !legal.contains(&mv) {
+            return Err(MoveError::IllegalMove { from, to });
+        }
+        Ok(mc)
     }
 
     fn toggle_turn(&mut self) {
-        self.turn = match self.turn {
-            Color::White => Color::Black,
-            Color::Black => Color::White,
-        };
+        self.turn = self.turn.opposite();
     }
 
     fn move_piece_unchecked(&mut self, from: impl Into<Coords>, to: impl Into<Coords>, promotion: Option<PieceType>, castle_king_side: bool, castle_queen_side: bool) {
@@ -246,56 +425,103 @@ impl Board {
         if let Some(promotion) = promotion {
             from_piece.piece_type = promotion;
         }
-        match (from_piece.color, castle_king_side, castle_queen_side) {
-            (Color::White, true, false) => {
-                self.state[7][4] = Piece::empty();
-                self.state[7][5] = Piece::white(PieceType::Rook);
-                self.state[7][6] = Piece::white(PieceType::King);
-                self.state[7][7] = Piece::empty();
-            }
-            (Color::White, false, true) => {
-                self.state[7][0] = Piece::empty();
-                self.state[7][1] = Piece::empty();
-                self.state[7][2] = Piece::white(PieceType::King);
-                self.state[7][3] = Piece::white(PieceType::Rook);
-                self.state[7][4] = Piece::empty();
-            }
-            (Color::Black, true, false) => {
-                self.state[0][4] = Piece::empty();
-                self.state[0][5] = Piece::black(PieceType::Rook);
-                self.state[0][6] = Piece::black(PieceType::King);
-                self.state[0][7] = Piece::empty();
-            }
-            (Color::Black, false, true) => {
-                self.state[0][0] = Piece::empty();
-                self.state[0][1] = Piece::empty();
-                self.state[0][2] = Piece::black(PieceType::King);
-                self.state[0][3] = Piece::black(PieceType::Rook);
-                self.state[0][4] = Piece::empty();
-            },
-            (_, false, false) => {
-                self.state[to.y as usize][to.x as usize] = from_piece;
-                self.state[from.y as usize][from.x as usize] = Piece::empty();
-            }
-            _ => (),
+        if castle_king_side || castle_queen_side {
+            // Lift both pieces first, as in Chess960 either may land where the other stood
+            let color = from_piece.color;
+            let y = from.y as usize;
+            let rook_x = self.castling.rook_file(color, castle_king_side) as usize;
+            let (king_to, rook_to) = castling_files(castle_king_side);
+            self.state[y][from.x as usize] = Piece::empty();
+            self.state[y][rook_x] = Piece::empty();
+            self.state[y][king_to as usize] = from_piece;
+            self.state[y][rook_to as usize] = Piece { piece_type: PieceType::Rook, color };
+        } else {
+            self.state[to.y as usize][to.x as usize] = from_piece;
+            self.state[from.y as usize][from.x as usize] = Piece::empty();
         }
         self.toggle_turn();
     }
 
-    pub fn move_piece(&mut self, from: impl Into<Coords>, to: impl Into<Coords>, promotion: Option<PieceType>) -> bool {
-        let from: Coords = from.into();
-        let to: Coords = to.into();
-        let mc = match self.parse_request(from, to, promotion) {
-            Some(mc) => mc,
-            None => return false,
+    /// Plays `mv` without recording it in the game history
+    pub fn make_move(&mut self, mv: Move) {
+        let (from, to) = (mv.from(), mv.to());
+        if let Some(piece_type) = mv.dropped() {
+            self.drop_unchecked(piece_type, to);
+            let variant = self.variant;
+            variant.after_move(self, mv, Piece::empty());
+            return;
+        }
+        let piece = self.get_piece(from);
+        let captured = if mv.is_en_passant() {
+            Piece { piece_type: PieceType::Pawn, color: piece.color.opposite() }
+        } else if mv.is_capture() {
+            self.get_piece(to)
+        } else {
+            Piece::empty()
         };
-        println!("{:?}", mc);
-        if !self.valid_move(&mc) {
-            return false;
+        if piece.piece_type == PieceType::Pawn || mv.is_capture() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if piece.color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        if mv.is_en_passant() {
+            self.state[from.y as usize][to.x as usize] = Piece::empty();
+        }
+        self.en_passant = if mv.is_double_push() {
+            Some(Coords::new(from.x, (from.y + to.y) / 2))
+        } else {
+            None
+        };
+        if piece.piece_type == PieceType::King {
+            self.castling.set(piece.color, true, false);
+            self.castling.set(piece.color, false, false);
         }
-        self.move_piece_unchecked(from, to, promotion, mc.king_side_castle, mc.queen_side_castle);
-        self.moves.push(mc);
-        true
+        self.castling.touch(from);
+        self.castling.touch(to);
+        self.move_piece_unchecked(from, to, mv.promotion(), mv.is_king_side_castle(), mv.is_queen_side_castle());
+        let variant = self.variant;
+        variant.after_move(self, mv, captured);
+    }
+
+    pub(crate) fn apply(&mut self, mc: &MoveCoords) {
+        self.make_move(self.encode_move(mc));
+        self.moves.push(*mc);
+    }
+
+    /// A copy of the current position without the game history, cheap to clone while searching
+    pub fn position(&self) -> Board {
+        Board {
+            state: self.state,
+            turn: self.turn,
+            moves: vec![],
+            undone: vec![],
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            start_fen: None,
+            variant: self.variant,
+            checks: self.checks,
+            pockets: self.pockets,
+            promoted: self.promoted,
+        }
+    }
+
+    pub fn move_piece(&mut self, from: impl Into<Coords>, to: impl Into<Coords>, promotion: Option<PieceType>) -> Result<MoveCoords, MoveError> {
+        let mc = self.legal_move(from, to, promotion)?;
+        log::debug!("{:?}", mc);
+        self.apply(&mc);
+        self.undone.clear();
+        Ok(mc)
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -364,6 +590,67 @@ mod tests {
         
     }
 
+    #[test]
+    fn test_outcome() {
+        let mut board = Board::new();
+        assert_eq!(board.outcome(), None);
+        assert_eq!(board.timeout(Color::White), Outcome::Timeout { winner: Color::Black });
+        for (from, to) in [("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")] {
+            assert!(board.move_piece(from.parse::<Coords>().unwrap(), to.parse::<Coords>().unwrap(), None).is_ok());
+        }
+        assert_eq!(board.outcome(), Some(Outcome::Checkmate { winner: Color::Black }));
+        assert_eq!(board.outcome().unwrap().result(), "0-1");
+
+        let mut board = Board::new();
+        board.state[1] = [Piece::empty(); 8];
+        board.state[0] = [Piece::empty(); 8];
+        board.state[0][4] = Piece::black(PieceType::King);
+        board.state[0][1] = Piece::black(PieceType::Knight);
+        assert!(!board.has_mating_material(Color::Black));
+        assert_eq!(board.timeout(Color::White), Outcome::TimeoutVsInsufficientMaterial);
+    }
+
+    #[test]
+    fn test_move_errors() {
+        let square = |s: &str| s.parse::<Coords>().unwrap();
+        let mut board = Board::new();
+        assert_eq!(board.move_piece(square("e4"), square("e5"), None), Err(MoveError::NoPiece(square("e4"))));
+        assert_eq!(board.move_piece(square("e7"), square("e5"), None), Err(MoveError::WrongTurn(Color::Black)));
+        assert_eq!(board.move_piece(square("e2"), square("e5"), None), Err(MoveError::IllegalMove { from: square("e2"), to: square("e5") }));
+        assert!(board.moves.is_empty());
+
+        let mut board = Board::from_fen("4k3/1P6/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.move_piece(square("b7"), square("b8"), Some(PieceType::Queen)), Err(MoveError::LeavesKingInCheck));
+        let mut board = Board::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.move_piece(square("b7"), square("b8"), None), Err(MoveError::NeedsPromotion));
+        assert_eq!(board.move_piece(square("b7"), square("b8"), Some(PieceType::King)), Err(MoveError::InvalidPromotion(PieceType::King)));
+        let mc = board.move_piece(square("b7"), square("b8"), Some(PieceType::Rook)).unwrap();
+        assert_eq!(mc.promotion, Some(PieceType::Rook));
+        assert!(board.piece_present(Piece::white(PieceType::Rook), square("b8")));
+        assert_eq!(MoveError::InvalidPromotion(PieceType::King).to_string(), "Can't promote to a king");
+    }
+
+    #[test]
+    fn test_undo_redo() {
+        let mut board = Board::new();
+        assert!(board.undo_move().is_none());
+        assert!(board.move_piece("e2".parse::<Coords>().unwrap(), "e4".parse::<Coords>().unwrap(), None).is_ok());
+        assert!(board.move_piece("e7".parse::<Coords>().unwrap(), "e5".parse::<Coords>().unwrap(), None).is_ok());
+        let mc = board.undo_move().unwrap();
+        assert_eq!(mc.to, "e5".parse().unwrap());
+        assert_eq!(board.turn, Color::Black);
+        assert!(board.piece_present(Piece::black(PieceType::Pawn), "e7".parse::<Coords>().unwrap()));
+        board.undo_move();
+        assert!(board.moves.is_empty());
+        assert_eq!(board.undone.len(), 2);
+        let next = *board.undone.last().unwrap();
+        assert_eq!(board.redo_move(), Some(next));
+        assert!(board.piece_present(Piece::white(PieceType::Pawn), "e4".parse::<Coords>().unwrap()));
+        // A new move drops the rest of the redo history
+        assert!(board.move_piece("c7".parse::<Coords>().unwrap(), "c5".parse::<Coords>().unwrap(), None).is_ok());
+        assert!(board.redo_move().is_none());
+    }
+
     #[test]
     fn test_piece_present() {
         let board = Board::new();
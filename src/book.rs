@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    board::Board,
+    coords::{Coords, MoveCoords},
+    pgn::Pgn,
+    pieces::{Color, PieceType},
+};
+
+/// Bytes in one book entry: key, move, weight and learn value, all big endian
+const ENTRY_SIZE: usize = 16;
+
+/// One move for a position in a Polyglot book
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    /// Polyglot move: to file and row, from file and row, then the promotion piece, three bits each
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+#[derive(Debug)]
+pub enum BookError {
+    Io(PathBuf, std::io::Error),
+    /// The file isn't a whole number of entries, holding this many bytes
+    Truncated(usize),
+}
+
+impl std::fmt::Display for BookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BookError::Io(path, e) => write!(f, "could not access {}: {}", path.display(), e),
+            BookError::Truncated(len) => write!(f, "book size of {} bytes isn't a multiple of {}", len, ENTRY_SIZE),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// How to pick among the book moves of a position
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum BookSelection {
+    /// At random, in proportion to the weights, so games vary
+    #[default]
+    Weighted,
+    /// Always the highest weight
+    Best,
+}
+
+/// A Polyglot opening book, entries sorted by position key
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book, BookError> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(BookError::Truncated(bytes.len()));
+        }
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| BookEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+        // Books should already be sorted, but lookups rely on it
+        entries.sort_by_key(|entry| entry.key);
+        Ok(Book { entries })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Book, BookError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| BookError::Io(path.to_path_buf(), e))?;
+        Book::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_SIZE);
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.mv.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BookError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes()).map_err(|e| BookError::Io(path.to_path_buf(), e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries for the position with Polyglot key `key`
+    pub fn entries(&self, key: u64) -> &[BookEntry] {
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let end = self.entries.partition_point(|entry| entry.key <= key);
+        &self.entries[start..end]
+    }
+
+    /// Book moves for the position with their weights, skipping any which aren't legal there
+    pub fn moves(&self, board: &Board) -> Vec<(MoveCoords, u16)> {
+        self.entries(board.polyglot_key())
+            .iter()
+            .filter_map(|entry| decode_move(board, entry.mv).map(|mc| (mc, entry.weight)))
+            .collect()
+    }
+
+    /// A book move for the position, if the book has one with a non-zero weight
+    pub fn choose(&self, board: &Board, selection: BookSelection) -> Option<MoveCoords> {
+        let moves = self.moves(board).into_iter().filter(|(_, weight)| *weight > 0).collect::<Vec<_>>();
+        match selection {
+            BookSelection::Best => moves.iter().max_by_key(|(_, weight)| *weight).map(|(mc, _)| *mc),
+            BookSelection::Weighted => {
+                let total = moves.iter().map(|(_, weight)| *weight as u32).sum::<u32>();
+                if total == 0 {
+                    return None;
+                }
+                let mut pick = fastrand::u32(0..total);
+                for (mc, weight) in moves {
+                    if pick < weight as u32 {
+                        return Some(mc);
+                    }
+                    pick -= weight as u32;
+                }
+                None
+            }
+        }
+    }
+
+    /// Builds a book from the first `max_plies` moves of each game. Moves score 2 for every game won
+    /// by the side playing them, 1 for draws and unfinished games and nothing for losses
+    pub fn from_games(games: &[Pgn], max_plies: usize) -> Book {
+        let mut scores: HashMap<(u64, u16), u64> = HashMap::new();
+        for pgn in games {
+            let mut board = pgn.board.replay(0);
+            for mc in pgn.board.moves.iter().take(max_plies) {
+                let score = match (pgn.result(), mc.piece.color) {
+                    ("1-0", Color::White) | ("0-1", Color::Black) => 2,
+                    ("1-0", _) | ("0-1", _) => 0,
+                    _ => 1,
+                };
+                *scores.entry((board.polyglot_key(), encode_move(mc))).or_default() += score;
+                board.apply(mc);
+            }
+        }
+        // Scale the weights down to fit 16 bits, keeping every scoring move in the book
+        let max = scores.values().copied().max().unwrap_or(0);
+        let mut entries = scores
+            .into_iter()
+            .filter(|(_, score)| *score > 0)
+            .map(|((key, mv), score)| {
+                let weight = if max > u16::MAX as u64 { (score * u16::MAX as u64 / max).max(1) } else { score };
+                BookEntry { key, mv, weight: weight as u16, learn: 0 }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.key.cmp(&b.key).then(b.weight.cmp(&a.weight)).then(a.mv.cmp(&b.mv)));
+        Book { entries }
+    }
+}
+
+/// Polyglot rows count from rank 1 and castling is written as the king taking its own rook
+pub fn encode_move(mc: &MoveCoords) -> u16 {
+    let to = if mc.king_side_castle {
+        Coords::new(7, mc.to.y)
+    } else if mc.queen_side_castle {
+        Coords::new(0, mc.to.y)
+    } else {
+        mc.to
+    };
+    let promotion = match mc.promotion {
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+        _ => 0,
+    };
+    let square = |coords: Coords| (7 - coords.y as u16) << 3 | coords.x as u16;
+    square(to) | square(mc.from) << 6 | promotion << 12
+}
+
+/// The legal move in `board` written as Polyglot move `mv`
+pub fn decode_move(board: &Board, mv: u16) -> Option<MoveCoords> {
+    board.legal_moves().into_iter().find(|mc| encode_move(mc) == mv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = "\
+[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 1-0
+
+[Result \"1/2-1/2\"]
+
+1. e4 c5 2. Nf3 1/2-1/2
+
+[Result \"0-1\"]
+
+1. d4 d5 0-1
+";
+
+    #[test]
+    fn test_polyglot_moves() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let castle = board.parse_move("O-O").unwrap();
+        // e1h1
+        assert_eq!(encode_move(&castle), 4 << 6 | 7);
+        assert_eq!(decode_move(&board, 4 << 6 | 7), Some(castle));
+        assert_eq!(decode_move(&board, 4 << 6), board.parse_move("O-O-O"));
+        let promotion = Board::from_fen("8/P6k/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        let mc = promotion.parse_move("a8=N").unwrap();
+        assert_eq!(decode_move(&promotion, encode_move(&mc)), Some(mc));
+        assert_eq!(decode_move(&Board::new(), 0), None);
+    }
+
+    #[test]
+    fn test_book_from_games() {
+        let games = Pgn::parse_all(GAMES).unwrap();
+        let book = Book::from_games(&games, 4);
+        let start = Board::new();
+        let mut moves = book.moves(&start).into_iter().map(|(mc, weight)| (mc.uci(), weight)).collect::<Vec<_>>();
+        moves.sort();
+        // 1. d4 only lost, so it stays out
+        assert_eq!(moves, vec![("e2e4".to_string(), 3)]);
+        assert_eq!(book.choose(&start, BookSelection::Best).unwrap().uci(), "e2e4");
+
+        // 1... e5 only lost, leaving 1... c5
+        let after_e4 = games[1].board.replay(1);
+        assert_eq!(book.moves(&after_e4).len(), 1);
+        for _ in 0..10 {
+            assert_eq!(book.choose(&after_e4, BookSelection::Weighted).unwrap().uci(), "c7c5");
+        }
+        // Beyond the first four plies the book is out
+        assert!(book.moves(&games[0].board.replay(4)).is_empty());
+        assert!(!Book::from_games(&games, 5).moves(&games[0].board.replay(4)).is_empty());
+    }
+
+    #[test]
+    fn test_book_bytes() {
+        let book = Book::from_games(&Pgn::parse_all(GAMES).unwrap(), 10);
+        let bytes = book.to_bytes();
+        assert_eq!(bytes.len(), book.len() * ENTRY_SIZE);
+        assert_eq!(Book::from_bytes(&bytes).unwrap(), book);
+        assert!(matches!(Book::from_bytes(&bytes[1..]), Err(BookError::Truncated(_))));
+        assert!(Book::open("no/such/book.bin").is_err());
+    }
+}
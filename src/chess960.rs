@@ -0,0 +1,164 @@
+use crate::{
+    board::{back_rank as back_rank_of, Board, CastlingRights},
+    coords::Coords,
+    pieces::{Color, Piece, PieceType},
+};
+
+/// Number of Chess960 start positions
+pub const POSITIONS: u16 = 960;
+
+/// Index of the standard start position
+pub const STANDARD: u16 = 518;
+
+/// White's back rank, a to h, for start position `index` in the usual numbering
+pub fn back_rank(index: u16) -> [PieceType; 8] {
+    use PieceType::*;
+    let mut rank = [Empty; 8];
+    let n = index % POSITIONS;
+    // Bishops on opposite colors, b d f h for the light squared one
+    rank[(n % 4 * 2 + 1) as usize] = Bishop;
+    let n = n / 4;
+    rank[(n % 4 * 2) as usize] = Bishop;
+    let n = n / 4;
+    // The rest fill the empty squares left to right
+    let mut place = |skip: u16, piece_type: PieceType| {
+        let x = (0..8).filter(|x| rank[*x] == Empty).nth(skip as usize).unwrap();
+        rank[x] = piece_type;
+    };
+    place(n % 6, Queen);
+    const KNIGHTS: [(u16, u16); 10] = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)];
+    let (first, second) = KNIGHTS[(n / 6) as usize];
+    // Placing the first knight takes away one empty square before the second
+    place(first, Knight);
+    place(second - 1, Knight);
+    place(0, Rook);
+    place(0, King);
+    place(0, Rook);
+    rank
+}
+
+impl Board {
+    /// Chess960 start position `index`, from 0 to 959, with 518 being the standard one
+    pub fn chess960(index: u16) -> Board {
+        let rank = back_rank(index);
+        let mut board = Board::new();
+        for (x, piece_type) in rank.iter().enumerate() {
+            board.state[0][x] = Piece { piece_type: *piece_type, color: Color::Black };
+            board.state[7][x] = Piece { piece_type: *piece_type, color: Color::White };
+        }
+        let rooks = (0..8).filter(|x| rank[*x as usize] == PieceType::Rook).collect::<Vec<u8>>();
+        board.castling = CastlingRights { rook_files: [rooks[1], rooks[0], rooks[1], rooks[0]], ..CastlingRights::all() };
+        if index != STANDARD {
+            board.start_fen = Some(board.to_fen());
+        }
+        board
+    }
+
+    /// A random Chess960 start position and its index
+    pub fn random_chess960() -> (u16, Board) {
+        let index = fastrand::u16(0..POSITIONS);
+        (index, Board::chess960(index))
+    }
+
+    /// Whether castling here needs Chess960 rules, with a castling king off the e file or rook off the a or h file
+    pub fn is_chess960(&self) -> bool {
+        [(Color::White, true), (Color::White, false), (Color::Black, true), (Color::Black, false)].into_iter().any(|(color, king_side)| {
+            let king = Coords::new(4, back_rank_of(color));
+            let rook_file = if king_side { 7 } else { 0 };
+            self.castling.get(color, king_side) && (self.get_piece(king) != Piece { piece_type: PieceType::King, color } || self.castling.rook_file(color, king_side) != rook_file)
+        })
+    }
+
+    /// Index of the current position if it's a Chess960 start position
+    pub fn chess960_index(&self) -> Option<u16> {
+        (0..POSITIONS).find(|index| {
+            let start = Board::chess960(*index);
+            start.state == self.state && start.castling == self.castling && self.turn == Color::White
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    #[test]
+    fn test_start_positions() {
+        assert_eq!(Board::chess960(STANDARD).to_fen(), STARTING_FEN);
+        assert_eq!(Board::chess960(0).to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1");
+        assert_eq!(Board::chess960(959).to_fen(), "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1");
+        let mut ranks = (0..POSITIONS).map(back_rank).collect::<Vec<_>>();
+        ranks.sort_by_key(|rank| rank.map(|piece_type| piece_type as u8));
+        ranks.dedup();
+        assert_eq!(ranks.len(), 960);
+        let (index, board) = Board::random_chess960();
+        assert_eq!(board.chess960_index(), Some(index));
+        assert_eq!(Board::from_fen(&board.to_fen()).unwrap().chess960_index(), Some(index));
+        assert!(Board::chess960(0).is_chess960());
+        assert!(!Board::chess960(STANDARD).is_chess960());
+    }
+
+    #[test]
+    fn test_castling() {
+        let board = Board::from_fen("3k4/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        assert_eq!(board.castling.rook_files[..2], [7, 0]);
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/RK5R w KQ - 0 1");
+        assert_eq!(board.to_shredder_fen(), "3k4/8/8/8/8/8/8/RK5R w HA - 0 1");
+        let long = board.parse_move("O-O-O").unwrap();
+        assert_eq!(board.uci_chess960(&long), "b1a1");
+        assert_eq!(board.parse_uci("b1a1"), Some(long));
+        let mut after = board.clone();
+        after.apply(&long);
+        assert_eq!(after.to_fen(), "3k4/8/8/8/8/8/8/2KR3R b - - 1 1");
+        let short = board.parse_uci("b1h1").unwrap();
+        assert!(short.king_side_castle);
+        let mut after = board.clone();
+        after.apply(&short);
+        assert_eq!(after.to_fen(), "3k4/8/8/8/8/8/8/R4RK1 b - - 1 1");
+        assert_eq!(after.replay(0).to_fen(), board.to_fen());
+        // Dropping the king on its rook castles
+        assert_eq!(board.legal_move("b1".parse::<Coords>().unwrap(), "a1".parse::<Coords>().unwrap(), None), Ok(long));
+
+        // Castling short from f1 with the rook on g1 just swaps them
+        let board = Board::from_fen("3k4/8/8/8/8/8/8/1R3KR1 w GB - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/1R3KR1 w KQ - 0 1");
+        let mut after = board.clone();
+        after.apply(&board.parse_uci("f1b1").unwrap());
+        assert_eq!(after.to_fen(), "3k4/8/8/8/8/8/8/2KR2R1 b - - 1 1");
+        let short = board.parse_uci("f1g1").unwrap();
+        assert!(short.king_side_castle);
+        let mut after = board.clone();
+        after.apply(&short);
+        assert_eq!(after.to_fen(), "3k4/8/8/8/8/8/8/1R3RK1 b - - 1 1");
+        // Moving a rook only drops its own side's right, named by file when it isn't the outer rook
+        let board = Board::from_fen("3k4/8/8/8/8/8/8/RR2K2R w BH - 0 1").unwrap();
+        assert_eq!(board.to_fen(), "3k4/8/8/8/8/8/8/RR2K2R w KB - 0 1");
+        assert!(board.can_castle(Color::White, false));
+        let mut after = board.clone();
+        after.apply(&board.parse_uci("a1a2").unwrap());
+        assert_eq!(after.castling, board.castling);
+        after.apply(&after.parse_uci("d8d7").unwrap());
+        after.apply(&after.parse_uci("b1b2").unwrap());
+        assert!(!after.castling.white_queen_side && after.castling.white_king_side);
+
+        // In check, or with the king passing an attacked square, castling is out
+        let board = Board::from_fen("1r1k4/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        assert!(!board.can_castle(Color::White, true) && !board.can_castle(Color::White, false));
+        let board = Board::from_fen("3k1r2/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        assert!(!board.can_castle(Color::White, true) && board.can_castle(Color::White, false));
+    }
+
+    #[test]
+    fn test_perft() {
+        // Reference counts for Chess960 positions from the published perft tables
+        let board = Board::from_fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9").unwrap();
+        assert_eq!(board.perft(1), 21);
+        assert_eq!(board.perft(2), 528);
+        assert_eq!(board.perft(3), 12189);
+        let board = Board::from_fen("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9").unwrap();
+        assert_eq!(board.perft(1), 21);
+        assert_eq!(board.perft(2), 807);
+        assert_eq!(board.perft(3), 18002);
+    }
+}
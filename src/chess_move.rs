@@ -0,0 +1,298 @@
+use crate::{
+    board::Board,
+    coords::{Coords, MoveCoords},
+    pieces::{Piece, PieceType},
+};
+
+/// A move packed into 16 bits: from square, to square and four flag bits.
+/// Squares are indexed like `Coords::index`, flags follow the usual
+/// quiet / double push / castle / capture / promotion layout. Drops from
+/// the pocket reuse the promotion flags with the same from and to square,
+/// which no pawn move has.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Move(u16);
+
+impl Move {
+    pub const QUIET: u16 = 0;
+    pub const DOUBLE_PUSH: u16 = 1;
+    pub const KING_CASTLE: u16 = 2;
+    pub const QUEEN_CASTLE: u16 = 3;
+    pub const CAPTURE: u16 = 4;
+    pub const EN_PASSANT: u16 = 5;
+    /// Promoting to a king, as Antichess allows, fills the two codes left over
+    pub const KING_PROMOTION: u16 = 6;
+    pub const KING_PROMOTION_CAPTURE: u16 = 7;
+    /// Set for promotions, with the piece in the two low bits
+    pub const PROMOTION: u16 = 8;
+
+    pub fn new(from: Coords, to: Coords, flags: u16) -> Self {
+        Move(from.index() as u16 | (to.index() as u16) << 6 | flags << 12)
+    }
+
+    /// Placing a `piece_type` from the pocket on `to`
+    pub fn drop(piece_type: PieceType, to: Coords) -> Self {
+        Move::new(to, to, Move::PROMOTION + piece_type as u16 - 1)
+    }
+
+    pub fn promotion_flags(piece_type: PieceType, capture: bool) -> u16 {
+        if piece_type == PieceType::King {
+            return if capture { Move::KING_PROMOTION_CAPTURE } else { Move::KING_PROMOTION };
+        }
+        let piece = match piece_type {
+            PieceType::Bishop => 1,
+            PieceType::Rook => 2,
+            PieceType::Queen => 3,
+            _ => 0,
+        };
+        Move::PROMOTION | if capture { Move::CAPTURE } else { 0 } | piece
+    }
+
+    pub fn from(self) -> Coords {
+        Coords::from_index((self.0 & 0x3f) as u8)
+    }
+    pub fn to(self) -> Coords {
+        Coords::from_index((self.0 >> 6 & 0x3f) as u8)
+    }
+    pub fn flags(self) -> u16 {
+        self.0 >> 12
+    }
+    /// The piece placed from the pocket, for a drop
+    pub fn dropped(self) -> Option<PieceType> {
+        if self.from() != self.to() || self.flags() < Move::PROMOTION {
+            return None;
+        }
+        match self.flags() - Move::PROMOTION {
+            0 => Some(PieceType::Pawn),
+            1 => Some(PieceType::Knight),
+            2 => Some(PieceType::Bishop),
+            3 => Some(PieceType::Rook),
+            4 => Some(PieceType::Queen),
+            _ => None,
+        }
+    }
+    pub fn is_drop(self) -> bool {
+        self.dropped().is_some()
+    }
+    pub fn is_capture(self) -> bool {
+        !self.is_drop() && self.flags() & Move::CAPTURE != 0 && self.flags() != Move::KING_PROMOTION
+    }
+    pub fn is_en_passant(self) -> bool {
+        self.flags() == Move::EN_PASSANT
+    }
+    pub fn is_double_push(self) -> bool {
+        self.flags() == Move::DOUBLE_PUSH
+    }
+    pub fn is_king_side_castle(self) -> bool {
+        self.flags() == Move::KING_CASTLE
+    }
+    pub fn is_queen_side_castle(self) -> bool {
+        self.flags() == Move::QUEEN_CASTLE
+    }
+    pub fn promotion(self) -> Option<PieceType> {
+        if self.is_drop() {
+            return None;
+        }
+        if self.flags() == Move::KING_PROMOTION || self.flags() == Move::KING_PROMOTION_CAPTURE {
+            return Some(PieceType::King);
+        }
+        if self.flags() & Move::PROMOTION == 0 {
+            return None;
+        }
+        Some(match self.flags() & 3 {
+            0 => PieceType::Knight,
+            1 => PieceType::Bishop,
+            2 => PieceType::Rook,
+            _ => PieceType::Queen,
+        })
+    }
+    pub fn uci(self) -> String {
+        if let Some(dropped) = self.dropped() {
+            return format!("{}@{}", dropped.letter(), self.to());
+        }
+        match self.promotion() {
+            Some(promotion) => format!("{}{}{}", self.from(), self.to(), promotion.letter().to_ascii_lowercase()),
+            None => format!("{}{}", self.from(), self.to()),
+        }
+    }
+}
+
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.uci())
+    }
+}
+
+/// Enough for the most moves any position has, with every kind of piece in hand to drop
+pub const MAX_MOVES: usize = 768;
+
+/// Fixed capacity list of moves kept on the stack, so generating moves doesn't allocate
+#[derive(Clone)]
+pub struct MoveList {
+    moves: [Move; MAX_MOVES],
+    len: usize,
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        MoveList { moves: [Move::default(); MAX_MOVES], len: 0 }
+    }
+    pub fn push(&mut self, mv: Move) {
+        self.moves[self.len] = mv;
+        self.len += 1;
+    }
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+    pub fn retain(&mut self, mut keep: impl FnMut(Move) -> bool) {
+        let mut kept = 0;
+        for i in 0..self.len {
+            if keep(self.moves[i]) {
+                self.moves[kept] = self.moves[i];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        MoveList::new()
+    }
+}
+
+impl std::ops::Deref for MoveList {
+    type Target = [Move];
+
+    fn deref(&self) -> &[Move] {
+        &self.moves[..self.len]
+    }
+}
+
+impl std::ops::DerefMut for MoveList {
+    fn deref_mut(&mut self) -> &mut [Move] {
+        &mut self.moves[..self.len]
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl std::fmt::Debug for MoveList {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl Board {
+    /// Packs `mc`, played from the current position, into a `Move`
+    pub fn encode_move(&self, mc: &MoveCoords) -> Move {
+        if mc.drop {
+            return Move::drop(mc.piece.piece_type, mc.to);
+        }
+        let flags = if mc.king_side_castle {
+            Move::KING_CASTLE
+        } else if mc.queen_side_castle {
+            Move::QUEEN_CASTLE
+        } else if let Some(promotion) = mc.promotion {
+            Move::promotion_flags(promotion, mc.takes)
+        } else if mc.piece.piece_type == PieceType::Pawn && mc.takes && Some(mc.to) == self.en_passant && self.is_empty(mc.to) {
+            Move::EN_PASSANT
+        } else if mc.takes {
+            Move::CAPTURE
+        } else if mc.piece.piece_type == PieceType::Pawn && mc.from.y.abs_diff(mc.to.y) == 2 {
+            Move::DOUBLE_PUSH
+        } else {
+            Move::QUIET
+        };
+        Move::new(mc.from, mc.to, flags)
+    }
+
+    /// Expands `mv`, played from the current position, into `MoveCoords`
+    pub fn decode_move(&self, mv: Move) -> MoveCoords {
+        let piece = match mv.dropped() {
+            Some(piece_type) => Piece { piece_type, color: self.turn },
+            None => self.get_piece(mv.from()),
+        };
+        MoveCoords {
+            piece,
+            from: mv.from(),
+            to: mv.to(),
+            takes: mv.is_capture(),
+            promotion: mv.promotion(),
+            king_side_castle: mv.is_king_side_castle(),
+            queen_side_castle: mv.is_queen_side_castle(),
+            drop: mv.is_drop(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_encoding() {
+        assert_eq!(std::mem::size_of::<Move>(), 2);
+        let e2: Coords = "e2".parse().unwrap();
+        let e4: Coords = "e4".parse().unwrap();
+        let mv = Move::new(e2, e4, Move::DOUBLE_PUSH);
+        assert_eq!((mv.from(), mv.to()), (e2, e4));
+        assert!(mv.is_double_push() && !mv.is_capture());
+        let promotion = Move::new("b7".parse().unwrap(), "a8".parse().unwrap(), Move::promotion_flags(PieceType::Rook, true));
+        assert_eq!(promotion.promotion(), Some(PieceType::Rook));
+        assert!(promotion.is_capture());
+        assert_eq!(promotion.uci(), "b7a8r");
+        for capture in [false, true] {
+            let king = Move::new("b2".parse().unwrap(), "b1".parse().unwrap(), Move::promotion_flags(PieceType::King, capture));
+            assert_eq!((king.promotion(), king.is_capture(), king.is_en_passant()), (Some(PieceType::King), capture, false));
+        }
+    }
+
+    #[test]
+    fn test_drop_encoding() {
+        let f3: Coords = "f3".parse().unwrap();
+        for piece_type in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+            let mv = Move::drop(piece_type, f3);
+            assert_eq!((mv.dropped(), mv.to()), (Some(piece_type), f3));
+            assert!(!mv.is_capture() && !mv.is_en_passant() && !mv.is_double_push() && !mv.is_king_side_castle() && !mv.is_queen_side_castle());
+            assert_eq!(mv.promotion(), None);
+        }
+        assert_eq!(Move::drop(PieceType::Knight, f3).uci(), "N@f3");
+        assert!(!Move::default().is_drop());
+        // Castling in Chess960 may leave the king where it stood
+        let g1: Coords = "g1".parse().unwrap();
+        assert!(!Move::new(g1, g1, Move::KING_CASTLE).is_drop());
+    }
+
+    #[test]
+    fn test_move_coords_round_trip() {
+        let board = Board::from_fen("r3k2r/pPpp1ppp/8/3Pp3/8/8/8/R3K2R w KQkq e6 0 1").unwrap();
+        for mc in board.legal_moves() {
+            let mv = board.encode_move(&mc);
+            assert_eq!(board.decode_move(mv), mc);
+            assert_eq!(mv.uci(), mc.uci());
+        }
+        let ep = board.parse_uci("d5e6").unwrap();
+        assert!(board.encode_move(&ep).is_en_passant());
+    }
+
+    #[test]
+    fn test_move_list() {
+        let mut list = MoveList::new();
+        assert!(list.is_empty());
+        for i in 0..10 {
+            list.push(Move::new(Coords::from_index(i), Coords::from_index(i + 8), Move::QUIET));
+        }
+        list.retain(|mv| mv.from().index() % 2 == 0);
+        assert_eq!(list.len(), 5);
+        assert_eq!(list[1].from(), Coords::from_index(2));
+        assert_eq!(list.iter().count(), 5);
+    }
+}
@@ -0,0 +1,477 @@
+use std::io::{BufRead, Write};
+use std::time::Duration;
+
+use crate::{
+    annotate::annotate,
+    board::Board,
+    book::Book,
+    database::{Database, Filter},
+    epd::Epd,
+    eval::evaluate_white,
+    pgn::Pgn,
+    pieces::Color,
+    search::{format_score, search_lines, Limits},
+    syzygy::Tablebase,
+    variant::{self, Standard, Variant},
+};
+
+pub const USAGE: &str = "\
+Usage: chess-cli <command> [arguments]
+
+Positions are FEN strings, `startpos`, or `-` to read one FEN per line from stdin.
+Every command takes `--variant NAME` to use the rules of King of the Hill, Three-check,
+Atomic, Antichess, Horde, Racing Kings or Crazyhouse instead of standard chess.
+
+  perft <position> <depth> [--divide]   count move paths, split by first move with --divide
+  eval <position>                       static evaluation in centipawns for White
+  bestmove <position> [--depth N | --time SECONDS] [--san] [--syzygy DIRS] [--lines N]
+                                        search for the best move, or the best N with their lines,
+                                        using Syzygy tablebases in DIRS
+  legal <position> [--uci]              legal moves, one per line
+  convert <file.pgn | -> [--to fen-list | uci | san | pgn]
+                                        convert every game in a PGN file
+  annotate <file.pgn | -> [--depth N | --time SECONDS] [--syzygy DIRS]
+                                        mark inaccuracies, mistakes and blunders in every game,
+                                        searching each position, with the engine's line as a variation
+  epd <file.epd | -> [--depth N | --time SECONDS] [--syzygy DIRS]
+                                        search each position of a test suite such as WAC or STS, checking
+                                        the move against its bm and am operations and c0 points
+  book build <file.pgn | -> <book.bin> [--plies N]
+                                        build a Polyglot opening book from the first N plies (default 16)
+  book probe <book.bin> <position>      book moves and weights for a position
+  db import <file.pgn | -> <games.db>  add the games of a PGN file to a database, creating it if needed
+  db search <games.db> [--position FEN] [--player NAME] [--white NAME] [--black NAME]
+            [--from DATE] [--to DATE] [--result RESULT]
+                                        list the games reaching a position and passing the filters
+  db explore <games.db> <position> [filters]
+                                        moves played from a position with their results, most played first
+  db show <games.db> <number>           a game from the database as PGN";
+
+/// Runs the command in `args` (without the program name), reading `-` inputs from `input`
+pub fn run(args: &[String], input: impl BufRead, output: &mut impl Write) -> Result<(), String> {
+    let (command, args) = args.split_first().ok_or(USAGE)?;
+    let (positional, flags) = split_flags(args);
+    let io = |e: std::io::Error| e.to_string();
+    let rules = match flag(&flags, "--variant") {
+        Some(name) => {
+            let name = name.ok_or("--variant needs a name")?;
+            variant::from_name(name).ok_or_else(|| format!("Unknown variant {}", name))?
+        }
+        None => &Standard,
+    };
+    match command.as_str() {
+        "perft" => {
+            let depth = match positional.get(1) {
+                Some(depth) => depth.parse::<u32>().map_err(|_| format!("Invalid depth {}", depth))?,
+                None => return Err("perft needs a position and a depth".to_string()),
+            };
+            let divide = flag(&flags, "--divide").is_some();
+            for_each_position(&positional, rules, input, |board| {
+                let nodes = if divide {
+                    let mut total = 0;
+                    for (mc, count) in board.divide(depth) {
+                        writeln!(output, "{}: {}", mc.uci(), count).map_err(io)?;
+                        total += count;
+                    }
+                    writeln!(output).map_err(io)?;
+                    total
+                } else {
+                    board.perft(depth)
+                };
+                writeln!(output, "{}", nodes).map_err(io)
+            })
+        }
+        "eval" => for_each_position(&positional, rules, input, |board| writeln!(output, "{}", evaluate_white(board)).map_err(io)),
+        "bestmove" => {
+            let limits = parse_limits(&flags)?;
+            let san = flag(&flags, "--san").is_some();
+            let lines = match flag(&flags, "--lines") {
+                Some(lines) => lines.ok_or("--lines needs a number")?.parse::<usize>().map_err(|_| "Invalid number of lines")?,
+                None => 1,
+            };
+            let tablebase = open_tablebase(&flags)?;
+            for_each_position(&positional, rules, input, |board| {
+                for result in search_lines(board, limits, lines, tablebase.as_ref(), None, |_| ()) {
+                    let best = match result.best_move {
+                        Some(mc) if san => board.san(&mc),
+                        Some(mc) => mc.uci(),
+                        None => "(none)".to_string(),
+                    };
+                    let pv = result.pv.iter().map(|mc| mc.uci()).collect::<Vec<_>>().join(" ");
+                    writeln!(output, "{} {} depth {} nodes {} pv {}", best, format_score(result.score), result.depth, result.nodes, pv).map_err(io)?;
+                }
+                Ok(())
+            })
+        }
+        "legal" => {
+            let uci = flag(&flags, "--uci").is_some();
+            for_each_position(&positional, rules, input, |board| {
+                for mc in board.legal_moves() {
+                    let text = if uci { mc.uci() } else { board.san(&mc) };
+                    writeln!(output, "{}", text).map_err(io)?;
+                }
+                Ok(())
+            })
+        }
+        "convert" => {
+            let path = positional.first().ok_or("convert needs a PGN file")?;
+            let text = read_text(path, input)?;
+            let to = match flag(&flags, "--to") {
+                Some(to) => to.ok_or("--to needs a format")?,
+                None => "fen-list",
+            };
+            for pgn in Pgn::parse_all(&text)? {
+                match to {
+                    "fen-list" => {
+                        for ply in 0..=pgn.board.moves.len() {
+                            writeln!(output, "{}", pgn.board.replay(ply).to_fen()).map_err(io)?;
+                        }
+                    }
+                    "uci" => writeln!(output, "{}", pgn.board.moves.iter().map(|mc| mc.uci()).collect::<Vec<_>>().join(" ")).map_err(io)?,
+                    "san" => writeln!(output, "{}", pgn.board.san_moves().join(" ")).map_err(io)?,
+                    "pgn" => writeln!(output, "{}", pgn).map_err(io)?,
+                    _ => return Err(format!("Unknown format {}, expected fen-list, uci, san or pgn", to)),
+                }
+            }
+            Ok(())
+        }
+        "annotate" => {
+            let path = positional.first().ok_or("annotate needs a PGN file")?;
+            let limits = parse_limits(&flags)?;
+            let tablebase = open_tablebase(&flags)?;
+            for pgn in Pgn::parse_all(&read_text(path, input)?)? {
+                let (mut annotated, _) = annotate(&pgn.board, limits, tablebase.as_ref());
+                annotated.headers = pgn.headers;
+                writeln!(output, "{}", annotated).map_err(io)?;
+            }
+            Ok(())
+        }
+        "epd" => {
+            let path = positional.first().ok_or("epd needs an EPD file")?;
+            let limits = parse_limits(&flags)?;
+            let tablebase = open_tablebase(&flags)?;
+            let (mut solved, mut tested, mut points, mut most) = (0, 0, 0, 0);
+            for (i, epd) in Epd::parse_all(&read_text(path, input)?)?.iter().enumerate() {
+                let board = &epd.board;
+                let result = search_lines(board, limits, 1, tablebase.as_ref(), None, |_| ()).into_iter().next().unwrap_or_default();
+                let id = epd.id().map_or_else(|| (i + 1).to_string(), |id| id.to_string());
+                let Some(mc) = result.best_move else {
+                    writeln!(output, "{} no moves", id).map_err(io)?;
+                    continue;
+                };
+                let mut line = format!("{} {}", id, board.san(&mc));
+                if let Some(ok) = epd.is_solved_by(&mc) {
+                    tested += 1;
+                    solved += ok as u32;
+                    line += if ok { " ok" } else { " failed" };
+                    for (opcode, moves) in [("bm", epd.best_moves()), ("am", epd.avoid_moves())] {
+                        if !moves.is_empty() {
+                            line += &format!(" {} {}", opcode, moves.iter().map(|mc| board.san(mc)).collect::<Vec<_>>().join(" "));
+                        }
+                    }
+                }
+                if let Some((got, best)) = epd.points_for(&mc) {
+                    (points, most) = (points + got, most + best);
+                    line += &format!(" points {}/{}", got, best);
+                }
+                writeln!(output, "{} score {} depth {}", line, format_score(result.score), result.depth).map_err(io)?;
+            }
+            writeln!(output, "Solved {}/{}", solved, tested).map_err(io)?;
+            if most > 0 {
+                writeln!(output, "Points {}/{}", points, most).map_err(io)?;
+            }
+            Ok(())
+        }
+        "book" => match positional.first().map(|s| s.as_str()) {
+            Some("build") => {
+                let (path, out) = match (positional.get(1), positional.get(2)) {
+                    (Some(path), Some(out)) => (path, out),
+                    _ => return Err("book build needs a PGN file and the book to write".to_string()),
+                };
+                let plies = match flag(&flags, "--plies") {
+                    Some(plies) => plies.ok_or("--plies needs a value")?.parse().map_err(|_| "Invalid plies")?,
+                    None => 16,
+                };
+                let text = read_text(path, input)?;
+                let games = Pgn::parse_all(&text)?;
+                let book = Book::from_games(&games, plies);
+                book.save(out).map_err(|e| e.to_string())?;
+                writeln!(output, "{} entries from {} games", book.len(), games.len()).map_err(io)
+            }
+            Some("probe") => {
+                let book = Book::open(positional.get(1).ok_or("book probe needs a book file")?).map_err(|e| e.to_string())?;
+                for_each_position(&positional[2..], rules, input, |board| {
+                    let mut moves = book.moves(board);
+                    moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+                    for (mc, weight) in moves {
+                        writeln!(output, "{} {}", board.san(&mc), weight).map_err(io)?;
+                    }
+                    Ok(())
+                })
+            }
+            _ => Err(format!("book needs build or probe\n{}", USAGE)),
+        },
+        "db" => match positional.first().map(|s| s.as_str()) {
+            Some("import") => {
+                let (path, out) = match (positional.get(1), positional.get(2)) {
+                    (Some(path), Some(out)) => (path, out),
+                    _ => return Err("db import needs a PGN file and the database to write".to_string()),
+                };
+                let mut database = if std::path::Path::new(out).exists() { Database::open(out).map_err(|e| e.to_string())? } else { Database::new() };
+                let imported = if path == "-" {
+                    database.import(input)
+                } else {
+                    let file = std::fs::File::open(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+                    database.import(std::io::BufReader::new(file))
+                }
+                .map_err(io)?;
+                database.save(out).map_err(|e| e.to_string())?;
+                writeln!(output, "{} games imported, {} skipped, {} in the database", imported.games, imported.skipped, database.len()).map_err(io)
+            }
+            Some("search") => {
+                let database = Database::open(positional.get(1).ok_or("db search needs a database")?).map_err(|e| e.to_string())?;
+                let position = match flag(&flags, "--position") {
+                    Some(fen) => Some(parse_position(fen.ok_or("--position needs a FEN")?, rules)?),
+                    None => None,
+                };
+                for id in database.search(&parse_filter(&flags)?, position.as_ref()) {
+                    let headers = database.headers(id).unwrap_or_default();
+                    let header = |name: &str| headers.iter().find(|(n, _)| n == name).map_or("?", |(_, value)| value.as_str());
+                    let result = headers.iter().find(|(n, _)| n == "Result").map_or("*", |(_, value)| value.as_str());
+                    writeln!(output, "{} {} - {}, {} {} {}", id + 1, header("White"), header("Black"), header("Event"), header("Date"), result).map_err(io)?;
+                }
+                Ok(())
+            }
+            Some("explore") => {
+                let database = Database::open(positional.get(1).ok_or("db explore needs a database")?).map_err(|e| e.to_string())?;
+                let filter = parse_filter(&flags)?;
+                for_each_position(&positional[2..], rules, input, |board| {
+                    for explored in database.explore(board, &filter) {
+                        let score = explored.score(board.turn) * 100.0;
+                        let (white, draws, black) = (explored.white_wins, explored.draws, explored.black_wins);
+                        let side = if board.turn == Color::White { "White" } else { "Black" };
+                        writeln!(output, "{} {} games +{} ={} -{} {:.1}% for {}", board.san(&explored.mc), explored.games, white, draws, black, score, side).map_err(io)?;
+                    }
+                    Ok(())
+                })
+            }
+            Some("show") => {
+                let database = Database::open(positional.get(1).ok_or("db show needs a database")?).map_err(|e| e.to_string())?;
+                let number = positional.get(2).ok_or("db show needs a game number")?;
+                match number.parse::<usize>() {
+                    Ok(n) if (1..=database.len()).contains(&n) => writeln!(output, "{}", database.game(n - 1)?).map_err(io),
+                    _ => Err(format!("No game {} among {}", number, database.len())),
+                }
+            }
+            _ => Err(format!("db needs import, search, explore or show\n{}", USAGE)),
+        },
+        "help" | "--help" | "-h" => writeln!(output, "{}", USAGE).map_err(io),
+        _ => Err(format!("Unknown command {}\n{}", command, USAGE)),
+    }
+}
+
+/// Splits `--flag [value]` arguments from positional ones, flags which take no value being listed here
+fn split_flags(args: &[String]) -> (Vec<String>, Vec<(String, Option<String>)>) {
+    const SWITCHES: [&str; 3] = ["--divide", "--san", "--uci"];
+    let mut positional = vec![];
+    let mut flags = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if SWITCHES.contains(&arg.as_str()) {
+            flags.push((arg.clone(), None));
+        } else if arg.starts_with("--") {
+            flags.push((arg.clone(), args.next().cloned()));
+        } else {
+            positional.push(arg.clone());
+        }
+    }
+    (positional, flags)
+}
+
+fn flag<'a>(flags: &'a [(String, Option<String>)], name: &str) -> Option<Option<&'a str>> {
+    flags.iter().find(|(flag, _)| flag == name).map(|(_, value)| value.as_deref())
+}
+
+/// `--depth N` or `--time SECONDS` for searches, depth 4 without either
+fn parse_limits(flags: &[(String, Option<String>)]) -> Result<Limits, String> {
+    Ok(match (flag(flags, "--depth"), flag(flags, "--time")) {
+        (Some(_), Some(_)) => return Err("Use either --depth or --time".to_string()),
+        (Some(depth), None) => Limits::depth(depth.ok_or("--depth needs a value")?.parse().map_err(|_| "Invalid depth")?),
+        (None, Some(time)) => {
+            let secs = time.ok_or("--time needs a value")?.parse::<f64>().map_err(|_| "Invalid time")?;
+            Limits::time(Duration::from_secs_f64(secs))
+        }
+        (None, None) => Limits::depth(4),
+    })
+}
+
+/// `--player`, `--white`, `--black`, `--from`, `--to` and `--result` for database queries
+fn parse_filter(flags: &[(String, Option<String>)]) -> Result<Filter, String> {
+    let value = |name: &str| match flag(flags, name) {
+        Some(value) => value.map(|value| Some(value.to_string())).ok_or_else(|| format!("{} needs a value", name)),
+        None => Ok(None),
+    };
+    Ok(Filter {
+        player: value("--player")?,
+        white: value("--white")?,
+        black: value("--black")?,
+        from: value("--from")?,
+        to: value("--to")?,
+        result: value("--result")?,
+    })
+}
+
+fn open_tablebase(flags: &[(String, Option<String>)]) -> Result<Option<Tablebase>, String> {
+    match flag(flags, "--syzygy") {
+        Some(path) => Ok(Some(Tablebase::open(path.ok_or("--syzygy needs a directory")?).map_err(|e| e.to_string())?)),
+        None => Ok(None),
+    }
+}
+
+/// The contents of the file at `path`, or all of `input` for `-`
+fn read_text(path: &str, input: impl BufRead) -> Result<String, String> {
+    if path == "-" {
+        std::io::read_to_string(input).map_err(|e| e.to_string())
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))
+    }
+}
+
+fn parse_position(position: &str, rules: &'static dyn Variant) -> Result<Board, String> {
+    let fen = if position == "startpos" { rules.start_fen() } else { position };
+    Board::from_fen_variant(fen, rules).map_err(|e| format!("Invalid FEN {}: {}", position, e))
+}
+
+/// Calls `f` with the position given as the first argument, or each FEN line from `input` for `-`
+fn for_each_position(positional: &[String], rules: &'static dyn Variant, input: impl BufRead, mut f: impl FnMut(&Board) -> Result<(), String>) -> Result<(), String> {
+    match positional.first().map(|p| p.as_str()) {
+        None => Err("Missing position".to_string()),
+        Some("-") => {
+            for line in input.lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                if !line.trim().is_empty() {
+                    f(&parse_position(line.trim(), rules)?)?;
+                }
+            }
+            Ok(())
+        }
+        Some(position) => f(&parse_position(position, rules)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fen::STARTING_FEN;
+
+    fn run_args(args: &[&str], input: &str) -> Result<String, String> {
+        let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let mut output = vec![];
+        run(&args, input.as_bytes(), &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_perft_and_legal() {
+        assert_eq!(run_args(&["perft", "startpos", "2"], "").unwrap(), "400\n");
+        let divide = run_args(&["perft", "startpos", "1", "--divide"], "").unwrap();
+        assert!(divide.contains("e2e4: 1\n"));
+        assert!(divide.contains("\n20\n"));
+        let piped = run_args(&["perft", "-", "1"], &format!("{}\n\n8/8/8/8/8/8/8/K6k w - - 0 1\n", STARTING_FEN)).unwrap();
+        assert_eq!(piped, "20\n3\n");
+        let legal = run_args(&["legal", "8/8/8/8/8/8/8/K6k w - - 0 1", "--uci"], "").unwrap();
+        assert_eq!(legal.lines().count(), 3);
+        assert!(legal.contains("a1b2"));
+    }
+
+    #[test]
+    fn test_eval_and_bestmove() {
+        assert_eq!(run_args(&["eval", "startpos"], "").unwrap(), "0\n");
+        let best = run_args(&["bestmove", "6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "--depth", "2", "--san"], "").unwrap();
+        assert!(best.starts_with("Ra8# #1 depth 1"));
+        assert!(run_args(&["bestmove", "startpos", "--depth", "x"], "").is_err());
+        let lines = run_args(&["bestmove", "startpos", "--depth", "1", "--lines", "3"], "").unwrap();
+        assert_eq!(lines.lines().count(), 3);
+    }
+
+    #[test]
+    fn test_variant() {
+        assert_eq!(run_args(&["perft", "startpos", "1", "--variant", "horde"], "").unwrap(), "8\n");
+        assert_eq!(run_args(&["perft", "startpos", "1", "--variant", "racing-kings"], "").unwrap(), "21\n");
+        let legal = run_args(&["legal", "rnbqkbnr/p1pppppp/8/1p6/8/4P3/PPPP1PPP/RNBQKBNR w - - 0 2", "--variant", "antichess"], "").unwrap();
+        assert_eq!(legal, "Bxb5\n");
+        let best = run_args(&["bestmove", "4k3/8/8/8/8/4K3/8/8 w - - 0 1", "--variant", "kingofthehill", "--depth", "2"], "").unwrap();
+        assert!(best.contains(" #1 "));
+        assert!(run_args(&["perft", "startpos", "1", "--variant", "shogi"], "").is_err());
+    }
+
+    #[test]
+    fn test_convert() {
+        let pgn = "[Event \"?\"]\n\n1. e4 e5 2. Nf3 *\n";
+        assert_eq!(run_args(&["convert", "-", "--to", "uci"], pgn).unwrap(), "e2e4 e7e5 g1f3\n");
+        let fens = run_args(&["convert", "-"], pgn).unwrap();
+        assert_eq!(fens.lines().count(), 4);
+        assert_eq!(fens.lines().next(), Some(STARTING_FEN));
+        // The bundled openings are too coarse to write into headers
+        assert!(!run_args(&["convert", "-", "--to", "pgn"], pgn).unwrap().contains("[ECO "));
+        assert!(run_args(&["convert", "-", "--to", "xml"], pgn).is_err());
+        assert!(run_args(&["frobnicate"], "").is_err());
+        assert!(run_args(&[], "").is_err());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let pgn = "[White \"Fool\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+        let annotated = run_args(&["annotate", "-", "--depth", "2"], pgn).unwrap();
+        assert!(annotated.starts_with("[White \"Fool\"]\n[Result \"0-1\"]\n"), "{}", annotated);
+        assert!(annotated.contains("\n1. f3 e5 2. g4?? {Blunder"), "{}", annotated);
+        assert!(run_args(&["annotate"], "").is_err());
+    }
+
+    #[test]
+    fn test_epd() {
+        let suite = "6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Ra8#; id \"mate\";\n\
+                     6k1/5ppp/8/8/8/8/8/R5K1 w - - am Ra8#;\n\
+                     6k1/5ppp/8/8/8/8/8/R5K1 w - - c0 \"Ra8#=10, Ra7=1\";\n";
+        let report = run_args(&["epd", "-", "--depth", "2"], suite).unwrap();
+        assert_eq!(report.lines().collect::<Vec<_>>(), vec![
+            "mate Ra8# ok bm Ra8# score #1 depth 1",
+            "2 Ra8# failed am Ra8# score #1 depth 1",
+            "3 Ra8# points 10/10 score #1 depth 1",
+            "Solved 1/2",
+            "Points 10/10",
+        ]);
+        assert!(run_args(&["epd", "-"], "8/8/8 w").is_err());
+    }
+
+    #[test]
+    fn test_book() {
+        let path = std::env::temp_dir().join(format!("chess-cli-test-{}.bin", std::process::id()));
+        let path = path.to_str().unwrap();
+        let pgn = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n[Result \"1/2-1/2\"]\n\n1. d4 d5 1/2-1/2\n";
+        assert_eq!(run_args(&["book", "build", "-", path, "--plies", "2"], pgn).unwrap(), "3 entries from 2 games\n");
+        assert_eq!(run_args(&["book", "probe", path, "startpos"], "").unwrap(), "e4 2\nd4 1\n");
+        std::fs::remove_file(path).unwrap();
+        assert!(run_args(&["book", "probe", path, "startpos"], "").is_err());
+        assert!(run_args(&["book"], "").is_err());
+    }
+
+    #[test]
+    fn test_database() {
+        let path = std::env::temp_dir().join(format!("chess-cli-test-{}.db", std::process::id()));
+        let path = path.to_str().unwrap();
+        let pgn = "[White \"Alice\"]\n[Black \"Bob\"]\n[Date \"2024.03.01\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n[White \"Bob\"]\n[Black \"Carol\"]\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n";
+        assert_eq!(run_args(&["db", "import", "-", path], pgn).unwrap(), "2 games imported, 0 skipped, 2 in the database\n");
+        assert_eq!(run_args(&["db", "explore", path, "startpos"], "").unwrap(), "e4 2 games +1 =1 -0 75.0% for White\n");
+        let after_e4 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        assert_eq!(run_args(&["db", "explore", path, after_e4, "--player", "carol"], "").unwrap(), "c5 1 games +0 =1 -0 50.0% for Black\n");
+        assert_eq!(run_args(&["db", "search", path, "--player", "bob", "--from", "2024"], "").unwrap(), "1 Alice - Bob, ? 2024.03.01 1-0\n");
+        assert_eq!(run_args(&["db", "search", path, "--position", after_e4, "--result", "1/2-1/2"], "").unwrap(), "2 Bob - Carol, ? ? 1/2-1/2\n");
+        assert!(run_args(&["db", "show", path, "1"], "").unwrap().contains("1. e4 e5 2. Nf3 1-0"));
+        assert!(run_args(&["db", "show", path, "3"], "").is_err());
+        // Importing again adds to the database
+        assert_eq!(run_args(&["db", "import", "-", path], pgn).unwrap(), "2 games imported, 0 skipped, 4 in the database\n");
+        std::fs::remove_file(path).unwrap();
+        assert!(run_args(&["db", "search", path], "").is_err());
+    }
+}
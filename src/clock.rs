@@ -0,0 +1,356 @@
+use std::time::Duration;
+
+use crate::pieces::Color;
+
+/// Time handed back to a player for each move
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bonus {
+    None,
+    /// Fischer: added after every move
+    Increment(Duration),
+    /// Bronstein: the time used on the move is given back, up to the delay
+    Bronstein(Duration),
+    /// Simple (US) delay: the clock waits this long before counting down
+    Delay(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Period {
+    /// Moves to make in this period, `None` for the rest of the game
+    pub moves: Option<u32>,
+    pub time: Duration,
+    pub bonus: Bonus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimeControl {
+    pub periods: Vec<Period>,
+}
+
+impl TimeControl {
+    pub fn sudden_death(time: Duration) -> Self {
+        TimeControl { periods: vec![Period { moves: None, time, bonus: Bonus::None }] }
+    }
+    pub fn fischer(time: Duration, increment: Duration) -> Self {
+        TimeControl { periods: vec![Period { moves: None, time, bonus: Bonus::Increment(increment) }] }
+    }
+    pub fn bronstein(time: Duration, delay: Duration) -> Self {
+        TimeControl { periods: vec![Period { moves: None, time, bonus: Bonus::Bronstein(delay) }] }
+    }
+    pub fn simple_delay(time: Duration, delay: Duration) -> Self {
+        TimeControl { periods: vec![Period { moves: None, time, bonus: Bonus::Delay(delay) }] }
+    }
+    /// A period of `moves` moves, followed by whatever is added with `then`
+    pub fn moves_in(moves: u32, time: Duration, bonus: Bonus) -> Self {
+        TimeControl { periods: vec![Period { moves: Some(moves), time, bonus }] }
+    }
+    pub fn then(mut self, period: Period) -> Self {
+        self.periods.push(period);
+        self
+    }
+}
+
+/// Parses the PGN `TimeControl` syntax, in seconds: `300`, `180+2`, `40/5400:1800+30`, or `10+0.1` for engine games.
+/// A bonus written `d5` is a simple delay and `b5` a Bronstein delay.
+impl std::str::FromStr for TimeControl {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_secs = |s: &str| s.trim().parse::<f64>().ok().filter(|secs| secs.is_finite() && *secs >= 0.0).map(Duration::from_secs_f64).ok_or("Invalid number of seconds");
+        let mut periods = vec![];
+        for period in s.split(':') {
+            let (moves, rest) = match period.split_once('/') {
+                Some((moves, rest)) => (Some(moves.trim().parse::<u32>().map_err(|_| "Invalid number of moves")?), rest),
+                None => (None, period),
+            };
+            let (time, bonus) = match rest.split_once('+') {
+                Some((time, bonus)) => {
+                    let bonus = if let Some(delay) = bonus.strip_prefix('d') {
+                        Bonus::Delay(parse_secs(delay)?)
+                    } else if let Some(delay) = bonus.strip_prefix('b') {
+                        Bonus::Bronstein(parse_secs(delay)?)
+                    } else {
+                        Bonus::Increment(parse_secs(bonus)?)
+                    };
+                    (parse_secs(time)?, bonus)
+                }
+                None => (parse_secs(rest)?, Bonus::None),
+            };
+            if moves == Some(0) {
+                return Err("A period needs at least one move");
+            }
+            periods.push(Period { moves, time, bonus });
+        }
+        Ok(TimeControl { periods })
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ":")?;
+            }
+            if let Some(moves) = period.moves {
+                write!(f, "{}/", moves)?;
+            }
+            write!(f, "{}", period.time.as_secs_f64())?;
+            match period.bonus {
+                Bonus::None => (),
+                Bonus::Increment(d) => write!(f, "+{}", d.as_secs_f64())?,
+                Bonus::Delay(d) => write!(f, "+d{}", d.as_secs_f64())?,
+                Bonus::Bronstein(d) => write!(f, "+b{}", d.as_secs_f64())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Clock {
+    pub control: TimeControl,
+    remaining: [Duration; 2],
+    period: [usize; 2],
+    moves_in_period: [u32; 2],
+    turn: Color,
+    running: bool,
+    /// Time spent so far on the current move
+    elapsed: Duration,
+    flagged: Option<Color>,
+}
+
+fn index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+impl Clock {
+    pub fn new(control: TimeControl) -> Self {
+        let time = control.periods.first().map(|p| p.time).unwrap_or_default();
+        Clock {
+            control,
+            remaining: [time; 2],
+            period: [0; 2],
+            moves_in_period: [0; 2],
+            turn: Color::White,
+            running: false,
+            elapsed: Duration::ZERO,
+            flagged: None,
+        }
+    }
+
+    pub fn turn(&self) -> Color {
+        self.turn
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged
+    }
+
+    pub fn start(&mut self) {
+        if self.flagged.is_none() {
+            self.running = true;
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running = false;
+    }
+
+    /// Time left for `color`, counting the move in progress
+    pub fn remaining(&self, color: Color) -> Duration {
+        let remaining = self.remaining[index(color)];
+        if color == self.turn {
+            remaining.saturating_sub(self.charged())
+        } else {
+            remaining
+        }
+    }
+
+    /// The period `color` is playing in
+    pub fn period(&self, color: Color) -> &Period {
+        &self.control.periods[self.period[index(color)]]
+    }
+
+    /// Moves `color` has left to make before the next period's time is added
+    pub fn moves_to_go(&self, color: Color) -> Option<u32> {
+        self.period(color).moves.map(|moves| moves - self.moves_in_period[index(color)])
+    }
+
+    /// Hands the move to `color` without bonuses, as when a move is taken back
+    pub fn set_turn(&mut self, color: Color) {
+        self.turn = color;
+        self.elapsed = Duration::ZERO;
+    }
+
+    /// Advances the clock of the side to move, flagging it once its time runs out
+    pub fn tick(&mut self, dt: Duration) {
+        if !self.running {
+            return;
+        }
+        self.elapsed += dt;
+        if self.charged() >= self.remaining[index(self.turn)] {
+            self.remaining[index(self.turn)] = Duration::ZERO;
+            self.flagged = Some(self.turn);
+            self.running = false;
+        }
+    }
+
+    /// The side to move completes its move and starts the opponent's clock
+    pub fn press(&mut self) {
+        if self.flagged.is_some() {
+            return;
+        }
+        let side = index(self.turn);
+        let period = &self.control.periods[self.period[side]];
+        let mut remaining = self.remaining[side].saturating_sub(self.charged());
+        remaining += match period.bonus {
+            Bonus::Increment(increment) => increment,
+            Bonus::Bronstein(delay) => self.elapsed.min(delay),
+            Bonus::None | Bonus::Delay(_) => Duration::ZERO,
+        };
+
+        self.moves_in_period[side] += 1;
+        if Some(self.moves_in_period[side]) == period.moves {
+            // The last period repeats once its moves are made
+            if self.period[side] + 1 < self.control.periods.len() {
+                self.period[side] += 1;
+            }
+            self.moves_in_period[side] = 0;
+            remaining += self.control.periods[self.period[side]].time;
+        }
+        self.remaining[side] = remaining;
+
+        self.turn = self.turn.opposite();
+        self.elapsed = Duration::ZERO;
+        self.running = true;
+    }
+
+    /// Time taken off the side to move's clock for the move in progress
+    fn charged(&self) -> Duration {
+        match self.control.periods[self.period[index(self.turn)]].bonus {
+            Bonus::Delay(delay) => self.elapsed.saturating_sub(delay),
+            _ => self.elapsed,
+        }
+    }
+}
+
+/// `h:mm:ss`, `m:ss`, or `s.t` under ten seconds
+pub fn format_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else if secs >= 10 {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    } else {
+        format!("{}.{}", secs, d.subsec_millis() / 100)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: u64) -> Duration {
+        Duration::from_secs(s)
+    }
+
+    #[test]
+    fn test_parse_time_control() {
+        assert_eq!("300".parse(), Ok(TimeControl::sudden_death(secs(300))));
+        assert_eq!("180+2".parse(), Ok(TimeControl::fischer(secs(180), secs(2))));
+        assert_eq!("300+d5".parse(), Ok(TimeControl::simple_delay(secs(300), secs(5))));
+        assert_eq!("300+b5".parse(), Ok(TimeControl::bronstein(secs(300), secs(5))));
+        let classical: TimeControl = "40/5400+30:1800+30".parse().unwrap();
+        assert_eq!(classical.periods.len(), 2);
+        assert_eq!(classical.periods[0].moves, Some(40));
+        assert_eq!(classical.to_string(), "40/5400+30:1800+30");
+        assert!("40/".parse::<TimeControl>().is_err());
+        let blitz: TimeControl = "10+0.1".parse().unwrap();
+        assert_eq!(blitz, TimeControl::fischer(secs(10), Duration::from_millis(100)));
+        assert_eq!(blitz.to_string(), "10+0.1");
+        assert!("-5".parse::<TimeControl>().is_err());
+        assert!("abc".parse::<TimeControl>().is_err());
+    }
+
+    #[test]
+    fn test_sudden_death_flag() {
+        let mut clock = Clock::new(TimeControl::sudden_death(secs(10)));
+        clock.start();
+        clock.tick(secs(4));
+        clock.press();
+        assert_eq!(clock.remaining(Color::White), secs(6));
+        clock.tick(secs(3));
+        assert_eq!(clock.remaining(Color::Black), secs(7));
+        clock.press();
+        clock.tick(secs(6));
+        assert_eq!(clock.flagged(), Some(Color::White));
+        assert_eq!(clock.remaining(Color::White), Duration::ZERO);
+        assert!(!clock.is_running());
+    }
+
+    #[test]
+    fn test_fischer_increment() {
+        let mut clock = Clock::new(TimeControl::fischer(secs(60), secs(2)));
+        clock.start();
+        clock.tick(secs(5));
+        clock.press();
+        assert_eq!(clock.remaining(Color::White), secs(57));
+    }
+
+    #[test]
+    fn test_bronstein_delay() {
+        let mut clock = Clock::new(TimeControl::bronstein(secs(60), secs(3)));
+        clock.start();
+        clock.tick(secs(2));
+        clock.press();
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        clock.tick(secs(5));
+        clock.press();
+        assert_eq!(clock.remaining(Color::Black), secs(58));
+    }
+
+    #[test]
+    fn test_simple_delay() {
+        let mut clock = Clock::new(TimeControl::simple_delay(secs(60), secs(3)));
+        clock.start();
+        clock.tick(secs(2));
+        assert_eq!(clock.remaining(Color::White), secs(60));
+        clock.tick(secs(3));
+        assert_eq!(clock.remaining(Color::White), secs(58));
+        clock.press();
+        assert_eq!(clock.remaining(Color::White), secs(58));
+    }
+
+    #[test]
+    fn test_multi_period() {
+        let mut clock = Clock::new("2/100:50+10".parse().unwrap());
+        clock.start();
+        assert_eq!(clock.moves_to_go(Color::White), Some(2));
+        for _ in 0..2 {
+            clock.tick(secs(10));
+            clock.press();
+            clock.press();
+        }
+        // White made two moves in the first period and gets the second period's time
+        assert_eq!(clock.remaining(Color::White), secs(130));
+        assert_eq!(clock.moves_to_go(Color::White), None);
+        assert_eq!(clock.period(Color::White).bonus, Bonus::Increment(secs(10)));
+        clock.tick(secs(10));
+        clock.press();
+        assert_eq!(clock.remaining(Color::White), secs(130));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(secs(3725)), "1:02:05");
+        assert_eq!(format_duration(secs(185)), "3:05");
+        assert_eq!(format_duration(Duration::from_millis(9_450)), "9.4");
+    }
+}
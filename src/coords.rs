@@ -1,6 +1,6 @@
 use crate::pieces::{Piece, PieceType};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct MoveCoords {
     pub piece: Piece,
     pub from: Coords,
@@ -9,6 +9,8 @@ pub struct MoveCoords {
     pub promotion: Option<PieceType>,
     pub king_side_castle: bool,
     pub queen_side_castle: bool,
+    /// Placed from the pocket on `to`, `from` is the same square
+    pub drop: bool,
 }
 
 impl MoveCoords {
@@ -21,6 +23,17 @@ impl MoveCoords {
             promotion: Some(piece),
             king_side_castle: self.king_side_castle,
             queen_side_castle: self.queen_side_castle,
+            drop: self.drop,
+        }
+    }
+    /// Long algebraic notation as used by UCI, e.g. `e2e4`, `e1g1`, `e7e8q` or `N@f3`
+    pub fn uci(&self) -> String {
+        if self.drop {
+            return format!("{}@{}", self.piece.piece_type.letter(), self.to);
+        }
+        match self.promotion {
+            Some(promotion) => format!("{}{}{}", self.from, self.to, promotion.letter().to_ascii_lowercase()),
+            None => format!("{}{}", self.from, self.to),
         }
     }
 }
@@ -35,6 +48,7 @@ impl Default for MoveCoords {
             promotion: None,
             king_side_castle: false,
             queen_side_castle: false,
+            drop: false,
         }
     }
 }
@@ -55,7 +69,7 @@ impl Coords {
         Coords { x, y }
     }
     pub fn std(&self) -> (char, u8) {
-        let x = (self.x as u8 + 97) as char;
+        let x = (self.x + 97) as char;
         let y = 8 - self.y;
         (x, y)
     }
@@ -63,11 +77,21 @@ impl Coords {
         self.y + 1
     }
     pub fn col(&self) -> char {
-        (self.x as u8 + 97) as char
+        (self.x + 97) as char
     }
     pub fn rdr(&self) -> (u8, u8) {
         (self.x, self.y)
     }
+    /// Square number 0..64 in the same order as `all`, a8 first
+    pub fn index(&self) -> u8 {
+        self.y * 8 + self.x
+    }
+    pub fn from_index(index: u8) -> Self {
+        Coords::new(index % 8, index / 8)
+    }
+    pub fn all() -> impl Iterator<Item = Coords> {
+        (0..8).flat_map(|y| (0..8).map(move |x| Coords::new(x, y)))
+    }
 }
 
 impl std::str::FromStr for Coords {
@@ -81,10 +105,19 @@ impl std::str::FromStr for Coords {
         };
         let y = match chars.next() {
             Some(c) => c,
-            None => return Err("No input"),
+            None => return Err("Missing rank"),
         };
-        let x = x as u8 - 97;
-        let y = 8 - y.to_digit(10).unwrap() as u8;
+        if chars.next().is_some() {
+            return Err("A square is a file and a rank, like e4");
+        }
+        if !('a'..='h').contains(&x) {
+            return Err("File must be a to h");
+        }
+        if !('1'..='8').contains(&y) {
+            return Err("Rank must be 1 to 8");
+        }
+        let x = x as u8 - b'a';
+        let y = 8 - (y as u8 - b'0');
         Ok(Coords::new(x, y))
     }
 }
@@ -124,6 +157,9 @@ impl From<(i32, i32)> for Coords {
 impl std::fmt::Display for MoveCoords {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let (x, y) = self.to.std();
+        if self.drop {
+            return write!(f, "{}@{}{}", self.piece, x, y);
+        }
         if PieceType::Pawn == self.piece.piece_type && !self.takes {
             return write!(f, "{}{}", x, y);
         }
@@ -236,4 +272,15 @@ mod tests {
 
         assert_eq!("h1".parse(), Ok(Coords::new(7, 7)));
     }
+
+    #[test]
+    fn test_invalid_coords() {
+        assert!("z9".parse::<Coords>().is_err());
+        assert!("a0".parse::<Coords>().is_err());
+        assert!("i1".parse::<Coords>().is_err());
+        assert!("A1".parse::<Coords>().is_err());
+        assert!("e".parse::<Coords>().is_err());
+        assert!("e44".parse::<Coords>().is_err());
+        assert!("".parse::<Coords>().is_err());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,214 @@
+use crate::{
+    board::{Board, MoveError},
+    chess_move::Move,
+    coords::{Coords, MoveCoords},
+    eval::piece_value,
+    pieces::{Color, Piece, PieceType},
+    variant::Variant,
+};
+
+/// Pieces in the order pockets list them, in FEN and in the window
+pub const POCKET_PIECES: [PieceType; 5] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::Pawn];
+
+/// Captured pieces change sides and may be dropped back on the board instead of moving
+pub struct Crazyhouse;
+
+impl Variant for Crazyhouse {
+    fn name(&self) -> &'static str {
+        "Crazyhouse"
+    }
+
+    fn has_pockets(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, board: &mut Board, mv: Move, captured: Piece) {
+        if captured.piece_type != PieceType::Empty {
+            let piece_type = if board.is_promoted(mv.to()) { PieceType::Pawn } else { captured.piece_type };
+            board.add_to_hand(captured.color.opposite(), piece_type);
+        }
+        track_promotions(board, mv);
+    }
+
+    fn evaluate_white(&self, board: &Board, score: i32) -> i32 {
+        score + pocket_value(board, Color::White) - pocket_value(board, Color::Black)
+    }
+}
+
+/// Crazyhouse for teams of two on two boards, where captures go to the partner's hand. Each board
+/// is its own game, and whoever runs them passes on what `captured_for_hand` reports with `add_to_hand`.
+/// Nothing here runs the partner board yet, so it isn't among `VARIANTS`
+pub struct Bughouse;
+
+impl Variant for Bughouse {
+    fn name(&self) -> &'static str {
+        "Bughouse"
+    }
+
+    fn has_pockets(&self) -> bool {
+        true
+    }
+
+    fn after_move(&self, board: &mut Board, mv: Move, _captured: Piece) {
+        track_promotions(board, mv);
+    }
+
+    fn evaluate_white(&self, board: &Board, score: i32) -> i32 {
+        score + pocket_value(board, Color::White) - pocket_value(board, Color::Black)
+    }
+}
+
+fn bit(square: Coords) -> u64 {
+    1 << square.index()
+}
+
+/// Moves the promoted mark along with the piece, and sets it on a pawn which just promoted
+fn track_promotions(board: &mut Board, mv: Move) {
+    let (from, to) = (bit(mv.from()), bit(mv.to()));
+    let promoted = mv.promotion().is_some() || (!mv.is_drop() && board.promoted & from != 0);
+    board.promoted &= !(from | to);
+    if promoted {
+        board.promoted |= to;
+    }
+}
+
+fn pocket_value(board: &Board, color: Color) -> i32 {
+    POCKET_PIECES.iter().map(|piece_type| piece_value(*piece_type) * board.in_hand(color, *piece_type) as i32).sum()
+}
+
+impl Board {
+    /// Pieces of `piece_type` which `color` holds in hand
+    pub fn in_hand(&self, color: Color, piece_type: PieceType) -> u8 {
+        self.pockets[color as usize][piece_type as usize]
+    }
+
+    /// Gives `color` a piece to drop, as when the partner's board captures one in Bughouse
+    pub fn add_to_hand(&mut self, color: Color, piece_type: PieceType) {
+        self.pockets[color as usize][piece_type as usize] += 1;
+    }
+
+    /// Whether the piece on `square` was a pawn before promoting
+    pub fn is_promoted(&self, square: Coords) -> bool {
+        self.promoted & bit(square) != 0
+    }
+
+    /// The piece `mc` captures as it goes into a pocket, promoted pieces going back as pawns
+    pub fn captured_for_hand(&self, mc: &MoveCoords) -> Option<PieceType> {
+        if !mc.takes {
+            return None;
+        }
+        Some(match self.get_piece(mc.to).piece_type {
+            // Taking en passant lands on an empty square
+            PieceType::Empty => PieceType::Pawn,
+            _ if self.is_promoted(mc.to) => PieceType::Pawn,
+            piece_type => piece_type,
+        })
+    }
+
+    /// Checks the side to move may drop a `piece_type` from its hand on `to`
+    pub fn legal_drop(&self, piece_type: PieceType, to: Coords) -> Result<MoveCoords, MoveError> {
+        self.legal_moves().into_iter().find(|mc| mc.drop && mc.piece.piece_type == piece_type && mc.to == to).ok_or(MoveError::IllegalDrop { piece_type, to })
+    }
+
+    pub fn drop_piece(&mut self, piece_type: PieceType, to: Coords) -> Result<MoveCoords, MoveError> {
+        let mc = self.legal_drop(piece_type, to)?;
+        log::debug!("{:?}", mc);
+        self.apply(&mc);
+        self.undone.clear();
+        Ok(mc)
+    }
+
+    /// Places a piece from the hand of the side to move on `to` and passes the turn
+    pub(crate) fn drop_unchecked(&mut self, piece_type: PieceType, to: Coords) {
+        let color = self.turn;
+        let count = &mut self.pockets[color as usize][piece_type as usize];
+        *count = count.saturating_sub(1);
+        self.state[to.y as usize][to.x as usize] = Piece { piece_type, color };
+        if piece_type == PieceType::Pawn {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.en_passant = None;
+        self.turn = color.opposite();
+    }
+
+    /// Pieces in hand as in the `[Qn]` FEN pocket, White's first
+    pub fn pocket_fen(&self) -> String {
+        let mut pocket = String::new();
+        for color in [Color::White, Color::Black] {
+            for piece_type in POCKET_PIECES {
+                for _ in 0..self.in_hand(color, piece_type) {
+                    pocket.push(Piece { piece_type, color }.fen_char());
+                }
+            }
+        }
+        pocket
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Outcome;
+
+    #[test]
+    fn test_drops() {
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1", &Crazyhouse).unwrap();
+        assert_eq!(board.perft(1), 5 + 62);
+        // Pawns may not be dropped on the first or last rank
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/8/4K3[P] w - - 0 1", &Crazyhouse).unwrap();
+        assert_eq!(board.perft(1), 5 + 62 - 14);
+        let mut board = Board::from_fen_variant("4k3/8/8/8/8/8/8/4K3[Nq] w - - 0 1", &Crazyhouse).unwrap();
+        let mc = board.parse_move("N@f3").unwrap();
+        assert_eq!(board.san(&mc), "N@f3");
+        assert_eq!(mc.uci(), "N@f3");
+        board.apply(&mc);
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/5N2/8/4K3[q] b - - 1 1");
+        assert_eq!(board.in_hand(Color::White, PieceType::Knight), 0);
+        assert_eq!(board.legal_drop(PieceType::Rook, "e2".parse().unwrap()), Err(MoveError::IllegalDrop { piece_type: PieceType::Rook, to: "e2".parse().unwrap() }));
+        board.drop_piece(PieceType::Queen, "e2".parse().unwrap()).unwrap();
+        assert_eq!(board.san_moves(), vec!["N@f3", "Q@e2+"]);
+        assert!(board.parse_san("@e4").is_none());
+    }
+
+    #[test]
+    fn test_captures_fill_the_pocket() {
+        let mut board = Board::new_variant(&Crazyhouse);
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR[] w KQkq - 0 1");
+        for san in ["e4", "d5", "exd5", "Qxd5", "Nc3", "Qxg2"] {
+            board.apply(&board.parse_san(san).unwrap());
+        }
+        assert_eq!(board.pocket_fen(), "Ppp");
+        board.apply(&board.parse_san("@e4").unwrap());
+        assert_eq!(board.to_fen(), "rnb1kbnr/ppp1pppp/8/8/4P3/2N5/PPPP1PqP/R1BQKBNR[pp] b KQkq - 0 4");
+        // A promoted piece goes back to the pocket as a pawn
+        let mut board = Board::from_fen_variant("4k3/8/8/8/8/8/6K1/7q~[] w - - 0 1", &Crazyhouse).unwrap();
+        assert!(board.is_promoted("h1".parse().unwrap()));
+        assert_eq!(board.to_fen(), "4k3/8/8/8/8/8/6K1/7q~[] w - - 0 1");
+        let takes = board.parse_san("Kxh1").unwrap();
+        assert_eq!(board.captured_for_hand(&takes), Some(PieceType::Pawn));
+        board.apply(&takes);
+        assert_eq!(board.pocket_fen(), "P");
+        assert_eq!(board.outcome(), None);
+    }
+
+    #[test]
+    fn test_drop_mate_and_bughouse() {
+        // Dropping the queen next to the cornered king mates
+        let mut board = Board::from_fen_variant("7k/8/6K1/8/8/8/8/8[Q] w - - 0 1", &Crazyhouse).unwrap();
+        board.apply(&board.parse_san("Q@g7").unwrap());
+        assert_eq!(board.outcome(), Some(Outcome::Checkmate { winner: Color::White }));
+        // A ninth rank works as the pocket too
+        let mut board = Board::from_fen_variant("4k3/8/8/3p4/4P3/8/8/4K3/ w - - 0 1", &Bughouse).unwrap();
+        let mc = board.parse_san("exd5").unwrap();
+        assert_eq!(board.captured_for_hand(&mc), Some(PieceType::Pawn));
+        board.apply(&mc);
+        assert_eq!(board.pocket_fen(), "");
+        board.add_to_hand(Color::Black, PieceType::Knight);
+        assert!(board.parse_move("N@d6").is_some());
+    }
+}
@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    board::Board,
+    book::{decode_move, encode_move},
+    coords::MoveCoords,
+    pgn::{start_board, Pgn},
+    pieces::Color,
+};
+
+/// Start of every database file, followed by the format version
+const MAGIC: &[u8; 4] = b"CHDB";
+const VERSION: u8 = 1;
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io(PathBuf, std::io::Error),
+    /// The file isn't a database this version can read, for the reason given
+    Format(&'static str),
+}
+
+impl std::fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            DatabaseError::Io(path, e) => write!(f, "could not access {}: {}", path.display(), e),
+            DatabaseError::Format(reason) => write!(f, "not a game database: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// One stored game: its headers, Polyglot moves, and the Polyglot key of every position from the start
+#[derive(Debug, Clone, PartialEq)]
+struct StoredGame {
+    headers: Vec<(String, String)>,
+    moves: Vec<u16>,
+    keys: Vec<u64>,
+}
+
+impl StoredGame {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Which games a query keeps. Players match case-insensitively on part of the name, dates compare
+/// as PGN dates so `--from 2020` includes all of 2020 and `--to 2020.06` all of June
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Filter {
+    /// Either side
+    pub player: Option<String>,
+    pub white: Option<String>,
+    pub black: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*`
+    pub result: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, game: &StoredGame) -> bool {
+        let name = |side: &str, wanted: &Option<String>| match wanted {
+            Some(wanted) => game.header(side).unwrap_or("").to_lowercase().contains(&wanted.to_lowercase()),
+            None => true,
+        };
+        let player = match &self.player {
+            Some(_) => name("White", &self.player) || name("Black", &self.player),
+            None => true,
+        };
+        // Unknown dates only pass when no range is asked for
+        let date = game.header("Date").filter(|date| date.starts_with(|c: char| c.is_ascii_digit()));
+        let from = self.from.as_ref().is_none_or(|from| date.is_some_and(|date| date >= from.as_str()));
+        let to = self.to.as_ref().is_none_or(|to| date.is_some_and(|date| date.get(..to.len()).unwrap_or(date) <= to.as_str()));
+        let result = self.result.as_ref().is_none_or(|result| game.header("Result").unwrap_or("*") == result);
+        player && name("White", &self.white) && name("Black", &self.black) && from && to && result
+    }
+}
+
+/// A move played from a position in the explorer, with how the games went on
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplorerMove {
+    pub mc: MoveCoords,
+    pub games: u32,
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+impl ExplorerMove {
+    /// Share of the points won by `color`, counting draws as half, over the games with a result
+    pub fn score(&self, color: Color) -> f64 {
+        let decided = self.white_wins + self.draws + self.black_wins;
+        if decided == 0 {
+            return 0.5;
+        }
+        let wins = if color == Color::White { self.white_wins } else { self.black_wins };
+        (wins as f64 + self.draws as f64 / 2.0) / decided as f64
+    }
+}
+
+/// Games and their counts after an import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub games: usize,
+    /// Games with illegal moves, bad headers or drops, left out
+    pub skipped: usize,
+}
+
+/// A local store of games, indexed by every position they reach
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Database {
+    games: Vec<StoredGame>,
+    /// Polyglot key to the games and plies reaching it
+    index: HashMap<u64, Vec<(u32, u16)>>,
+}
+
+/// Reads the big endian fields of a database file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8], DatabaseError> {
+        if self.bytes.len() < len {
+            return Err(DatabaseError::Format("file is truncated"));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, DatabaseError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, DatabaseError> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, DatabaseError> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, DatabaseError> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| DatabaseError::Format("header isn't UTF-8"))
+    }
+}
+
+fn push_string(bytes: &mut Vec<u8>, text: &str) {
+    // Headers longer than 64K are cut at a character boundary
+    let mut end = text.len().min(u16::MAX as usize);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    bytes.extend_from_slice(&(end as u16).to_be_bytes());
+    bytes.extend_from_slice(&text.as_bytes()[..end]);
+}
+
+impl Database {
+    pub fn new() -> Self {
+        Database::default()
+    }
+
+    /// Reads a database written by `to_bytes`: the magic and version, the number of games, then for each
+    /// game its headers as length-prefixed strings, its moves and the keys of the positions it reaches
+    pub fn from_bytes(bytes: &[u8]) -> Result<Database, DatabaseError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4).ok() != Some(MAGIC.as_slice()) {
+            return Err(DatabaseError::Format("missing CHDB header"));
+        }
+        if reader.take(1)?[0] != VERSION {
+            return Err(DatabaseError::Format("unsupported version"));
+        }
+        let mut database = Database::new();
+        for _ in 0..reader.u32()? {
+            let headers = (0..reader.u16()?).map(|_| Ok((reader.string()?, reader.string()?))).collect::<Result<Vec<_>, DatabaseError>>()?;
+            let plies = reader.u16()? as usize;
+            let moves = (0..plies).map(|_| reader.u16()).collect::<Result<Vec<_>, _>>()?;
+            let keys = (0..=plies).map(|_| reader.u64()).collect::<Result<Vec<_>, _>>()?;
+            database.insert(StoredGame { headers, moves, keys });
+        }
+        if !reader.bytes.is_empty() {
+            return Err(DatabaseError::Format("unexpected bytes after the last game"));
+        }
+        Ok(database)
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Database, DatabaseError> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|e| DatabaseError::Io(path.to_path_buf(), e))?;
+        Database::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(self.games.len() as u32).to_be_bytes());
+        for game in &self.games {
+            bytes.extend_from_slice(&(game.headers.len() as u16).to_be_bytes());
+            for (name, value) in &game.headers {
+                push_string(&mut bytes, name);
+                push_string(&mut bytes, value);
+            }
+            bytes.extend_from_slice(&(game.moves.len() as u16).to_be_bytes());
+            for mv in &game.moves {
+                bytes.extend_from_slice(&mv.to_be_bytes());
+            }
+            for key in &game.keys {
+                bytes.extend_from_slice(&key.to_be_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), DatabaseError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes()).map_err(|e| DatabaseError::Io(path.to_path_buf(), e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    fn insert(&mut self, game: StoredGame) {
+        let id = self.games.len() as u32;
+        for (ply, key) in game.keys.iter().enumerate() {
+            self.index.entry(*key).or_default().push((id, ply as u16));
+        }
+        self.games.push(game);
+    }
+
+    /// Stores `pgn`, unless it has drops, which Polyglot moves can't hold, or too many moves
+    pub fn add(&mut self, pgn: &Pgn) -> bool {
+        let moves = &pgn.board.moves;
+        if moves.len() >= u16::MAX as usize || pgn.headers.len() > u16::MAX as usize || moves.iter().any(|mc| mc.drop) {
+            return false;
+        }
+        let mut board = pgn.board.replay(0);
+        let mut keys = vec![board.polyglot_key()];
+        for mc in moves {
+            board.apply(mc);
+            keys.push(board.polyglot_key());
+        }
+        self.insert(StoredGame { headers: pgn.headers.clone(), moves: moves.iter().map(encode_move).collect(), keys });
+        true
+    }
+
+    /// Adds every game of a PGN file read from `input` one game at a time, so files of any size
+    /// fit, and skipping those which don't parse. Text which isn't UTF-8 is read as far as it can be
+    pub fn import(&mut self, mut input: impl BufRead) -> std::io::Result<Imported> {
+        let mut imported = Imported::default();
+        let mut game = String::new();
+        let mut in_movetext = false;
+        let mut line = vec![];
+        let mut finish = |game: &mut String, database: &mut Database| {
+            if !game.trim().is_empty() {
+                match Pgn::parse(game) {
+                    Ok(pgn) if database.add(&pgn) => imported.games += 1,
+                    Ok(_) => imported.skipped += 1,
+                    Err(e) => {
+                        log::debug!("Skipping game: {}", e);
+                        imported.skipped += 1;
+                    }
+                }
+            }
+            game.clear();
+        };
+        loop {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            let trimmed = text.trim();
+            if trimmed.starts_with('[') {
+                if in_movetext {
+                    finish(&mut game, self);
+                    in_movetext = false;
+                }
+            } else if !trimmed.is_empty() {
+                in_movetext = true;
+            }
+            game.push_str(trimmed);
+            game.push('\n');
+        }
+        finish(&mut game, self);
+        Ok(imported)
+    }
+
+    /// Tag pairs of game `id`, without replaying its moves
+    pub fn headers(&self, id: usize) -> Option<&[(String, String)]> {
+        self.games.get(id).map(|game| game.headers.as_slice())
+    }
+
+    /// Game `id` as it was imported, less any comments and variations
+    pub fn game(&self, id: usize) -> Result<Pgn, String> {
+        let stored = self.games.get(id).ok_or_else(|| format!("No game {} in the database", id + 1))?;
+        let mut pgn = Pgn::new(start_board(&stored.headers)?);
+        pgn.headers = stored.headers.clone();
+        for mv in &stored.moves {
+            let mc = decode_move(&pgn.board, *mv).ok_or_else(|| format!("Game {} has an illegal move", id))?;
+            pgn.board.apply(&mc);
+        }
+        Ok(pgn)
+    }
+
+    /// Games and the first ply at which they reach the position of `board`, in the order they were added
+    fn occurrences(&self, board: &Board) -> Vec<(usize, usize)> {
+        let mut found: Vec<(usize, usize)> = vec![];
+        for (id, ply) in self.index.get(&board.polyglot_key()).map_or(&[][..], |found| found.as_slice()) {
+            if found.last().is_none_or(|(last, _)| *last != *id as usize) {
+                found.push((*id as usize, *ply as usize));
+            }
+        }
+        found
+    }
+
+    /// Every game that reaches the position of `board`, by any move order
+    pub fn games_reaching(&self, board: &Board) -> Vec<usize> {
+        self.search(&Filter::default(), Some(board))
+    }
+
+    /// Games passing `filter`, and reaching `position` if one is given
+    pub fn search(&self, filter: &Filter, position: Option<&Board>) -> Vec<usize> {
+        let ids = match position {
+            Some(board) => self.occurrences(board).into_iter().map(|(id, _)| id).collect(),
+            None => (0..self.games.len()).collect::<Vec<_>>(),
+        };
+        ids.into_iter().filter(|id| filter.matches(&self.games[*id])).collect()
+    }
+
+    /// The moves played from the position of `board` in games passing `filter`, most played first
+    pub fn explore(&self, board: &Board, filter: &Filter) -> Vec<ExplorerMove> {
+        let mut moves: Vec<ExplorerMove> = vec![];
+        for (id, ply) in self.occurrences(board) {
+            let game = &self.games[id];
+            let Some(mv) = game.moves.get(ply).filter(|_| filter.matches(game)) else { continue };
+            // Checking the move is legal also rules out another position sharing the key
+            let Some(mc) = decode_move(board, *mv) else { continue };
+            let index = match moves.iter().position(|explored| explored.mc == mc) {
+                Some(index) => index,
+                None => {
+                    moves.push(ExplorerMove { mc, games: 0, white_wins: 0, draws: 0, black_wins: 0 });
+                    moves.len() - 1
+                }
+            };
+            let explored = &mut moves[index];
+            explored.games += 1;
+            match game.header("Result") {
+                Some("1-0") => explored.white_wins += 1,
+                Some("0-1") => explored.black_wins += 1,
+                Some("1/2-1/2") => explored.draws += 1,
+                _ => (),
+            }
+        }
+        moves.sort_by_key(|explored| std::cmp::Reverse(explored.games));
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAMES: &str = "\
+[Event \"Club\"]
+[Date \"2023.05.01\"]
+[White \"Alice\"]
+[Black \"Bob\"]
+[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 Nc6 3. Bb5 {Spanish} 1-0
+
+[Event \"Club\"]
+[Date \"2024.02.10\"]
+[White \"Bob\"]
+[Black \"Carol\"]
+[Result \"1/2-1/2\"]
+
+1. Nf3 Nc6 2. e4 e5 1/2-1/2
+
+[White \"Dave\"]
+[Black \"Alice\"]
+[Result \"0-1\"]
+
+1. e4 c5 2. Nf3 Nc6 3. Qxf7 0-1
+
+[Date \"2024.06.30\"]
+[White \"Carol\"]
+[Black \"Alice\"]
+[Result \"0-1\"]
+
+1. d4 d5 0-1
+";
+
+    fn board(sans: &[&str]) -> Board {
+        let mut board = Board::new();
+        for san in sans {
+            board.apply(&board.parse_san(san).unwrap());
+        }
+        board
+    }
+
+    #[test]
+    fn test_import_and_search() {
+        let mut database = Database::new();
+        let imported = database.import(GAMES.as_bytes()).unwrap();
+        // The third game has an illegal move
+        assert_eq!(imported, Imported { games: 3, skipped: 1 });
+        assert_eq!(Database::from_bytes(&database.to_bytes()).unwrap(), database);
+
+        // Both move orders reach the Four Knights' starting point
+        let position = board(&["e4", "e5", "Nf3", "Nc6"]);
+        assert_eq!(database.games_reaching(&position), vec![0, 1]);
+        assert_eq!(database.games_reaching(&board(&["e4", "c5"])), Vec::<usize>::new());
+        let player = |name: &str| Filter { player: Some(name.to_string()), ..Filter::default() };
+        assert_eq!(database.search(&player("alice"), None), vec![0, 2]);
+        assert_eq!(database.search(&player("carol"), Some(&position)), vec![1]);
+        let dates = Filter { from: Some("2024".to_string()), to: Some("2024.02".to_string()), ..Filter::default() };
+        assert_eq!(database.search(&dates, None), vec![1]);
+        let result = Filter { result: Some("0-1".to_string()), ..Filter::default() };
+        assert_eq!(database.search(&result, None), vec![2]);
+
+        let pgn = database.game(0).unwrap();
+        assert_eq!(pgn.header("White"), Some("Alice"));
+        assert_eq!(pgn.board.san_moves(), vec!["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert!(database.headers(1).unwrap().contains(&("Black".to_string(), "Carol".to_string())));
+        assert_eq!(database.headers(3), None);
+        assert_eq!(database.game(3).err(), Some("No game 4 in the database".to_string()));
+        assert!(Database::from_bytes(b"CHDB\x01\x00").is_err());
+        assert!(Database::from_bytes(b"PGN!").is_err());
+    }
+
+    #[test]
+    fn test_explorer() {
+        let mut database = Database::new();
+        database.import(GAMES.as_bytes()).unwrap();
+        let start = Board::new();
+        let moves = database.explore(&start, &Filter::default());
+        let summary = moves.iter().map(|explored| (start.san(&explored.mc), explored.games, explored.score(Color::White))).collect::<Vec<_>>();
+        assert_eq!(summary, vec![("e4".to_string(), 1, 1.0), ("Nf3".to_string(), 1, 0.5), ("d4".to_string(), 1, 0.0)]);
+        let position = board(&["e4", "e5", "Nf3", "Nc6"]);
+        let moves = database.explore(&position, &Filter::default());
+        assert_eq!(moves.len(), 1);
+        assert_eq!((moves[0].games, moves[0].white_wins), (1, 1));
+        // The drawn game ends in the position
+        assert_eq!(database.search(&Filter::default(), Some(&position)).len(), 2);
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::{board::Board, coords::MoveCoords};
+
+/// Openings in the layout of the Lichess chess-openings tables: ECO code, name and moves, tab separated
+const BUNDLED: &str = include_str!("../assets/eco.tsv");
+
+/// A named opening line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opening {
+    pub eco: String,
+    pub name: String,
+    /// The moves defining it from the initial position
+    pub moves: Vec<MoveCoords>,
+}
+
+impl std::fmt::Display for Opening {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.eco, self.name)
+    }
+}
+
+/// Openings indexed by the Polyglot key of the position each one ends in, so lines are
+/// recognised whatever order their moves came in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EcoTable {
+    openings: Vec<Opening>,
+    positions: HashMap<u64, usize>,
+}
+
+impl EcoTable {
+    /// Reads `eco`, `name` and `pgn` columns, skipping the header line. The first opening listed
+    /// for a position is the one reported
+    pub fn parse(text: &str) -> Result<EcoTable, String> {
+        let mut table = EcoTable::default();
+        for line in text.lines().filter(|line| !line.trim().is_empty() && !line.starts_with("eco\t")) {
+            let mut columns = line.split('\t');
+            let (Some(eco), Some(name), Some(pgn)) = (columns.next(), columns.next(), columns.next()) else {
+                return Err(format!("Expected ECO, name and moves: {}", line));
+            };
+            let mut board = Board::new();
+            for san in pgn.split_whitespace().filter(|token| !token.ends_with('.')) {
+                let mc = board.parse_san(san).ok_or_else(|| format!("Illegal move {} in {}", san, name))?;
+                board.apply(&mc);
+            }
+            table.positions.entry(board.polyglot_key()).or_insert(table.openings.len());
+            table.openings.push(Opening { eco: eco.to_string(), name: name.to_string(), moves: board.moves });
+        }
+        Ok(table)
+    }
+
+    /// The table shipped with the crate. It is partial, about 170 common lines across 130 ECO codes,
+    /// so games leaving them are named after the last of those they passed through. The full
+    /// Lichess `a.tsv` to `e.tsv` files can be read with `parse` instead
+    pub fn bundled() -> &'static EcoTable {
+        static TABLE: OnceLock<EcoTable> = OnceLock::new();
+        TABLE.get_or_init(|| EcoTable::parse(BUNDLED).expect("bundled openings are valid"))
+    }
+
+    pub fn len(&self) -> usize {
+        self.openings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.openings.is_empty()
+    }
+
+    /// The opening ending in exactly the position of `board`
+    pub fn lookup(&self, board: &Board) -> Option<&Opening> {
+        if !board.is_standard() {
+            return None;
+        }
+        self.positions.get(&board.polyglot_key()).map(|i| &self.openings[*i])
+    }
+
+    /// The opening of the game so far: the last position along its moves with a name, so a game
+    /// stays classified once it leaves the table
+    pub fn classify(&self, board: &Board) -> Option<&Opening> {
+        let mut position = board.replay(0);
+        let mut opening = self.lookup(&position);
+        for mc in &board.moves {
+            position.apply(mc);
+            opening = self.lookup(&position).or(opening);
+        }
+        opening
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(sans: &str) -> Board {
+        let mut board = Board::new();
+        for san in sans.split_whitespace() {
+            board.apply(&board.parse_san(san).unwrap());
+        }
+        board
+    }
+
+    #[test]
+    fn test_classify() {
+        let table = EcoTable::bundled();
+        assert!(table.len() > 150);
+        assert_eq!(table.classify(&Board::new()), None);
+        let najdorf = play("e4 c5 Nf3 d6 d4 cxd4 Nxd4 Nf6 Nc3 a6 f3 e5");
+        assert_eq!(table.classify(&najdorf).unwrap().to_string(), "B90 Sicilian Defense: Najdorf Variation");
+        assert_eq!(table.lookup(&najdorf), None);
+        // The London System reached by another move order
+        let london = play("d4 d5 Bf4 Nf6 Nf3");
+        assert_eq!(table.classify(&london).unwrap().name, "Queen's Pawn Game: London System");
+        let position = Board::from_fen(&london.to_fen()).unwrap();
+        assert_eq!(table.lookup(&position).unwrap().eco, "D02");
+        assert_eq!(table.classify(&Board::new_variant(&crate::variant::Horde)), None);
+        assert!(EcoTable::parse("A00\tNot a move\t1. e5").is_err());
+        assert!(EcoTable::parse("A00\tPolish Opening").is_err());
+    }
+}
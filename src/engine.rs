@@ -0,0 +1,274 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use crate::{board::Board, coords::MoveCoords, uci::Uci};
+
+/// Time an engine gets to answer `uci` and `isready`
+const STARTUP: Duration = Duration::from_secs(10);
+
+/// How to start one side of a match
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineConfig {
+    pub name: String,
+    /// Program to run, or `None` for this crate's own engine on a thread
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    /// Sent with `setoption` once the engine is up
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    pub fn builtin() -> Self {
+        EngineConfig { name: "chess-engine".to_string(), command: None, args: vec![], options: vec![] }
+    }
+
+    /// Parses `builtin` or `cmd=PATH`, followed by any of `name=NAME`, `arg=ARG` and `option.NAME=VALUE`
+    pub fn parse(words: &[String]) -> Result<Self, String> {
+        let mut config = EngineConfig::builtin();
+        let mut named = false;
+        for word in words {
+            if word == "builtin" {
+                config.command = None;
+                continue;
+            }
+            let (key, value) = word.split_once('=').ok_or_else(|| format!("Expected key=value, got {}", word))?;
+            match key {
+                "cmd" => {
+                    config.command = Some(value.to_string());
+                    if !named {
+                        config.name = value.rsplit(['/', '\\']).next().unwrap_or(value).to_string();
+                    }
+                }
+                "name" => {
+                    config.name = value.to_string();
+                    named = true;
+                }
+                "arg" => config.args.push(value.to_string()),
+                _ => match key.strip_prefix("option.") {
+                    Some(option) => config.options.push((option.to_string(), value.to_string())),
+                    None => return Err(format!("Unknown engine setting {}", key)),
+                },
+            }
+        }
+        Ok(config)
+    }
+
+    /// Launches the engine and waits until it is ready
+    pub fn start(&self) -> Result<UciEngine, String> {
+        let mut engine = match &self.command {
+            Some(command) => UciEngine::spawn(&self.name, command, &self.args)?,
+            None => UciEngine::builtin(&self.name),
+        };
+        engine.send("uci")?;
+        engine.wait_for("uciok", STARTUP)?;
+        for (name, value) in &self.options {
+            engine.send(&format!("setoption name {} value {}", name, value))?;
+        }
+        engine.send("isready")?;
+        engine.wait_for("readyok", STARTUP)?;
+        Ok(engine)
+    }
+}
+
+/// Passes on whole lines written to it, for the in-process engine
+struct LineSender {
+    sender: Sender<String>,
+    buffer: Vec<u8>,
+}
+
+impl Write for LineSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        while let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line = String::from_utf8_lossy(&self.buffer[..end]).trim_end().to_string();
+            self.buffer.drain(..=end);
+            self.sender.send(line).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A UCI engine driven from the GUI's side of the protocol
+pub struct UciEngine {
+    pub name: String,
+    input: Box<dyn Write + Send>,
+    /// Lines the engine sends, read on another thread so waiting can time out
+    lines: Receiver<String>,
+    child: Option<Child>,
+    /// Whether `UCI_Chess960` was last set on
+    chess960: bool,
+}
+
+impl UciEngine {
+    fn spawn(name: &str, command: &str, args: &[String]) -> Result<Self, String> {
+        let mut child = Command::new(command)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start {}: {}", command, e))?;
+        let input = child.stdin.take().expect("stdin is piped");
+        let output = child.stdout.take().expect("stdout is piped");
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(UciEngine { name: name.to_string(), input: Box::new(input), lines, child: Some(child), chess960: false })
+    }
+
+    fn builtin(name: &str) -> Self {
+        let (commands, received) = mpsc::channel::<String>();
+        let (sender, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut uci = Uci::new(LineSender { sender, buffer: vec![] });
+            for line in received {
+                if !matches!(uci.command(line.trim()), Ok(true)) {
+                    break;
+                }
+            }
+            let _ = uci.command("stop");
+        });
+        UciEngine { name: name.to_string(), input: Box::new(LineSender { sender: commands, buffer: vec![] }), lines, child: None, chess960: false }
+    }
+
+    pub fn send(&mut self, line: &str) -> Result<(), String> {
+        log::trace!("{} < {}", self.name, line);
+        writeln!(self.input, "{}", line).and_then(|_| self.input.flush()).map_err(|e| format!("{} stopped listening: {}", self.name, e))
+    }
+
+    /// Reads lines until one starting with `word`, which is returned
+    fn wait_for(&mut self, word: &str, timeout: Duration) -> Result<String, String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let line = match self.lines.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => return Err(format!("{} did not send {} in time", self.name, word)),
+                Err(RecvTimeoutError::Disconnected) => return Err(format!("{} exited", self.name)),
+            };
+            log::trace!("{} > {}", self.name, line);
+            if line.split_whitespace().next() == Some(word) {
+                return Ok(line);
+            }
+        }
+    }
+
+    /// Tells the engine a new game starts, and drops anything left over from the last one
+    pub fn new_game(&mut self) -> Result<(), String> {
+        self.send("ucinewgame")?;
+        self.send("isready")?;
+        self.wait_for("readyok", STARTUP).map(|_| ())
+    }
+
+    /// Searches the current position of `board` with `go` arguments such as `depth 5` or
+    /// `wtime 10000 btime 10000`, giving up after `timeout`. Chess960 games turn on `UCI_Chess960`
+    /// and write castling as the king taking its rook
+    pub fn best_move(&mut self, board: &Board, go: &str, timeout: Duration) -> Result<MoveCoords, String> {
+        let chess960 = board.replay(0).is_chess960();
+        if chess960 != self.chess960 {
+            self.send(&format!("setoption name UCI_Chess960 value {}", chess960))?;
+            self.chess960 = chess960;
+        }
+        self.send(&position_command(board))?;
+        self.send(&format!("go {}", go))?;
+        let line = match self.wait_for("bestmove", timeout) {
+            Ok(line) => line,
+            Err(e) => {
+                // Whatever it answers later is thrown away by the next `new_game`
+                let _ = self.send("stop");
+                return Err(e);
+            }
+        };
+        let text = line.split_whitespace().nth(1).unwrap_or("(none)");
+        board.parse_uci(text).ok_or_else(|| format!("{} played the illegal move {}", self.name, text))
+    }
+}
+
+/// The `position` command for the game in `board`, writing Chess960 castling as the king taking its rook
+fn position_command(board: &Board) -> String {
+    let mut replay = board.replay(0);
+    let chess960 = replay.is_chess960();
+    let mut position = match &board.start_fen {
+        Some(fen) => format!("position fen {}", fen),
+        None => "position startpos".to_string(),
+    };
+    if !board.moves.is_empty() {
+        let mut moves = vec![];
+        for mc in &board.moves {
+            moves.push(if chess960 { replay.uci_chess960(mc) } else { mc.uci() });
+            replay.apply(mc);
+        }
+        position += " moves ";
+        position += &moves.join(" ");
+    }
+    position
+}
+
+impl Drop for UciEngine {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        if let Some(child) = &mut self.child {
+            for _ in 0..20 {
+                if matches!(child.try_wait(), Ok(Some(_))) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(25));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(text: &str) -> Vec<String> {
+        text.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = EngineConfig::parse(&words("cmd=engines/stockfish arg=--quiet option.Hash=64")).unwrap();
+        assert_eq!(config.name, "stockfish");
+        assert_eq!(config.command.as_deref(), Some("engines/stockfish"));
+        assert_eq!(config.args, vec!["--quiet"]);
+        assert_eq!(config.options, vec![("Hash".to_string(), "64".to_string())]);
+        let config = EngineConfig::parse(&words("name=base builtin")).unwrap();
+        assert_eq!(config, EngineConfig { name: "base".to_string(), ..EngineConfig::builtin() });
+        assert!(EngineConfig::parse(&words("hash=64")).is_err());
+        assert!(EngineConfig::parse(&words("cmd=no/such/engine")).unwrap().start().is_err());
+    }
+
+    #[test]
+    fn test_builtin_engine() {
+        let mut engine = EngineConfig::builtin().start().unwrap();
+        engine.new_game().unwrap();
+        let mut board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let mc = engine.best_move(&board, "depth 2", Duration::from_secs(30)).unwrap();
+        assert_eq!(mc.uci(), "a1a8");
+        board = Board::new();
+        board.apply(&board.parse_san("e4").unwrap());
+        let mc = engine.best_move(&board, "movetime 50", Duration::from_secs(30)).unwrap();
+        assert!(board.legal_moves().contains(&mc));
+
+        // Castling in Chess960 reaches the engine as the king taking its rook
+        let mut board = Board::from_fen("3k4/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        board.apply(&board.parse_san("O-O-O").unwrap());
+        assert_eq!(position_command(&board), "position fen 3k4/8/8/8/8/8/8/RK5R w KQ - 0 1 moves b1a1");
+        let mc = engine.best_move(&board, "depth 1", Duration::from_secs(30)).unwrap();
+        assert!(engine.chess960);
+        assert!(board.legal_moves().contains(&mc));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::{board::Board, coords::MoveCoords};
+
+/// A position from an Extended Position Description line, the first four FEN fields followed
+/// by operations such as `bm Qg6; id "WAC.001";`
+pub struct Epd {
+    pub board: Board,
+    /// Opcodes with their operands, in the order given
+    pub operations: Vec<(String, Vec<String>)>,
+}
+
+/// Splits `text` at unquoted semicolons into operations, and operations into words, keeping quoted strings whole
+fn operation_tokens(text: &str) -> Result<Vec<Vec<String>>, &'static str> {
+    let mut operations = vec![];
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("Unclosed string in EPD"),
+                    }
+                }
+                words.push(std::mem::take(&mut word));
+            }
+            ';' | ' ' | '\t' => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+                if c == ';' && !words.is_empty() {
+                    operations.push(std::mem::take(&mut words));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    if !words.is_empty() {
+        return Err("EPD operation without a closing semicolon");
+    }
+    Ok(operations)
+}
+
+impl Epd {
+    pub fn parse(line: &str) -> Result<Epd, String> {
+        let line = line.trim();
+        // Fields may be separated by any run of whitespace
+        let mut rest = line;
+        let mut position = vec![];
+        while position.len() < 4 && !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            position.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        if position.len() < 4 {
+            return Err(format!("EPD needs four position fields: {}", line));
+        }
+        let operations = operation_tokens(rest)
+            .map_err(|e| format!("{}: {}", e, line))?
+            .into_iter()
+            .map(|mut words| {
+                let operands = words.split_off(1);
+                (words.remove(0), operands)
+            })
+            .collect::<Vec<(String, Vec<String>)>>();
+        let operand = |opcode: &str, default: &'static str| {
+            operations.iter().find(|(op, _)| op == opcode).and_then(|(_, operands)| operands.first().cloned()).unwrap_or(default.to_string())
+        };
+        let fen = format!("{} {} {}", position.join(" "), operand("hmvc", "0"), operand("fmvn", "1"));
+        let board = Board::from_fen(&fen).map_err(|e| format!("Invalid position {}: {}", line, e))?;
+        for (opcode, operands) in operations.iter().filter(|(op, _)| op == "bm" || op == "am") {
+            if let Some(san) = operands.iter().find(|san| board.parse_san(san).is_none()) {
+                return Err(format!("Illegal {} move {}: {}", opcode, san, line));
+            }
+        }
+        Ok(Epd { board, operations })
+    }
+
+    /// Every position in an EPD file, skipping blank lines and `#` comments
+    pub fn parse_all(text: &str) -> Result<Vec<Epd>, String> {
+        text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).map(Epd::parse).collect()
+    }
+
+    pub fn operands(&self, opcode: &str) -> Option<&[String]> {
+        self.operations.iter().find(|(op, _)| op == opcode).map(|(_, operands)| operands.as_slice())
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.operands("id")?.first().map(|id| id.as_str())
+    }
+
+    /// The comment of `c0` to `c9`
+    pub fn comment(&self, n: u8) -> Option<&str> {
+        self.operands(&format!("c{}", n))?.first().map(|comment| comment.as_str())
+    }
+
+    /// Moves listed under `opcode`, which `parse` has checked are legal
+    fn moves(&self, opcode: &str) -> Vec<MoveCoords> {
+        self.operands(opcode).unwrap_or_default().iter().filter_map(|san| self.board.parse_san(san)).collect()
+    }
+
+    /// Moves to find, from `bm`
+    pub fn best_moves(&self) -> Vec<MoveCoords> {
+        self.moves("bm")
+    }
+
+    /// Moves to avoid, from `am`
+    pub fn avoid_moves(&self) -> Vec<MoveCoords> {
+        self.moves("am")
+    }
+
+    /// Whether `mc` is one of the best moves and none of those to avoid, `None` for positions without either
+    pub fn is_solved_by(&self, mc: &MoveCoords) -> Option<bool> {
+        let (best, avoid) = (self.best_moves(), self.avoid_moves());
+        if best.is_empty() && avoid.is_empty() {
+            return None;
+        }
+        Some((best.is_empty() || best.contains(mc)) && !avoid.contains(mc))
+    }
+
+    /// Points for `mc` and the most to be had, from a `c0` listing moves as `f5=10, Be5+=2` as the
+    /// Strategic Test Suite does
+    pub fn points_for(&self, mc: &MoveCoords) -> Option<(u32, u32)> {
+        let mut points = vec![];
+        for entry in self.comment(0)?.split(',') {
+            let (san, value) = entry.trim().split_once('=')?;
+            points.push((self.board.parse_san(san)?, value.trim().parse::<u32>().ok()?));
+        }
+        let best = points.iter().map(|(_, value)| *value).max()?;
+        Some((points.iter().find(|(listed, _)| listed == mc).map_or(0, |(_, value)| *value), best))
+    }
+}
+
+/// The four position fields, then each operation with strings quoted
+impl std::fmt::Display for Epd {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let fen = self.board.to_fen();
+        write!(f, "{}", fen.split(' ').take(4).collect::<Vec<_>>().join(" "))?;
+        for (opcode, operands) in &self.operations {
+            write!(f, " {}", opcode)?;
+            for operand in operands {
+                if operand.is_empty() || operand.contains([' ', ';', '"']) || opcode == "id" || (opcode.len() == 2 && opcode.starts_with('c')) {
+                    write!(f, " \"{}\"", operand.replace('"', "'"))?;
+                } else {
+                    write!(f, " {}", operand)?;
+                }
+            }
+            write!(f, ";")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WAC_001: &str = "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id \"WAC.001\";";
+
+    #[test]
+    fn test_parse_epd() {
+        let epd = Epd::parse(WAC_001).unwrap();
+        assert_eq!(epd.id(), Some("WAC.001"));
+        assert_eq!(epd.best_moves().iter().map(|mc| epd.board.san(mc)).collect::<Vec<_>>(), vec!["Qg6"]);
+        assert_eq!(epd.is_solved_by(&epd.best_moves()[0]), Some(true));
+        assert_eq!(epd.to_string(), WAC_001);
+        assert_eq!(epd.board.to_fen(), "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1");
+
+        let epd = Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - am Kd1 O-O-O; hmvc 12; fmvn 40; c0 \"a; b\";").unwrap();
+        assert_eq!(epd.board.halfmove_clock, 12);
+        assert_eq!(epd.board.fullmove_number, 40);
+        assert_eq!(epd.comment(0), Some("a; b"));
+        assert_eq!(epd.avoid_moves().len(), 2);
+        assert_eq!(epd.is_solved_by(&epd.board.parse_san("Ra8+").unwrap()), Some(true));
+        assert_eq!(epd.is_solved_by(&epd.board.parse_san("O-O-O").unwrap()), Some(false));
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra8").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - id \"open;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - bm Ra9;").is_err());
+        assert!(Epd::parse("4k3/8/8/8/8/8/8/R3K3 w Q - am Ra8 Kg3;").is_err());
+        // Repeated spaces between fields are allowed
+        let spaced = Epd::parse("2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1  w  -  -   bm Qg6; id \"WAC.001\";").unwrap();
+        assert_eq!(spaced.to_string(), WAC_001);
+    }
+
+    #[test]
+    fn test_sts_points() {
+        let text = "# Strategic Test Suite\n\n1kr5/3n4/q3p2p/p2n2p1/PppB1P2/5BP1/1P2Q2P/3R2K1 w - - bm f5; id \"STS(v1.0) Undermine.001\"; c0 \"f5=10, Be5+=2, Bf2=3, Bg4=2\";\n";
+        let epds = Epd::parse_all(text).unwrap();
+        assert_eq!(epds.len(), 1);
+        let epd = &epds[0];
+        let board = &epd.board;
+        assert_eq!(epd.points_for(&board.parse_san("f5").unwrap()), Some((10, 10)));
+        assert_eq!(epd.points_for(&board.parse_san("Bf2").unwrap()), Some((3, 10)));
+        assert_eq!(epd.points_for(&board.parse_san("Kg2").unwrap()), Some((0, 10)));
+        assert_eq!(Epd::parse(WAC_001).unwrap().points_for(&board.parse_san("f5").unwrap()), None);
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    board::Board,
+    coords::Coords,
+    pieces::{Color, PieceType},
+};
+
+pub fn piece_value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Empty => 0,
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+    }
+}
+
+// Piece-square bonuses for white, rank 8 first like `Board::state`
+const PAWN: [[i32; 8]; 8] = [
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+    [ 50, 50, 50, 50, 50, 50, 50, 50],
+    [ 10, 10, 20, 30, 30, 20, 10, 10],
+    [  5,  5, 10, 25, 25, 10,  5,  5],
+    [  0,  0,  0, 20, 20,  0,  0,  0],
+    [  5, -5,-10,  0,  0,-10, -5,  5],
+    [  5, 10, 10,-20,-20, 10, 10,  5],
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+];
+const KNIGHT: [[i32; 8]; 8] = [
+    [-50,-40,-30,-30,-30,-30,-40,-50],
+    [-40,-20,  0,  0,  0,  0,-20,-40],
+    [-30,  0, 10, 15, 15, 10,  0,-30],
+    [-30,  5, 15, 20, 20, 15,  5,-30],
+    [-30,  0, 15, 20, 20, 15,  0,-30],
+    [-30,  5, 10, 15, 15, 10,  5,-30],
+    [-40,-20,  0,  5,  5,  0,-20,-40],
+    [-50,-40,-30,-30,-30,-30,-40,-50],
+];
+const BISHOP: [[i32; 8]; 8] = [
+    [-20,-10,-10,-10,-10,-10,-10,-20],
+    [-10,  0,  0,  0,  0,  0,  0,-10],
+    [-10,  0,  5, 10, 10,  5,  0,-10],
+    [-10,  5,  5, 10, 10,  5,  5,-10],
+    [-10,  0, 10, 10, 10, 10,  0,-10],
+    [-10, 10, 10, 10, 10, 10, 10,-10],
+    [-10,  5,  0,  0,  0,  0,  5,-10],
+    [-20,-10,-10,-10,-10,-10,-10,-20],
+];
+const ROOK: [[i32; 8]; 8] = [
+    [  0,  0,  0,  0,  0,  0,  0,  0],
+    [  5, 10, 10, 10, 10, 10, 10,  5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [ -5,  0,  0,  0,  0,  0,  0, -5],
+    [  0,  0,  0,  5,  5,  0,  0,  0],
+];
+const QUEEN: [[i32; 8]; 8] = [
+    [-20,-10,-10, -5, -5,-10,-10,-20],
+    [-10,  0,  0,  0,  0,  0,  0,-10],
+    [-10,  0,  5,  5,  5,  5,  0,-10],
+    [ -5,  0,  5,  5,  5,  5,  0, -5],
+    [  0,  0,  5,  5,  5,  5,  0, -5],
+    [-10,  5,  5,  5,  5,  5,  0,-10],
+    [-10,  0,  5,  0,  0,  0,  0,-10],
+    [-20,-10,-10, -5, -5,-10,-10,-20],
+];
+const KING_MIDDLEGAME: [[i32; 8]; 8] = [
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-30,-40,-40,-50,-50,-40,-40,-30],
+    [-20,-30,-30,-40,-40,-30,-30,-20],
+    [-10,-20,-20,-20,-20,-20,-20,-10],
+    [ 20, 20,  0,  0,  0,  0, 20, 20],
+    [ 20, 30, 10,  0,  0, 10, 30, 20],
+];
+const KING_ENDGAME: [[i32; 8]; 8] = [
+    [-50,-40,-30,-20,-20,-30,-40,-50],
+    [-30,-20,-10,  0,  0,-10,-20,-30],
+    [-30,-10, 20, 30, 30, 20,-10,-30],
+    [-30,-10, 30, 40, 40, 30,-10,-30],
+    [-30,-10, 30, 40, 40, 30,-10,-30],
+    [-30,-10, 20, 30, 30, 20,-10,-30],
+    [-30,-30,  0,  0,  0,  0,-30,-30],
+    [-50,-30,-30,-30,-30,-30,-30,-50],
+];
+
+/// Non-pawn material at or below which the kings head for the centre
+const ENDGAME_MATERIAL: i32 = 1300;
+
+/// Static evaluation in centipawns from White's point of view
+pub fn evaluate_white(board: &Board) -> i32 {
+    let non_pawn_material = |color: Color| {
+        board
+            .state
+            .iter()
+            .flatten()
+            .filter(|p| p.color == color && p.piece_type != PieceType::Pawn)
+            .map(|p| piece_value(p.piece_type))
+            .sum::<i32>()
+    };
+    let endgame = non_pawn_material(Color::White) <= ENDGAME_MATERIAL && non_pawn_material(Color::Black) <= ENDGAME_MATERIAL;
+
+    let mut score = 0;
+    for coords in Coords::all() {
+        let piece = board.get_piece(coords);
+        let table = match piece.piece_type {
+            PieceType::Empty => continue,
+            PieceType::Pawn => &PAWN,
+            PieceType::Knight => &KNIGHT,
+            PieceType::Bishop => &BISHOP,
+            PieceType::Rook => &ROOK,
+            PieceType::Queen => &QUEEN,
+            PieceType::King if endgame => &KING_ENDGAME,
+            PieceType::King => &KING_MIDDLEGAME,
+        };
+        let (x, y) = (coords.x as usize, coords.y as usize);
+        match piece.color {
+            Color::White => score += piece_value(piece.piece_type) + table[y][x],
+            Color::Black => score -= piece_value(piece.piece_type) + table[7 - y][x],
+        }
+    }
+    board.variant.evaluate_white(board, score)
+}
+
+/// Static evaluation in centipawns from the side to move's point of view
+pub fn evaluate(board: &Board) -> i32 {
+    match board.turn {
+        Color::White => evaluate_white(board),
+        Color::Black => -evaluate_white(board),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let board = Board::new();
+        assert_eq!(evaluate(&board), 0);
+        // Black is a knight down, mirrored for the side to move
+        let board = Board::from_fen("r1bqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert!(evaluate_white(&board) > 250);
+        assert_eq!(evaluate(&board), -evaluate_white(&board));
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    board::{back_rank, Board, CastlingRights},
+    coords::Coords,
+    pieces::{Color, Piece, PieceType},
+    variant::{Standard, Variant},
+};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl Board {
+    pub fn from_fen(fen: &str) -> Result<Board, &'static str> {
+        Board::from_fen_variant(fen, &Standard)
+    }
+
+    /// Reads a position of `variant`, with the checks each side still needs to give after the en passant square for Three-check,
+    /// and the pieces in hand after the placement as `[Qn]` or a ninth rank for Crazyhouse
+    pub fn from_fen_variant(fen: &str, variant: &'static dyn Variant) -> Result<Board, &'static str> {
+        let mut fields = fen.split_whitespace().peekable();
+        let placement = fields.next().ok_or("Empty FEN")?;
+        let turn = fields.next().unwrap_or("w");
+        let castling = fields.next().unwrap_or("-");
+        let en_passant = fields.next().unwrap_or("-");
+        let remaining_checks = fields.next_if(|field| field.contains('+'));
+        let halfmove_clock = fields.next().unwrap_or("0");
+        let fullmove_number = fields.next().unwrap_or("1");
+
+        let mut board = Board::new();
+        board.variant = variant;
+        board.state = [[Piece::empty(); 8]; 8];
+        let (placement, pocket) = match placement.split_once('[') {
+            Some((placement, pocket)) => (placement, Some(pocket.strip_suffix(']').ok_or("Unclosed pocket in FEN")?)),
+            None => (placement, None),
+        };
+        let mut ranks = placement.split('/').collect::<Vec<_>>();
+        let pocket = match pocket {
+            None if ranks.len() == 9 => ranks.pop(),
+            pocket => pocket,
+        };
+        if ranks.len() != 8 {
+            return Err("FEN needs 8 ranks");
+        }
+        for (y, rank) in ranks.iter().enumerate() {
+            let mut x = 0;
+            for c in rank.chars() {
+                if c == '~' {
+                    // Marks the piece before it as a promoted pawn
+                    if x == 0 {
+                        return Err("Promotion mark without a piece in FEN");
+                    }
+                    board.promoted |= 1 << Coords::new(x as u8 - 1, y as u8).index();
+                } else if let Some(empty) = c.to_digit(10) {
+                    x += empty as usize;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or("Invalid piece in FEN")?;
+                    if x >= 8 {
+                        return Err("FEN rank has more than 8 squares");
+                    }
+                    board.state[y][x] = piece;
+                    x += 1;
+                }
+            }
+            if x != 8 {
+                return Err("FEN rank doesn't have 8 squares");
+            }
+        }
+        for c in pocket.unwrap_or("").chars() {
+            let piece = Piece::from_fen_char(c).filter(|piece| piece.piece_type != PieceType::King).ok_or("Invalid piece in FEN pocket")?;
+            board.pockets[piece.color as usize][piece.piece_type as usize] += 1;
+        }
+        board.turn = match turn {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err("Invalid side to move in FEN"),
+        };
+        // KQkq name the outermost rook on each side (X-FEN), file letters any rook (Shredder-FEN) for Chess960.
+        // Either way the king and that rook must be on the back rank
+        board.castling = CastlingRights::none();
+        if castling != "-" {
+            const MISSING: &str = "Castling rights without the king and rook in FEN";
+            for c in castling.chars() {
+                let color = if c.is_ascii_uppercase() { Color::White } else { Color::Black };
+                let (king_side, file) = match c.to_ascii_lowercase() {
+                    'k' => (true, board.outer_rook(color, true).ok_or(MISSING)?),
+                    'q' => (false, board.outer_rook(color, false).ok_or(MISSING)?),
+                    file @ 'a'..='h' => {
+                        let file = file as u8 - b'a';
+                        let king = board.king_file(color).ok_or(MISSING)?;
+                        if file == king || board.get_piece(Coords::new(file, back_rank(color))) != (Piece { piece_type: PieceType::Rook, color }) {
+                            return Err(MISSING);
+                        }
+                        (file > king, file)
+                    }
+                    _ => return Err("Invalid castling rights in FEN"),
+                };
+                board.castling.set(color, king_side, true);
+                board.castling.set_rook_file(color, king_side, file);
+            }
+        }
+        board.en_passant = match en_passant {
+            "-" => None,
+            square => Some(square.parse::<Coords>().map_err(|_| "Invalid en passant square in FEN")?),
+        };
+        board.halfmove_clock = halfmove_clock.parse().map_err(|_| "Invalid halfmove clock in FEN")?;
+        board.fullmove_number = fullmove_number.parse().map_err(|_| "Invalid fullmove number in FEN")?;
+        if let (Some(remaining), Some(limit)) = (remaining_checks, variant.check_limit()) {
+            let (white, black) = remaining.split_once('+').ok_or("Invalid remaining checks in FEN")?;
+            for (checks, remaining) in board.checks.iter_mut().zip([white, black]) {
+                let remaining = remaining.parse::<u8>().ok().filter(|remaining| *remaining <= limit).ok_or("Invalid remaining checks in FEN")?;
+                *checks = limit - remaining;
+            }
+        }
+        variant.validate(&board)?;
+
+        board.start_fen = Some(board.to_fen());
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+        for (y, row) in self.state.iter().enumerate() {
+            let mut empty = 0;
+            for (x, piece) in row.iter().enumerate() {
+                if piece.piece_type == PieceType::Empty {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(piece.fen_char());
+                if self.promoted & 1 << Coords::new(x as u8, y as u8).index() != 0 {
+                    fen.push('~');
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if y < 7 {
+                fen.push('/');
+            }
+        }
+        if self.variant.has_pockets() {
+            fen.push_str(&format!("[{}]", self.pocket_fen()));
+        }
+
+        fen.push_str(match self.turn {
+            Color::White => " w ",
+            Color::Black => " b ",
+        });
+        fen.push_str(&self.castling_field(false));
+        match self.en_passant {
+            Some(square) => fen.push_str(&format!(" {}", square)),
+            None => fen.push_str(" -"),
+        }
+        if let Some(limit) = self.variant.check_limit() {
+            fen.push_str(&format!(" {}+{}", limit.saturating_sub(self.checks[0]), limit.saturating_sub(self.checks[1])));
+        }
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove_number));
+        fen
+    }
+
+    /// FEN with the castling rooks always given by file, as in `HAha`
+    pub fn to_shredder_fen(&self) -> String {
+        let fen = self.to_fen();
+        let mut fields = fen.split(' ').map(|field| field.to_string()).collect::<Vec<_>>();
+        fields[2] = self.castling_field(true);
+        fields.join(" ")
+    }
+
+    /// `KQkq` style rights, naming the rook's file instead where it isn't the outermost one or `shredder` is set
+    fn castling_field(&self, shredder: bool) -> String {
+        let mut field = String::new();
+        for (color, king_side) in [(Color::White, true), (Color::White, false), (Color::Black, true), (Color::Black, false)] {
+            if !self.castling.get(color, king_side) {
+                continue;
+            }
+            let file = self.castling.rook_file(color, king_side);
+            let c = if shredder || self.outer_rook(color, king_side) != Some(file) {
+                (b'a' + file) as char
+            } else if king_side {
+                'k'
+            } else {
+                'q'
+            };
+            field.push(if color == Color::White { c.to_ascii_uppercase() } else { c });
+        }
+        if field.is_empty() {
+            field.push('-');
+        }
+        field
+    }
+
+    fn king_file(&self, color: Color) -> Option<u8> {
+        (0..8).find(|x| self.get_piece(Coords::new(*x, back_rank(color))) == Piece { piece_type: PieceType::King, color })
+    }
+
+    /// File of `color`'s rook furthest from its king on `king_side` of its back rank
+    fn outer_rook(&self, color: Color, king_side: bool) -> Option<u8> {
+        let king = self.king_file(color)?;
+        let rook = |x: &u8| self.get_piece(Coords::new(*x, back_rank(color))) == Piece { piece_type: PieceType::Rook, color };
+        if king_side {
+            (king + 1..8).rev().find(rook)
+        } else {
+            (0..king).find(rook)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_starting_fen() {
+        assert_eq!(Board::new().to_fen(), STARTING_FEN);
+        let board = Board::from_fen(STARTING_FEN).unwrap();
+        assert_eq!(board.state, Board::new().state);
+        assert_eq!(board.castling, CastlingRights::all());
+    }
+
+    #[test]
+    fn test_fen_after_moves() {
+        let mut board = Board::new();
+        assert!(board.move_piece("e2".parse::<Coords>().unwrap(), "e4".parse::<Coords>().unwrap(), None).is_ok());
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(board.move_piece("g8".parse::<Coords>().unwrap(), "f6".parse::<Coords>().unwrap(), None).is_ok());
+        assert!(board.move_piece("e1".parse::<Coords>().unwrap(), "e2".parse::<Coords>().unwrap(), None).is_ok());
+        assert_eq!(board.to_fen(), "rnbqkb1r/pppppppp/5n2/8/4P3/8/PPPPKPPP/RNBQ1BNR b kq - 2 2");
+    }
+
+    #[test]
+    fn test_fen_round_trip() {
+        let fen = "r3k2r/8/8/3pP3/8/8/8/R3K2R w Kq d6 0 12";
+        let board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.to_fen(), fen);
+        assert!(board.can_castle(Color::White, true));
+        assert!(!board.can_castle(Color::White, false));
+        assert_eq!(board.replay(0).to_fen(), fen);
+    }
+
+    #[test]
+    fn test_invalid_fen() {
+        assert!(Board::from_fen("").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").is_err());
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq z9 0 1").is_err());
+        // Castling rights need the king and the rook they name on the back rank
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").err(), Some("Castling rights without the king and rook in FEN"));
+        assert!(Board::from_fen("4k3/8/8/8/8/8/4K3/R6R w Q - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w B - 0 1").is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/1R2K3 w B - 0 1").is_ok());
+    }
+}
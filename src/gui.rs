@@ -1,55 +1,1104 @@
-use ggez::{event::{EventHandler, MouseButton}, glam::Vec2, graphics::{self, Canvas, Color, DrawParam, Rect}, input::keyboard::{KeyCode, KeyInput}, Context, GameResult};
+use std::sync::{mpsc::{self, Receiver}, Arc};
+use std::time::Duration;
 
-use crate::{assets::AssetManager, board::Board, coords::{Coords, MoveCoords}, pieces::PieceType};
+use ggez::{event::{EventHandler, MouseButton}, glam::Vec2, graphics::{self, Canvas, Color, DrawParam, Rect}, input::keyboard::{KeyCode, KeyInput, KeyMods}, Context, GameResult};
+
+use crate::{analysis::{white_share, Analysis}, assets::{AssetError, AssetManager, PieceSet}, theme::{LabelStyle, Theme, ThemeConfig}, board::{Board, MoveError, Outcome}, book::{Book, BookSelection}, clock::{format_duration, Clock, TimeControl}, coords::{Coords, MoveCoords}, crazyhouse::POCKET_PIECES, eco::{EcoTable, Opening}, pgn::Pgn, pieces::{Color as PieceColor, Piece, PieceType}, puzzle::{Attempt, Trainer}, search::{format_score, search, Limits}, variant::{Standard, Variant, VARIANTS}};
 
 pub struct BoardState {
     selected_square: Option<Coords>,
+    /// Piece picked from the pocket of the side to move, dropped on the next square clicked
+    selected_drop: Option<PieceType>,
+    /// Pawn move waiting for the piece it promotes to, picked from those offered down from its target square
+    promotion: Option<(Coords, Coords, Vec<PieceType>)>,
     pub board: Board,
     assets: AssetManager,
+    /// SAN of every ply in `board.moves`
+    notation: Vec<String>,
+    /// Earlier position being reviewed, `None` while on the live position
+    viewing: Option<(usize, Board)>,
+    clock: Option<Clock>,
+    outcome: Option<Outcome>,
+    prompt: Option<Prompt>,
+    /// Result of the last save or load, shown until the next input
+    message: Option<String>,
+    /// Board themes with the piece set each one selects
+    themes: Vec<(Theme, Option<usize>)>,
+    theme: usize,
+    layout: Layout,
+    /// Side played by the computer, `None` for two players at the board
+    computer: Option<PieceColor>,
+    pub limits: Limits,
+    book: Option<Arc<Book>>,
+    /// New games start from a random Chess960 position
+    chess960: bool,
+    /// Rules new games are played by
+    variant: &'static dyn Variant,
+    /// Computer move being searched for on another thread, dropped to abandon it when the game changes
+    thinking: Option<Receiver<Option<MoveCoords>>>,
+    /// Best lines of the displayed position, searched while the analysis panel is open
+    analysis: Option<Analysis>,
+    /// Puzzles served in place of games, set with `set_puzzles`
+    puzzles: Option<Trainer>,
+    /// The opponent's answer to a right move, played once the delay runs out
+    puzzle_reply: Option<(MoveCoords, Duration)>,
+    /// Opening of the displayed game, named in the window title and under the move list
+    opening: Option<&'static Opening>,
+    /// Ply and Polyglot key of the position `opening` was looked up for
+    opening_for: Option<(usize, u64)>,
 }
 
-const GRID_CELL_SIZE: f32 = 100.0;
-pub const BOARD_SIZE: f32 = GRID_CELL_SIZE * 8.0;
+#[derive(Copy, Clone, PartialEq)]
+enum Command {
+    SavePgn,
+    SaveFen,
+    Load,
+}
 
-impl BoardState {
-    pub fn new() -> Self {
-        let assets = AssetManager::new(std::path::PathBuf::from("assets/chess"));
-        BoardState { selected_square: None, board: Board::new(), assets }
+struct Prompt {
+    command: Command,
+    input: String,
+}
+
+/// Square size the window opens with, which sizes in the theme are relative to
+const DEFAULT_CELL_SIZE: f32 = 100.0;
+pub const BOARD_SIZE: f32 = DEFAULT_CELL_SIZE * 8.0;
+const PANEL_WIDTH: f32 = 260.0;
+pub const WINDOW_WIDTH: f32 = BOARD_SIZE + PANEL_WIDTH;
+/// Smallest board the window can shrink to while keeping the side panel usable
+pub const MIN_BOARD_SIZE: f32 = 440.0;
+pub const MIN_WINDOW_WIDTH: f32 = MIN_BOARD_SIZE + PANEL_WIDTH;
+
+// Side panel offsets from its top left corner
+const CLOCK_HEIGHT: f32 = 60.0;
+const MOVE_LIST_TOP: f32 = CLOCK_HEIGHT + 44.0;
+const BUTTON_HEIGHT: f32 = 32.0;
+const MOVE_ROW_HEIGHT: f32 = 26.0;
+const MOVE_NUMBER_X: f32 = 16.0;
+const WHITE_MOVE_X: f32 = 64.0;
+const BLACK_MOVE_X: f32 = 160.0;
+const POCKET_HEIGHT: f32 = 40.0;
+const POCKET_SLOT_WIDTH: f32 = 44.0;
+/// Lines shown by the analysis panel
+const ANALYSIS_LINES: usize = 3;
+const ANALYSIS_ROW_HEIGHT: f32 = 22.0;
+const EVAL_BAR_WIDTH: f32 = 6.0;
+/// Pause before the opponent answers in a puzzle, so the solver's move can be seen
+const PUZZLE_REPLY_DELAY: Duration = Duration::from_millis(400);
+
+/// Where the board and side panel go in the current window, keeping the board square and the pair centered
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Layout {
+    /// Top left corner of the board
+    origin: Vec2,
+    cell_size: f32,
+    /// Rows for the pieces in hand, Black's under its clock and White's above the buttons
+    pockets: bool,
+    /// Engine lines shown under the move list
+    analysis_rows: usize,
+}
+
+impl Layout {
+    fn new(width: f32, height: f32) -> Self {
+        let board_size = height.min(width - PANEL_WIDTH).max(MIN_BOARD_SIZE);
+        let x = ((width - board_size - PANEL_WIDTH) / 2.0).max(0.0);
+        let y = ((height - board_size) / 2.0).max(0.0);
+        Layout { origin: Vec2::new(x, y), cell_size: board_size / 8.0, pockets: false, analysis_rows: 0 }
+    }
+
+    fn board_size(&self) -> f32 {
+        self.cell_size * 8.0
     }
 
-    fn get_square_at(&self, x: f32, y: f32) -> Option<Coords> {
-        let col = (x / GRID_CELL_SIZE) as u8;
-        let row = (y / GRID_CELL_SIZE) as u8;
+    /// Ratio to the default square size, for scaling pieces and labels
+    fn scale(&self) -> f32 {
+        self.cell_size / DEFAULT_CELL_SIZE
+    }
+
+    fn square_rect(&self, coords: Coords) -> Rect {
+        Rect::new(
+            self.origin.x + coords.x as f32 * self.cell_size,
+            self.origin.y + coords.y as f32 * self.cell_size,
+            self.cell_size,
+            self.cell_size,
+        )
+    }
+
+    fn square_at(&self, x: f32, y: f32) -> Option<Coords> {
+        let (x, y) = (x - self.origin.x, y - self.origin.y);
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let col = (x / self.cell_size) as u8;
+        let row = (y / self.cell_size) as u8;
         if col < 8 && row < 8 {
             Some((col, row).into())
         } else {
             None
         }
     }
+
+    /// Top left corner of the side panel
+    fn panel(&self) -> Vec2 {
+        Vec2::new(self.origin.x + self.board_size(), self.origin.y)
+    }
+
+    fn in_panel(&self, x: f32, y: f32) -> bool {
+        let panel = self.panel();
+        Rect::new(panel.x, panel.y, PANEL_WIDTH, self.board_size()).contains([x, y])
+    }
+
+    fn button_top(&self) -> f32 {
+        self.origin.y + self.board_size() - CLOCK_HEIGHT - BUTTON_HEIGHT - 8.0
+    }
+
+    fn pocket_height(&self) -> f32 {
+        if self.pockets {
+            POCKET_HEIGHT
+        } else {
+            0.0
+        }
+    }
+
+    fn move_list_top(&self) -> f32 {
+        self.origin.y + MOVE_LIST_TOP + self.pocket_height()
+    }
+
+    fn analysis_height(&self) -> f32 {
+        if self.analysis_rows == 0 {
+            0.0
+        } else {
+            8.0 + self.analysis_rows as f32 * ANALYSIS_ROW_HEIGHT
+        }
+    }
+
+    fn move_list_bottom(&self) -> f32 {
+        self.button_top() - 36.0 - self.pocket_height() - self.analysis_height()
+    }
+
+    /// Line under the move list and analysis for the result and other notes
+    fn status_top(&self) -> f32 {
+        self.button_top() - 24.0 - self.pocket_height()
+    }
+
+    /// Slot for the pieces of the `slot`th type in `POCKET_PIECES` held by `color`
+    fn pocket_rect(&self, color: PieceColor, slot: usize) -> Rect {
+        let y = match color {
+            PieceColor::Black => self.origin.y + CLOCK_HEIGHT + 4.0,
+            PieceColor::White => self.button_top() - POCKET_HEIGHT - 4.0,
+        };
+        Rect::new(self.panel().x + MOVE_NUMBER_X + slot as f32 * POCKET_SLOT_WIDTH, y, POCKET_SLOT_WIDTH - 4.0, POCKET_HEIGHT - 4.0)
+    }
+
+    fn pocket_at(&self, x: f32, y: f32) -> Option<(PieceColor, PieceType)> {
+        if !self.pockets {
+            return None;
+        }
+        for color in [PieceColor::White, PieceColor::Black] {
+            if let Some(slot) = (0..POCKET_PIECES.len()).find(|slot| self.pocket_rect(color, *slot).contains([x, y])) {
+                return Some((color, POCKET_PIECES[slot]));
+            }
+        }
+        None
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Button {
+    NewGame,
+    Undo,
+    Redo,
+}
+
+const BUTTONS: [(Button, &str); 3] = [(Button::NewGame, "New"), (Button::Undo, "Undo"), (Button::Redo, "Redo")];
+
+/// Cycled through with `T` before the first move, in PGN `TimeControl` syntax
+const TIME_CONTROLS: [&str; 5] = ["60", "180+2", "300+d3", "900+10", "40/5400+30:1800+30"];
+
+impl BoardState {
+    pub fn new() -> Self {
+        Self::with_assets(AssetManager::embedded())
+    }
+
+    pub fn with_assets(assets: AssetManager) -> Self {
+        BoardState {
+            selected_square: None,
+            selected_drop: None,
+            promotion: None,
+            board: Board::new(),
+            assets,
+            notation: vec![],
+            viewing: None,
+            clock: None,
+            outcome: None,
+            prompt: None,
+            message: None,
+            themes: Theme::built_in().into_iter().map(|theme| (theme, None)).collect(),
+            theme: 0,
+            layout: Layout::new(WINDOW_WIDTH, BOARD_SIZE),
+            computer: None,
+            limits: Limits::time(Duration::from_secs(1)),
+            book: None,
+            chess960: false,
+            variant: &Standard,
+            thinking: None,
+            analysis: None,
+            puzzles: None,
+            puzzle_reply: None,
+            opening: None,
+            opening_for: None,
+        }
+    }
+
+    /// Lets the computer play `side`, or hands the board back to two players with `None`
+    pub fn set_computer(&mut self, side: Option<PieceColor>) {
+        self.computer = side;
+        self.thinking = None;
+    }
+
+    /// Opening book the computer plays from before it starts searching
+    pub fn set_book(&mut self, book: Option<Book>) {
+        self.book = book.map(Arc::new);
+    }
+
+    /// Plays Chess960 from a random start position, starting a new game now
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+        self.new_game();
+    }
+
+    /// Plays by the rules of `variant`, starting a new game now
+    pub fn set_variant(&mut self, variant: &'static dyn Variant) {
+        self.variant = variant;
+        self.new_game();
+    }
+
+    /// Serves puzzles from `trainer` instead of games, without a clock or computer opponent
+    pub fn set_puzzles(&mut self, trainer: Trainer) {
+        self.set_computer(None);
+        self.set_time_control(None);
+        self.puzzles = Some(trainer);
+        self.next_puzzle();
+    }
+
+    fn next_puzzle(&mut self) {
+        self.puzzle_reply = None;
+        let Some(run) = self.puzzles.as_mut().and_then(|trainer| trainer.next_puzzle()) else {
+            self.message = Some("No puzzles left to try".to_string());
+            return;
+        };
+        let (board, player) = (run.board.clone(), run.player());
+        self.set_board(board);
+        let side = if player == PieceColor::White { "White" } else { "Black" };
+        self.message = Some(format!("Find the best move for {}", side));
+    }
+
+    /// Rating and puzzle line for the status area
+    fn puzzle_status(&self) -> Option<String> {
+        let trainer = self.puzzles.as_ref()?;
+        let run = trainer.current.as_ref()?;
+        let state = if run.is_solved() { "solved" } else if run.failed { "failed" } else { "to play" };
+        Some(format!("Puzzle {} ({}) {} - rating {:.0}", run.puzzle.id, run.puzzle.rating, state, trainer.rating.rating))
+    }
+
+    /// Follows up the user's move from `before`, checking it against the puzzle being solved
+    fn player_move(&mut self, before: Board, mc: MoveCoords) {
+        let Some(trainer) = &mut self.puzzles else {
+            self.after_move(&before, &mc);
+            return;
+        };
+        let rating = trainer.rating.rating;
+        let attempt = trainer.play(&mc);
+        let change = trainer.rating.rating - rating;
+        match attempt {
+            Some(Attempt::Wrong) => {
+                self.board = before;
+                self.message = Some(if change == 0.0 { "Not the move, try again".to_string() } else { format!("Not the move, try again (rating {:+.0})", change) });
+            }
+            Some(Attempt::Correct(reply)) => {
+                self.after_move(&before, &mc);
+                self.puzzle_reply = Some((reply, PUZZLE_REPLY_DELAY));
+            }
+            Some(Attempt::Solved) => {
+                self.after_move(&before, &mc);
+                self.message = Some(if change == 0.0 { "Solved - N for the next puzzle".to_string() } else { format!("Solved (rating {:+.0}) - N for the next puzzle", change) });
+            }
+            None => self.after_move(&before, &mc),
+        }
+    }
+
+    /// Whether the board waits on a puzzle: for the opponent's reply, or for the next puzzle once solved
+    fn puzzle_waiting(&self) -> bool {
+        self.puzzle_reply.is_some() || self.puzzles.as_ref().and_then(|trainer| trainer.current.as_ref()).is_some_and(|run| run.is_solved())
+    }
+
+    /// Plays the piece on `from` to `to`, first offering the pieces a pawn may promote to
+    fn click_move(&mut self, from: Coords, to: Coords) {
+        let choices: Vec<PieceType> = PROMOTION_PIECES.into_iter().filter(|piece_type| self.board.possible_moves(from).iter().any(|mc| mc.to == to && mc.promotion == Some(*piece_type))).collect();
+        if !choices.is_empty() {
+            self.selected_square = None;
+            self.promotion = Some((from, to, choices));
+            return;
+        }
+        self.play_click(from, to, None);
+    }
+
+    /// Finishes the waiting promotion with the piece offered on `square`, or drops it when none is
+    fn pick_promotion(&mut self, square: Coords) {
+        let Some((from, to, choices)) = self.promotion.take() else {
+            return;
+        };
+        let picked = choices.iter().enumerate().find(|(i, _)| promotion_square(to, *i) == square);
+        if let Some((_, piece_type)) = picked {
+            self.play_click(from, to, Some(*piece_type));
+        }
+    }
+
+    fn play_click(&mut self, from: Coords, to: Coords, promotion: Option<PieceType>) {
+        let before = self.board.clone();
+        let moved = self.board.move_piece(from, to, promotion);
+        log::debug!("Move {}{}: {:?}", from, to, moved);
+        if let Err(MoveError::LeavesKingInCheck) = moved {
+            self.message = Some(MoveError::LeavesKingInCheck.to_string());
+        }
+        if let Ok(mc) = moved {
+            self.player_move(before, mc);
+            self.selected_square = None;
+        } else {
+            self.selected_square = Some(to);
+        }
+    }
+
+    /// Pieces offered for a waiting promotion, on the squares they are picked from
+    fn draw_promotion(&self, canvas: &mut Canvas) {
+        let Some((_, to, choices)) = &self.promotion else {
+            return;
+        };
+        let theme = self.current_theme();
+        for (i, piece_type) in choices.iter().enumerate() {
+            let rect = self.layout.square_rect(promotion_square(*to, i));
+            canvas.draw(&graphics::Quad, DrawParam::new().dest(Vec2::new(rect.x, rect.y)).scale(Vec2::new(rect.w, rect.h)).color(Color::from_rgb(62, 60, 56)));
+            if let Some(image) = self.assets.image(&Piece { piece_type: *piece_type, color: self.board.turn }) {
+                canvas.draw(
+                    image,
+                    DrawParam::new()
+                        .dest(Vec2::new(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0))
+                        .scale(Vec2::splat(theme.piece_scale * self.layout.scale()))
+                        .offset(Vec2::new(0.5, 0.5)),
+                );
+            }
+        }
+    }
+
+    fn next_variant(&mut self) {
+        let index = VARIANTS.iter().position(|variant| variant.name() == self.variant.name()).unwrap_or(0);
+        self.set_variant(VARIANTS[(index + 1) % VARIANTS.len()]);
+        self.message = Some(format!("Variant: {}", self.variant.name()));
+    }
+
+    fn next_computer_side(&mut self) {
+        let next = match self.computer {
+            None => Some(PieceColor::Black),
+            Some(PieceColor::Black) => Some(PieceColor::White),
+            Some(PieceColor::White) => None,
+        };
+        self.set_computer(next);
+        self.message = Some(match next {
+            Some(PieceColor::White) => "Computer plays White".to_string(),
+            Some(PieceColor::Black) => "Computer plays Black".to_string(),
+            None => "Two players".to_string(),
+        });
+    }
+
+    fn computer_to_move(&self) -> bool {
+        self.computer == Some(self.board.turn) && self.outcome.is_none()
+    }
+
+    /// Starts searching for the computer's move, taking it from the book while there is one
+    fn start_thinking(&mut self) {
+        let board = self.board.clone();
+        let book = self.book.clone();
+        let limits = self.limits;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mc = book.and_then(|book| book.choose(&board, BookSelection::Weighted)).or_else(|| search(&board, limits).best_move);
+            // The game may have moved on and dropped the receiver
+            let _ = sender.send(mc);
+        });
+        self.thinking = Some(receiver);
+    }
+
+    fn computer_move(&mut self, mc: MoveCoords) {
+        let before = self.board.clone();
+        self.board.apply(&mc);
+        self.board.undone.clear();
+        self.after_move(&before, &mc);
+    }
+
+    /// Updates the move list, result and clock once `mc` has been played from `before`
+    fn after_move(&mut self, before: &Board, mc: &MoveCoords) {
+        self.notation.push(before.san(mc));
+        self.outcome = self.board.outcome();
+        if let Some(clock) = &mut self.clock {
+            clock.press();
+            if self.outcome.is_some() {
+                clock.stop();
+            }
+        }
+    }
+
+    /// Adds the themes from a themes file, loading their piece sets, and selects its starting theme
+    pub fn add_themes(&mut self, config: ThemeConfig) -> Result<(), AssetError> {
+        for theme in config.themes {
+            let piece_set = match &theme.piece_set {
+                Some(dir) => Some(self.assets.add_set(PieceSet::from_dir(dir.clone())?)),
+                None => None,
+            };
+            self.themes.push((theme, piece_set));
+        }
+        if let Some(name) = config.theme {
+            if let Some(index) = self.themes.iter().position(|(theme, _)| theme.name == name) {
+                self.select_theme(index);
+            }
+        }
+        Ok(())
+    }
+
+    fn select_theme(&mut self, index: usize) {
+        self.theme = index;
+        if let Some(piece_set) = self.themes[index].1 {
+            self.assets.select(piece_set);
+        }
+    }
+
+    fn current_theme(&self) -> &Theme {
+        &self.themes[self.theme].0
+    }
+
+    pub fn set_time_control(&mut self, control: Option<TimeControl>) {
+        self.clock = control.map(Clock::new);
+    }
+
+    fn next_time_control(&mut self) {
+        let current = self.clock.as_ref().map(|clock| clock.control.to_string());
+        let next = match TIME_CONTROLS.iter().position(|tc| Some(tc.to_string()) == current) {
+            Some(i) => TIME_CONTROLS.get(i + 1),
+            None => TIME_CONTROLS.first(),
+        };
+        self.set_time_control(next.and_then(|tc| tc.parse().ok()));
+    }
+
+    fn undo(&mut self) {
+        // Taking back moves would lose track of the puzzle's solution
+        if self.puzzles.is_some() {
+            return;
+        }
+        self.view_ply(self.board.moves.len());
+        self.thinking = None;
+        if self.board.undo_move().is_some() {
+            self.notation.pop();
+            // Against the computer, take back its reply as well
+            if self.computer == Some(self.board.turn) && self.board.undo_move().is_some() {
+                self.notation.pop();
+            }
+            self.after_takeback();
+        }
+    }
+
+    fn redo(&mut self) {
+        if self.puzzles.is_some() {
+            return;
+        }
+        self.view_ply(self.board.moves.len());
+        self.thinking = None;
+        let before = self.board.clone();
+        if let Some(mc) = self.board.redo_move() {
+            self.notation.push(before.san(&mc));
+            if self.computer == Some(self.board.turn) {
+                let before = self.board.clone();
+                if let Some(mc) = self.board.redo_move() {
+                    self.notation.push(before.san(&mc));
+                }
+            }
+            self.after_takeback();
+        }
+    }
+
+    /// Brings the clock and result in line with the board after undo or redo
+    fn after_takeback(&mut self) {
+        self.outcome = self.board.outcome();
+        if let Some(clock) = &mut self.clock {
+            clock.set_turn(self.board.turn);
+            if let Some(flagged) = clock.flagged() {
+                self.outcome = Some(self.board.timeout(flagged));
+            } else if self.outcome.is_some() {
+                clock.stop();
+            } else if !self.board.moves.is_empty() {
+                clock.start();
+            }
+        }
+    }
+
+    fn button_rect(&self, index: usize) -> Rect {
+        let gap = 8.0;
+        let width = (PANEL_WIDTH - 2.0 * MOVE_NUMBER_X - gap * (BUTTONS.len() - 1) as f32) / BUTTONS.len() as f32;
+        let x = self.layout.panel().x + MOVE_NUMBER_X + index as f32 * (width + gap);
+        Rect::new(x, self.layout.button_top(), width, BUTTON_HEIGHT)
+    }
+
+    fn button_at(&self, x: f32, y: f32) -> Option<Button> {
+        (0..BUTTONS.len()).find(|i| self.button_rect(*i).contains([x, y])).map(|i| BUTTONS[i].0)
+    }
+
+    fn draw_buttons(&self, canvas: &mut Canvas) {
+        for (i, (button, label)) in BUTTONS.iter().enumerate() {
+            let enabled = match button {
+                Button::NewGame => true,
+                Button::Undo => !self.board.moves.is_empty(),
+                Button::Redo => !self.board.undone.is_empty(),
+            };
+            let rect = self.button_rect(i);
+            canvas.draw(
+                &graphics::Quad,
+                DrawParam::new()
+                    .dest(Vec2::new(rect.x, rect.y))
+                    .scale(Vec2::new(rect.w, rect.h))
+                    .color(Color::from_rgb(70, 68, 64)),
+            );
+            let text_color = if enabled { Color::WHITE } else { Color::from_rgb(120, 120, 120) };
+            canvas.draw(
+                graphics::Text::new(*label).set_scale(18.0),
+                DrawParam::new().dest(Vec2::new(rect.x + 12.0, rect.y + 7.0)).color(text_color),
+            );
+        }
+    }
+
+    /// Loads a PGN or FEN file, or a FEN typed in directly
+    pub fn load(&mut self, source: &str) -> Result<(), String> {
+        let path = std::path::Path::new(source);
+        let is_fen = |text: &str| text.lines().count() == 1 && text.split_whitespace().next().is_some_and(|p| p.matches('/').count() == 7);
+        // A single line naming a file which isn't there gets that said, rather than a parse error
+        let extension = path.extension().and_then(|ext| ext.to_str()).map(|ext| ext.to_ascii_lowercase());
+        let looks_like_path = !source.trim().contains('\n')
+            && !source.trim_start().starts_with('[')
+            && (extension.is_some_and(|ext| ["pgn", "fen", "epd", "txt"].contains(&ext.as_str())) || (source.contains(['/', '\\']) && !source.trim().contains(char::is_whitespace)));
+        let text = if path.is_file() {
+            std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", source, e))?
+        } else if looks_like_path && !is_fen(source.trim()) {
+            return Err(format!("Could not read {}: no such file", source));
+        } else {
+            source.to_string()
+        };
+        let text = text.trim();
+        let board = if is_fen(text) {
+            Board::from_fen_variant(text, self.variant).map_err(|e| format!("Invalid FEN: {}", e))?
+        } else {
+            Pgn::parse(text).map_err(|e| format!("Invalid PGN: {}", e))?.board
+        };
+        self.set_board(board);
+        Ok(())
+    }
+
+    fn set_board(&mut self, board: Board) {
+        self.thinking = None;
+        self.board = board;
+        self.board.undone.clear();
+        self.notation = self.board.san_moves();
+        self.outcome = self.board.outcome();
+        self.selected_square = None;
+        self.promotion = None;
+        self.viewing = None;
+        self.layout.pockets = self.board.variant.has_pockets();
+        self.set_time_control(self.clock.as_ref().map(|clock| clock.control.clone()));
+        if let Some(clock) = &mut self.clock {
+            clock.set_turn(self.board.turn);
+        }
+    }
+
+    fn run_command(&mut self, command: Command, input: &str) -> Result<String, String> {
+        match command {
+            Command::SavePgn => {
+                let mut pgn = Pgn::new(self.board.clone());
+                if let Some(outcome) = self.outcome {
+                    pgn.set_header("Result", outcome.result());
+                }
+                if let Some(clock) = &self.clock {
+                    pgn.set_header("TimeControl", &clock.control.to_string());
+                }
+                std::fs::write(input, pgn.to_string()).map_err(|e| format!("Could not save {}: {}", input, e))?;
+                Ok(format!("Saved game to {}", input))
+            }
+            Command::SaveFen => {
+                let fen = self.displayed_board().to_fen();
+                std::fs::write(input, format!("{}\n", fen)).map_err(|e| format!("Could not save {}: {}", input, e))?;
+                Ok(format!("Saved position to {}", input))
+            }
+            Command::Load => {
+                self.load(input)?;
+                Ok(format!("Loaded {}", input))
+            }
+        }
+    }
+
+    fn open_prompt(&mut self, command: Command) {
+        let input = match command {
+            Command::SavePgn => "game.pgn",
+            Command::SaveFen => "position.fen",
+            Command::Load => "",
+        };
+        self.prompt = Some(Prompt { command, input: input.to_string() });
+    }
+
+    fn prompt_key(&mut self, keycode: Option<KeyCode>) {
+        match keycode {
+            Some(KeyCode::Return) | Some(KeyCode::NumpadEnter) => {
+                if let Some(prompt) = self.prompt.take() {
+                    let result = self.run_command(prompt.command, prompt.input.trim());
+                    self.message = Some(result.unwrap_or_else(|e| e));
+                }
+            }
+            Some(KeyCode::Escape) => self.prompt = None,
+            Some(KeyCode::Back) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.input.pop();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Prompt or message bar along the bottom of the board
+    fn draw_prompt(&self, canvas: &mut Canvas) {
+        let text = match (&self.prompt, &self.message) {
+            (Some(prompt), _) => {
+                let label = match prompt.command {
+                    Command::SavePgn => "Save PGN to",
+                    Command::SaveFen => "Save FEN to",
+                    Command::Load => "Load PGN/FEN file or FEN",
+                };
+                format!("{}: {}_", label, prompt.input)
+            }
+            (None, Some(message)) => message.clone(),
+            (None, None) => return,
+        };
+        let height = 56.0;
+        let board_size = self.layout.board_size();
+        let top = self.layout.origin.y + board_size - height;
+        canvas.draw(
+            &graphics::Quad,
+            DrawParam::new()
+                .dest(Vec2::new(self.layout.origin.x, top))
+                .scale(Vec2::new(board_size, height))
+                .color(Color::from_rgba(32, 31, 29, 230)),
+        );
+        let mut text = graphics::Text::new(text);
+        text.set_scale(18.0).set_bounds(Vec2::new(board_size - 32.0, height));
+        canvas.draw(&text, DrawParam::new().dest(Vec2::new(self.layout.origin.x + 16.0, top + 8.0)).color(Color::WHITE));
+    }
+
+    fn new_game(&mut self) {
+        if self.puzzles.is_some() {
+            self.next_puzzle();
+            return;
+        }
+        self.thinking = None;
+        // Chess960 start positions only go with the standard rules
+        self.board = if self.chess960 && self.variant.name() == Standard.name() { Board::random_chess960().1 } else { Board::new_variant(self.variant) };
+        self.notation.clear();
+        self.outcome = None;
+        self.layout.pockets = self.board.variant.has_pockets();
+        self.set_time_control(self.clock.as_ref().map(|clock| clock.control.clone()));
+        self.view_ply(0);
+    }
+
+    fn displayed_board(&self) -> &Board {
+        match &self.viewing {
+            Some((_, board)) => board,
+            None => &self.board,
+        }
+    }
+
+    fn displayed_ply(&self) -> usize {
+        match &self.viewing {
+            Some((ply, _)) => *ply,
+            None => self.board.moves.len(),
+        }
+    }
+
+    /// Show the position after `ply` half moves, going back to the live game at the end of the list
+    fn view_ply(&mut self, ply: usize) {
+        self.selected_square = None;
+        self.selected_drop = None;
+        self.promotion = None;
+        if ply >= self.board.moves.len() {
+            self.viewing = None;
+        } else {
+            self.viewing = Some((ply, self.board.replay(ply)));
+        }
+    }
+
+    fn visible_move_rows(&self) -> usize {
+        ((self.layout.move_list_bottom() - self.layout.move_list_top()) / MOVE_ROW_HEIGHT) as usize
+    }
+
+    /// First row of the move list on screen, scrolled so the displayed ply stays visible
+    fn first_move_row(&self) -> usize {
+        let current_row = (self.displayed_ply() + self.move_list_start().0).saturating_sub(1) / 2;
+        (current_row + 1).saturating_sub(self.visible_move_rows())
+    }
+
+    /// Empty cells before the first move, one when Black moved first, and the number of its row
+    fn move_list_start(&self) -> (usize, u32) {
+        let plies = self.board.moves.len();
+        let offset = if (plies % 2 == 1) == (self.board.turn == PieceColor::White) { 1 } else { 0 };
+        (offset, self.board.fullmove_number.saturating_sub(((offset + plies) / 2) as u32).max(1))
+    }
+
+    fn ply_at(&self, x: f32, y: f32) -> Option<usize> {
+        let x = x - self.layout.panel().x;
+        let top = self.layout.move_list_top();
+        if x < WHITE_MOVE_X || !(top..self.layout.move_list_bottom()).contains(&y) {
+            return None;
+        }
+        let row = self.first_move_row() + ((y - top) / MOVE_ROW_HEIGHT) as usize;
+        let cell = row * 2 + if x < BLACK_MOVE_X { 0 } else { 1 };
+        let ply = (cell + 1).checked_sub(self.move_list_start().0)?;
+        if ply > 0 && ply <= self.board.moves.len() {
+            Some(ply)
+        } else {
+            None
+        }
+    }
+
+    fn draw_move_list(&self, canvas: &mut Canvas) {
+        let panel = self.layout.panel();
+        canvas.draw(
+            &graphics::Quad,
+            DrawParam::new()
+                .dest(panel)
+                .scale(Vec2::new(PANEL_WIDTH, self.layout.board_size()))
+                .color(Color::from_rgb(48, 46, 43)),
+        );
+        canvas.draw(
+            graphics::Text::new("Moves").set_scale(22.0),
+            DrawParam::new().dest(Vec2::new(panel.x + MOVE_NUMBER_X, self.layout.move_list_top() - 32.0)).color(Color::WHITE),
+        );
+
+        let displayed = self.displayed_ply();
+        let first_row = self.first_move_row();
+        let (offset, first_number) = self.move_list_start();
+        let rows = (self.notation.len() + offset).div_ceil(2);
+        for row in (first_row..rows).take(self.visible_move_rows()) {
+            let y = self.layout.move_list_top() + (row - first_row) as f32 * MOVE_ROW_HEIGHT;
+            canvas.draw(
+                graphics::Text::new(format!("{}.", first_number as usize + row)).set_scale(18.0),
+                DrawParam::new().dest(Vec2::new(panel.x + MOVE_NUMBER_X, y + 4.0)).color(Color::from_rgb(150, 150, 150)),
+            );
+            for i in 0..2 {
+                let x = panel.x + if i == 0 { WHITE_MOVE_X } else { BLACK_MOVE_X };
+                // Cells before the first move hold an ellipsis, as in `5...` for Black moving first
+                let ply = row * 2 + i + 1;
+                let san = if ply <= offset {
+                    "..."
+                } else {
+                    match self.notation.get(ply - offset - 1) {
+                        Some(san) => san.as_str(),
+                        None => break,
+                    }
+                };
+                if ply > offset && ply - offset == displayed {
+                    canvas.draw(
+                        &graphics::Quad,
+                        DrawParam::new()
+                            .dest(Vec2::new(x - 6.0, y))
+                            .scale(Vec2::new(BLACK_MOVE_X - WHITE_MOVE_X - 8.0, MOVE_ROW_HEIGHT))
+                            .color(Color::from_rgb(90, 110, 140)),
+                    );
+                }
+                canvas.draw(
+                    graphics::Text::new(san).set_scale(18.0),
+                    DrawParam::new().dest(Vec2::new(x, y + 4.0)).color(Color::WHITE),
+                );
+            }
+        }
+
+        let status = if self.viewing.is_some() {
+            Some("Reviewing - End to resume".to_string())
+        } else if self.thinking.is_some() {
+            Some("Computer thinking...".to_string())
+        } else {
+            self.puzzle_status().or_else(|| self.outcome.map(|outcome| format!("{} {}", outcome.result(), outcome))).or_else(|| self.variant_status()).or_else(|| self.opening_status())
+        };
+        if let Some(status) = status {
+            canvas.draw(
+                graphics::Text::new(status).set_scale(16.0),
+                DrawParam::new()
+                    .dest(Vec2::new(panel.x + MOVE_NUMBER_X, self.layout.status_top()))
+                    .color(Color::from_rgb(230, 200, 120)),
+            );
+        }
+    }
+
+    /// Opens the analysis panel on the displayed position, or closes it
+    fn toggle_analysis(&mut self) {
+        if self.analysis.take().is_some() {
+            self.layout.analysis_rows = 0;
+            self.message = Some("Analysis off".to_string());
+        } else {
+            self.analysis = Some(Analysis::start(self.displayed_board(), ANALYSIS_LINES, Limits::infinite(), None));
+            self.layout.analysis_rows = ANALYSIS_LINES;
+            self.message = Some("Analysis on - Left and Right step through the game".to_string());
+        }
+    }
+
+    /// Takes in the newest lines, starting over whenever a move is played or another position reviewed
+    fn follow_analysis(&mut self) {
+        let Some(analysis) = &mut self.analysis else {
+            return;
+        };
+        analysis.poll();
+        let displayed = match &self.viewing {
+            Some((_, board)) => board,
+            None => &self.board,
+        };
+        if analysis.board.to_fen() != displayed.to_fen() {
+            self.analysis = Some(Analysis::start(displayed, ANALYSIS_LINES, Limits::infinite(), None));
+        }
+    }
+
+    /// The engine's best lines in SAN with scores for White, and the evaluation bar beside the move list
+    fn draw_analysis(&self, canvas: &mut Canvas) {
+        let Some(analysis) = &self.analysis else {
+            return;
+        };
+        let panel = self.layout.panel();
+        let top = self.layout.move_list_bottom() + 4.0;
+        if let Some(line) = analysis.lines.first() {
+            canvas.draw(
+                graphics::Text::new(format!("depth {}", line.depth)).set_scale(16.0),
+                DrawParam::new().dest(Vec2::new(panel.x + BLACK_MOVE_X, self.layout.move_list_top() - 28.0)).color(Color::from_rgb(150, 150, 150)),
+            );
+        }
+        for (row, line) in analysis.lines.iter().take(self.layout.analysis_rows).enumerate() {
+            let score = if analysis.board.turn == PieceColor::White { line.score } else { -line.score };
+            let mut board = analysis.board.clone();
+            let mut text = format_score(score);
+            for mc in line.pv.iter().take(6) {
+                text.push(' ');
+                text.push_str(&board.san(mc));
+                board.apply(mc);
+            }
+            let mut text = graphics::Text::new(text);
+            text.set_scale(16.0).set_bounds(Vec2::new(PANEL_WIDTH - MOVE_NUMBER_X - 8.0, ANALYSIS_ROW_HEIGHT));
+            canvas.draw(&text, DrawParam::new().dest(Vec2::new(panel.x + MOVE_NUMBER_X, top + 4.0 + row as f32 * ANALYSIS_ROW_HEIGHT)).color(Color::WHITE));
+        }
+        // White's share grows from the bottom, as White sits at the bottom of the board
+        let bar_top = self.layout.move_list_top() - 32.0;
+        let bar_height = top + self.layout.analysis_height() - 4.0 - bar_top;
+        let white = analysis.score_white().map_or(0.5, white_share) * bar_height;
+        canvas.draw(
+            &graphics::Quad,
+            DrawParam::new().dest(Vec2::new(panel.x + 4.0, bar_top)).scale(Vec2::new(EVAL_BAR_WIDTH, bar_height - white)).color(Color::from_rgb(20, 20, 20)),
+        );
+        canvas.draw(
+            &graphics::Quad,
+            DrawParam::new().dest(Vec2::new(panel.x + 4.0, bar_top + bar_height - white)).scale(Vec2::new(EVAL_BAR_WIDTH, white)).color(Color::from_rgb(235, 235, 230)),
+        );
+    }
+
+    /// Pieces each side holds in hand, with a count under those held more than once
+    fn draw_pockets(&self, canvas: &mut Canvas) {
+        if !self.layout.pockets {
+            return;
+        }
+        let board = self.displayed_board();
+        let theme = self.current_theme();
+        for color in [PieceColor::White, PieceColor::Black] {
+            for (slot, piece_type) in POCKET_PIECES.into_iter().enumerate() {
+                let rect = self.layout.pocket_rect(color, slot);
+                let selected = self.viewing.is_none() && color == board.turn && self.selected_drop == Some(piece_type);
+                let background = if selected { rgba(theme.selected) } else { Color::from_rgb(62, 60, 56) };
+                canvas.draw(&graphics::Quad, DrawParam::new().dest(Vec2::new(rect.x, rect.y)).scale(Vec2::new(rect.w, rect.h)).color(background));
+                let count = board.in_hand(color, piece_type);
+                if count == 0 {
+                    continue;
+                }
+                if let Some(image) = self.assets.image(&Piece { piece_type, color }) {
+                    canvas.draw(
+                        image,
+                        DrawParam::new()
+                            .dest(Vec2::new(rect.x + rect.w / 2.0, rect.y + rect.h / 2.0))
+                            .scale(Vec2::splat(theme.piece_scale * rect.h / DEFAULT_CELL_SIZE))
+                            .offset(Vec2::new(0.5, 0.5)),
+                    );
+                }
+                if count > 1 {
+                    canvas.draw(
+                        graphics::Text::new(count.to_string()).set_scale(14.0),
+                        DrawParam::new().dest(Vec2::new(rect.x + rect.w - 10.0, rect.y + rect.h - 14.0)).color(Color::WHITE),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Looks up the opening again once the displayed position changes, returning whether it did
+    fn refresh_opening(&mut self) -> bool {
+        let board = self.displayed_board();
+        let shown = Some((board.moves.len(), board.polyglot_key()));
+        if shown == self.opening_for {
+            return false;
+        }
+        let opening = EcoTable::bundled().classify(board);
+        self.opening_for = shown;
+        let changed = opening != self.opening;
+        self.opening = opening;
+        changed
+    }
+
+    fn title(&self) -> String {
+        match self.opening {
+            Some(opening) => format!("Chess - {}", opening),
+            None => "Chess".to_string(),
+        }
+    }
+
+    /// ECO code and name of the opening, cut down to the last variation when the whole name doesn't fit the panel
+    fn opening_status(&self) -> Option<String> {
+        let opening = self.opening?;
+        let name = if opening.name.len() > 26 { opening.name.rsplit([':', ',']).next().unwrap_or(&opening.name).trim() } else { &opening.name };
+        Some(format!("{} {}", opening.eco, name))
+    }
+
+    /// The variant being played, with the checks given so far where they count
+    fn variant_status(&self) -> Option<String> {
+        if self.board.is_standard() {
+            return None;
+        }
+        let name = self.board.variant.name();
+        Some(match self.board.variant.check_limit() {
+            Some(_) => format!("{} - checks {}:{}", name, self.board.checks[0], self.board.checks[1]),
+            None => name.to_string(),
+        })
+    }
+
+    fn draw_clocks(&self, canvas: &mut Canvas) {
+        let panel = self.layout.panel();
+        let bottom = panel.y + self.layout.board_size() - CLOCK_HEIGHT;
+        for (color, y) in [(PieceColor::Black, panel.y), (PieceColor::White, bottom)] {
+            let active = match &self.clock {
+                Some(clock) => clock.is_running() && clock.turn() == color,
+                None => false,
+            };
+            let background = if active { Color::from_rgb(90, 110, 140) } else { Color::from_rgb(32, 31, 29) };
+            canvas.draw(
+                &graphics::Quad,
+                DrawParam::new()
+                    .dest(Vec2::new(panel.x, y))
+                    .scale(Vec2::new(PANEL_WIDTH, CLOCK_HEIGHT))
+                    .color(background),
+            );
+            let (time, time_color) = match &self.clock {
+                Some(clock) if clock.flagged() == Some(color) => (format_duration(clock.remaining(color)), Color::from_rgb(220, 80, 70)),
+                Some(clock) => (format_duration(clock.remaining(color)), Color::WHITE),
+                None => ("--:--".to_string(), Color::from_rgb(150, 150, 150)),
+            };
+            let side = match color {
+                PieceColor::White => "White",
+                PieceColor::Black => "Black",
+            };
+            canvas.draw(
+                graphics::Text::new(side).set_scale(18.0),
+                DrawParam::new().dest(Vec2::new(panel.x + MOVE_NUMBER_X, y + 20.0)).color(Color::from_rgb(150, 150, 150)),
+            );
+            canvas.draw(
+                graphics::Text::new(time).set_scale(32.0),
+                DrawParam::new().dest(Vec2::new(panel.x + BLACK_MOVE_X - 40.0, y + 14.0)).color(time_color),
+            );
+        }
+    }
+}
+
+/// Pieces offered when a pawn promotes, in the order they are listed, the king only where a variant allows it
+const PROMOTION_PIECES: [PieceType; 5] = [PieceType::Queen, PieceType::Knight, PieceType::Rook, PieceType::Bishop, PieceType::King];
+
+/// Square the `i`th promotion choice is picked from, counting from the promotion square towards the middle of the board
+fn promotion_square(to: Coords, i: usize) -> Coords {
+    let y = if to.y == 0 { to.y + i as u8 } else { to.y - i as u8 };
+    Coords::new(to.x, y)
+}
+
+fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgb(r, g, b)
+}
+
+fn rgba([r, g, b, a]: [u8; 4]) -> Color {
+    Color::from_rgba(r, g, b, a)
+}
+
+impl Default for BoardState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EventHandler for BoardState {
-    fn update(&mut self, _ctx: &mut Context) -> GameResult {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(clock) = &mut self.clock {
+            clock.tick(ctx.time.delta());
+            if let (None, Some(flagged)) = (self.outcome, clock.flagged()) {
+                self.outcome = Some(self.board.timeout(flagged));
+            }
+        }
+        match self.thinking.as_ref().map(|thinking| thinking.try_recv()) {
+            Some(Ok(mc)) => {
+                self.thinking = None;
+                if let (Some(mc), true) = (mc, self.computer_to_move()) {
+                    self.computer_move(mc);
+                }
+            }
+            Some(Err(mpsc::TryRecvError::Empty)) => (),
+            Some(Err(mpsc::TryRecvError::Disconnected)) => self.thinking = None,
+            None if self.computer_to_move() => self.start_thinking(),
+            None => (),
+        }
+        if let Some((reply, delay)) = self.puzzle_reply {
+            match delay.checked_sub(ctx.time.delta()) {
+                Some(delay) if !delay.is_zero() => self.puzzle_reply = Some((reply, delay)),
+                _ => {
+                    self.puzzle_reply = None;
+                    self.computer_move(reply);
+                }
+            }
+        }
+        self.follow_analysis();
+        if self.refresh_opening() {
+            ctx.gfx.set_window_title(&self.title());
+        }
+        Ok(())
+    }
+
+    fn resize_event(&mut self, _ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        self.layout = Layout { pockets: self.layout.pockets, analysis_rows: self.layout.analysis_rows, ..Layout::new(width, height) };
         Ok(())
     }
 
     fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let mut canvas = Canvas::from_frame(ctx, Color::WHITE);
+        self.assets.prepare(ctx)?;
+        let mut canvas = Canvas::from_frame(ctx, Color::from_rgb(32, 31, 29));
+        let layout = self.layout;
+        let cell_size = layout.cell_size;
+        let board = self.displayed_board();
+        let theme = self.current_theme();
+        let (light, dark) = (rgb(theme.light_square), rgb(theme.dark_square));
+        let last_move = board.moves.last();
         for row in 0..8 {
             for col in 0..8 {
-                let piece = self.board.get_piece((col, row));
-                let color: Color = if (row + col) % 2 == 0 {
-                    graphics::Color::from_rgb(240, 217, 181)
-                } else {
-                    graphics::Color::from_rgb(181, 136, 99)
-                };
+                let piece = board.get_piece((col, row));
+                let (color, opposite) = if (row + col) % 2 == 0 { (light, dark) } else { (dark, light) };
 
-                let rect = Rect::new(
-                    col as f32 * GRID_CELL_SIZE,
-                    row as f32 * GRID_CELL_SIZE,
-                    GRID_CELL_SIZE,
-                    GRID_CELL_SIZE,
-                );
+                let coords: Coords = (col, row).into();
+                let rect = layout.square_rect(coords);
 
                 canvas.draw(
                     &graphics::Quad,
@@ -59,27 +1108,40 @@ impl EventHandler for BoardState {
                         .color(color),
                 );
 
-                let coords: Coords = (col, row).into();
+                if last_move.is_some_and(|mc| mc.from == coords || mc.to == coords) {
+                    canvas.draw(
+                        &graphics::Quad,
+                        DrawParam::new()
+                            .dest(Vec2::new(rect.x, rect.y))
+                            .scale(Vec2::new(rect.w, rect.h))
+                            .color(rgba(theme.last_move)),
+                    );
+                }
+
                 let row = coords.row();
                 let col = coords.col();
-                let scale = Vec2::new(1.25, 1.25);
-                let notation_offset = 15.0;
-                if row == 8 {
+                let scale = Vec2::splat(1.25 * layout.scale());
+                let notation_offset = 0.15 * cell_size;
+                let label_color = match theme.label_color {
+                    Some(label_color) => rgb(label_color),
+                    None => opposite,
+                };
+                if theme.labels == LabelStyle::Corner && row == 8 {
                     canvas.draw(
                         &graphics::Text::new(format!("{}", col)),
                         DrawParam::new()
-                            .dest(Vec2::new(rect.x + GRID_CELL_SIZE - notation_offset, rect.y + GRID_CELL_SIZE - notation_offset))
+                            .dest(Vec2::new(rect.x + cell_size - notation_offset, rect.y + cell_size - notation_offset))
                             .scale(scale)
-                            .color(Color::BLACK),
+                            .color(label_color),
                     );
                 }
-                if col == 'a' {
+                if theme.labels == LabelStyle::Corner && col == 'a' {
                     canvas.draw(
                         &graphics::Text::new(format!("{}", 9 - row)),
                         DrawParam::new()
                             .dest(Vec2::new(rect.x + notation_offset, rect.y + notation_offset))
                             .scale(scale)
-                            .color(Color::BLACK),
+                            .color(label_color),
                     );
                 }
 
@@ -87,12 +1149,15 @@ impl EventHandler for BoardState {
                 if piece.piece_type == PieceType::Empty {
                     continue;
                 }
-                let image = self.assets.image(&piece, ctx);
+                let image = match self.assets.image(&piece) {
+                    Some(image) => image,
+                    None => continue,
+                };
                 canvas.draw(
-                    &image,
+                    image,
                     DrawParam::new()
-                        .dest(Vec2::new(rect.x + GRID_CELL_SIZE / 2.0, rect.y + GRID_CELL_SIZE / 2.0))
-                        .scale(Vec2::new(3.7f32, 3.7f32))
+                        .dest(Vec2::new(rect.x + cell_size / 2.0, rect.y + cell_size / 2.0))
+                        .scale(Vec2::splat(theme.piece_scale * layout.scale()))
                         .offset(Vec2::new(0.5, 0.5)),
                 );
             }
@@ -100,23 +1165,23 @@ impl EventHandler for BoardState {
 
         // Highlight selected square
         if let Some(coords) = self.selected_square {
-            let col = coords.x as f32;
-            let row = coords.y as f32;
-            let highlight_rect = Rect::new(
-                col * GRID_CELL_SIZE,
-                row * GRID_CELL_SIZE,
-                GRID_CELL_SIZE,
-                GRID_CELL_SIZE,
-            );
+            let highlight_rect = layout.square_rect(coords);
 
             canvas.draw(
                 &graphics::Quad,
                 DrawParam::new()
                     .dest(Vec2::new(highlight_rect.x, highlight_rect.y))
                     .scale(Vec2::new(highlight_rect.w, highlight_rect.h))
-                    .color(graphics::Color::from_rgba(255, 0, 0, 100)),
+                    .color(rgba(theme.selected)),
             );
         }
+        self.draw_promotion(&mut canvas);
+        self.draw_move_list(&mut canvas);
+        self.draw_analysis(&mut canvas);
+        self.draw_pockets(&mut canvas);
+        self.draw_clocks(&mut canvas);
+        self.draw_buttons(&mut canvas);
+        self.draw_prompt(&mut canvas);
         canvas.finish(ctx)
     }
 
@@ -126,9 +1191,38 @@ impl EventHandler for BoardState {
         input: KeyInput,
         _repeated: bool,
     ) -> GameResult {
+        if self.prompt.is_some() {
+            self.prompt_key(input.keycode);
+            return Ok(());
+        }
+        self.message = None;
+        if self.promotion.is_some() && input.keycode == Some(KeyCode::Escape) {
+            self.promotion = None;
+            return Ok(());
+        }
         match input.keycode {
+            Some(KeyCode::S) if input.mods.contains(KeyMods::CTRL) => self.open_prompt(Command::SavePgn),
+            Some(KeyCode::E) if input.mods.contains(KeyMods::CTRL) => self.open_prompt(Command::SaveFen),
+            Some(KeyCode::O) if input.mods.contains(KeyMods::CTRL) => self.open_prompt(Command::Load),
             Some(KeyCode::Q) => ctx.request_quit(),
-            Some(KeyCode::R) => self.board = Board::new(),
+            Some(KeyCode::R) | Some(KeyCode::N) => self.new_game(),
+            Some(KeyCode::Z) if input.mods.contains(KeyMods::CTRL | KeyMods::SHIFT) => self.redo(),
+            Some(KeyCode::Z) if input.mods.contains(KeyMods::CTRL) => self.undo(),
+            Some(KeyCode::Y) if input.mods.contains(KeyMods::CTRL) => self.redo(),
+            Some(KeyCode::Back) => self.undo(),
+            Some(KeyCode::T) if self.board.moves.is_empty() => self.next_time_control(),
+            Some(KeyCode::C) if self.puzzles.is_none() => self.next_computer_side(),
+            Some(KeyCode::V) if self.puzzles.is_none() => self.next_variant(),
+            Some(KeyCode::A) => self.toggle_analysis(),
+            Some(KeyCode::B) => {
+                self.select_theme((self.theme + 1) % self.themes.len());
+                self.message = Some(format!("Board theme: {}", self.current_theme().name));
+            }
+            Some(KeyCode::P) => self.message = Some(format!("Piece set: {}", self.assets.next_set())),
+            Some(KeyCode::Left) => self.view_ply(self.displayed_ply().saturating_sub(1)),
+            Some(KeyCode::Right) => self.view_ply(self.displayed_ply() + 1),
+            Some(KeyCode::Home) => self.view_ply(0),
+            Some(KeyCode::End) => self.view_ply(self.board.moves.len()),
             _ => (),
         }
         if input.keycode == Some(KeyCode::Escape) {
@@ -138,18 +1232,82 @@ impl EventHandler for BoardState {
         Ok(())
     }
 
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if let Some(prompt) = &mut self.prompt {
+            if !character.is_control() {
+                prompt.input.push(character);
+            }
+        }
+        Ok(())
+    }
+
     fn mouse_button_down_event(
         &mut self,
-        ctx: &mut Context,
+        _ctx: &mut Context,
         button: MouseButton,
         x: f32,
         y: f32,
     ) -> GameResult {
-        let selected = self.get_square_at(x, y);
-        if selected.is_none() {
-            println!("Clicked outside the board");
+        self.message = None;
+        if self.prompt.is_some() {
+            return Ok(());
         }
-        println!("Clicked: {}, x: {}, y: {}", selected.unwrap(), selected.unwrap().x, selected.unwrap().y);
+        if self.layout.in_panel(x, y) {
+            if button != MouseButton::Left {
+                return Ok(());
+            }
+            if let Some((color, piece_type)) = self.layout.pocket_at(x, y) {
+                // Only the side to move picks from its pocket, and picking again puts the piece back
+                let live = self.viewing.is_none() && self.outcome.is_none() && !self.computer_to_move();
+                if live && color == self.board.turn && self.board.in_hand(color, piece_type) > 0 {
+                    self.selected_drop = if self.selected_drop == Some(piece_type) { None } else { Some(piece_type) };
+                    self.selected_square = None;
+                }
+                return Ok(());
+            }
+            match self.button_at(x, y) {
+                Some(Button::NewGame) => self.new_game(),
+                Some(Button::Undo) => self.undo(),
+                Some(Button::Redo) => self.redo(),
+                None => {
+                    if let Some(ply) = self.ply_at(x, y) {
+                        self.view_ply(ply);
+                    }
+                }
+            }
+            return Ok(());
+        }
+        // The reviewed position, finished games and the computer's turn are read only
+        if self.viewing.is_some() || self.outcome.is_some() || self.computer_to_move() || self.puzzle_waiting() {
+            return Ok(());
+        }
+        let selected = match self.layout.square_at(x, y) {
+            Some(selected) => selected,
+            None => {
+                log::debug!("Clicked outside the board");
+                return Ok(());
+            }
+        };
+        log::debug!("Clicked: {}, x: {}, y: {}", selected, selected.x, selected.y);
+        if self.promotion.is_some() {
+            if button == MouseButton::Left {
+                self.pick_promotion(selected);
+            } else {
+                self.promotion = None;
+            }
+            return Ok(());
+        }
+        if let Some(piece_type) = self.selected_drop.take() {
+            if button == MouseButton::Left {
+                let before = self.board.clone();
+                match self.board.drop_piece(piece_type, selected) {
+                    Ok(mc) => self.after_move(&before, &mc),
+                    Err(error) => self.message = Some(error.to_string()),
+                }
+            }
+            return Ok(());
+        }
+        let selected = Some(selected);
         match (button, self.selected_square, selected) {
             (MouseButton::Left, Some(from), Some(to)) => {
                 // Clicked on the same square
@@ -159,23 +1317,10 @@ impl EventHandler for BoardState {
                 }
                 // First click was on a piece and second click on different
                 if self.board.movable_piece(from) {
-                    let promotion = if self.board.is_promote(from, to) {
-                        Some(PieceType::Queen)
-                    } else {
-                        None
-                    };
-                    let moved = self.board.move_piece(from, to, promotion);
-                    println!("Valid move? {:?}", moved);
-                    if moved {
-                        self.selected_square = None;
-                    } else {
-                        self.selected_square = Some(to);
-                    }
-                
+                    self.click_move(from, to);
                 } else {
                     self.selected_square = Some(to);
                 }
-
             }
             _ => self.selected_square = selected,
         }
@@ -194,3 +1339,175 @@ impl EventHandler for BoardState {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let layout = Layout::new(WINDOW_WIDTH, BOARD_SIZE);
+        assert_eq!(layout.origin, Vec2::ZERO);
+        assert_eq!(layout.cell_size, DEFAULT_CELL_SIZE);
+        assert_eq!(layout.panel(), Vec2::new(BOARD_SIZE, 0.0));
+
+        // A wide window centers the board and panel, a tall one centers the board vertically
+        let wide = Layout::new(WINDOW_WIDTH + 400.0, 600.0);
+        assert_eq!(wide.board_size(), 600.0);
+        assert_eq!(wide.origin, Vec2::new(300.0, 0.0));
+        let tall = Layout::new(MIN_WINDOW_WIDTH, 1000.0);
+        assert_eq!(tall.board_size(), MIN_BOARD_SIZE);
+        assert_eq!(tall.origin.y, (1000.0 - MIN_BOARD_SIZE) / 2.0);
+    }
+
+    #[test]
+    fn test_layout_hit_testing() {
+        let layout = Layout::new(WINDOW_WIDTH + 400.0, 600.0);
+        assert_eq!(layout.square_at(305.0, 5.0), Some((0, 0).into()));
+        assert_eq!(layout.square_at(299.0, 5.0), None);
+        assert_eq!(layout.square_at(300.0 + 7.5 * 75.0, 599.0), Some((7, 7).into()));
+        assert_eq!(layout.square_rect((2, 1).into()), Rect::new(450.0, 75.0, 75.0, 75.0));
+        assert!(layout.in_panel(901.0, 10.0));
+        assert!(!layout.in_panel(899.0, 10.0));
+        assert!(!layout.in_panel(901.0 + PANEL_WIDTH, 10.0));
+    }
+
+    #[test]
+    fn test_layout_pockets() {
+        let mut layout = Layout::new(WINDOW_WIDTH, BOARD_SIZE);
+        let black_queen = (BOARD_SIZE + MOVE_NUMBER_X + 1.0, CLOCK_HEIGHT + 10.0);
+        assert_eq!(layout.pocket_at(black_queen.0, black_queen.1), None);
+        let rows = layout.move_list_bottom() - layout.move_list_top();
+        layout.pockets = true;
+        assert_eq!(layout.pocket_at(black_queen.0, black_queen.1), Some((PieceColor::Black, PieceType::Queen)));
+        let white_pawn = layout.pocket_rect(PieceColor::White, 4);
+        assert_eq!(layout.pocket_at(white_pawn.x + 1.0, white_pawn.y + 1.0), Some((PieceColor::White, PieceType::Pawn)));
+        assert!(white_pawn.right() <= BOARD_SIZE + PANEL_WIDTH);
+        // The move list makes room for both pockets, keeping its header and the status line clear of them
+        assert_eq!(layout.move_list_bottom() - layout.move_list_top(), rows - 2.0 * POCKET_HEIGHT);
+        assert!(layout.pocket_rect(PieceColor::Black, 0).bottom() <= layout.move_list_top() - 32.0);
+        assert!(white_pawn.top() >= layout.move_list_bottom() + 28.0);
+    }
+
+    #[test]
+    fn test_layout_analysis() {
+        let mut layout = Layout::new(WINDOW_WIDTH, BOARD_SIZE);
+        let (bottom, status) = (layout.move_list_bottom(), layout.status_top());
+        assert_eq!(status, bottom + 12.0);
+        layout.analysis_rows = ANALYSIS_LINES;
+        // The lines take room from the move list and leave the status line where it was
+        assert_eq!(layout.move_list_bottom(), bottom - layout.analysis_height());
+        assert_eq!(layout.status_top(), status);
+        assert!(layout.move_list_bottom() + layout.analysis_height() <= layout.status_top());
+    }
+
+    #[test]
+    fn test_puzzle_mode() {
+        let csv = "0000D,5rk1/1p3ppp/pq3b2/8/8/1P1Q1N2/P4PPP/3R2K1 w - - 2 27,d3d6 f8d8 d6d8 f6d8,1426,74,96,37862,advantage endgame short,,";
+        let trainer = Trainer::new(crate::puzzle::PuzzleSet::parse(csv).0, Default::default(), None);
+        let mut state = BoardState::new();
+        state.set_puzzles(trainer);
+        assert_eq!(state.board.turn, PieceColor::Black);
+        assert_eq!(state.notation, vec!["Qd6"]);
+        // A wrong move is taken back, a right one answered after a pause
+        let before = state.board.clone();
+        let wrong = state.board.parse_san("Qc7").unwrap();
+        state.board.apply(&wrong);
+        state.player_move(before, wrong);
+        assert_eq!(state.board.moves.len(), 1);
+        assert!(state.puzzle_status().unwrap().contains("failed"));
+        let before = state.board.clone();
+        let right = state.board.parse_san("Rd8").unwrap();
+        state.board.apply(&right);
+        state.player_move(before, right);
+        assert!(state.puzzle_waiting());
+        let (reply, _) = state.puzzle_reply.unwrap();
+        assert_eq!(reply.uci(), "d6d8");
+        state.undo();
+        assert_eq!(state.board.moves.len(), 2);
+    }
+
+    #[test]
+    fn test_promotion_choice() {
+        let csv = "0000U,8/6P1/8/8/8/8/k7/7K b - - 0 1,a2b2 g7g8n,1500,80,90,100,underPromotion oneMove,,";
+        let mut state = BoardState::new();
+        state.set_puzzles(Trainer::new(crate::puzzle::PuzzleSet::parse(csv).0, Default::default(), None));
+        let (g7, g8): (Coords, Coords) = ("g7".parse().unwrap(), "g8".parse().unwrap());
+        state.click_move(g7, g8);
+        let choices = vec![PieceType::Queen, PieceType::Knight, PieceType::Rook, PieceType::Bishop];
+        assert_eq!(state.promotion, Some((g7, g8, choices)));
+        assert_eq!(state.board.moves.len(), 1);
+        // The queen is offered on the promotion square and the knight below it
+        state.pick_promotion(g8);
+        assert_eq!(state.board.moves.len(), 1);
+        assert!(state.puzzle_status().unwrap().contains("failed"));
+        state.click_move(g7, g8);
+        state.pick_promotion("g7".parse().unwrap());
+        assert_eq!(state.notation, vec!["Kb2", "g8=N"]);
+        assert!(state.puzzle_status().unwrap().contains("solved"));
+        // Clicking away from the choices leaves the pawn where it was
+        state.set_board(Board::from_fen("8/6P1/8/8/8/8/k7/7K w - - 0 1").unwrap());
+        state.puzzles = None;
+        state.click_move(g7, g8);
+        state.pick_promotion("a1".parse().unwrap());
+        assert_eq!(state.promotion, None);
+        assert!(state.board.moves.is_empty());
+        // Antichess offers the king as well
+        state.set_board(Board::from_fen_variant("8/6P1/8/8/8/8/k7/8 w - - 0 1", &crate::variant::Antichess).unwrap());
+        state.click_move(g7, g8);
+        assert_eq!(state.promotion.as_ref().unwrap().2.last(), Some(&PieceType::King));
+    }
+
+    #[test]
+    fn test_move_list_black_first() {
+        let mut state = BoardState::new();
+        assert_eq!(state.move_list_start(), (0, 1));
+        state.set_board(Board::from_fen("4k3/8/8/8/8/8/8/4K3 b - - 0 12").unwrap());
+        assert_eq!(state.move_list_start(), (1, 12));
+        for san in ["Kd7", "Kd2", "Kc6"] {
+            let mc = state.board.parse_san(san).unwrap();
+            state.board.apply(&mc);
+        }
+        // Black's first move takes the second column of row 12, so White's reply opens row 13
+        assert_eq!(state.move_list_start(), (1, 12));
+        let panel = state.layout.panel();
+        let row = |row: f32| state.layout.move_list_top() + row * MOVE_ROW_HEIGHT + 1.0;
+        assert_eq!(state.ply_at(panel.x + WHITE_MOVE_X + 1.0, row(0.0)), None);
+        assert_eq!(state.ply_at(panel.x + BLACK_MOVE_X + 1.0, row(0.0)), Some(1));
+        assert_eq!(state.ply_at(panel.x + WHITE_MOVE_X + 1.0, row(1.0)), Some(2));
+        assert_eq!(state.ply_at(panel.x + BLACK_MOVE_X + 1.0, row(1.0)), Some(3));
+        assert_eq!(state.ply_at(panel.x + WHITE_MOVE_X + 1.0, row(2.0)), None);
+    }
+
+    #[test]
+    fn test_opening_title() {
+        let mut state = BoardState::new();
+        assert!(!state.refresh_opening());
+        assert_eq!(state.title(), "Chess");
+        for san in ["e4", "c5", "Nf3", "d6", "d4", "cxd4", "Nxd4", "Nf6", "Nc3", "a6", "Be3"] {
+            let mc = state.board.parse_san(san).unwrap();
+            state.board.apply(&mc);
+        }
+        assert!(state.refresh_opening());
+        assert_eq!(state.title(), "Chess - B90 Sicilian Defense: Najdorf Variation, English Attack");
+        assert_eq!(state.opening_status().unwrap(), "B90 English Attack");
+        // Stepping back through the game names the earlier position
+        state.view_ply(1);
+        assert!(state.refresh_opening());
+        assert_eq!(state.opening_status().unwrap(), "B00 King's Pawn Game");
+        assert!(!state.refresh_opening());
+    }
+
+    #[test]
+    fn test_load() {
+        let mut state = BoardState::new();
+        state.load("1. e4 e5 2. Nf3 *").unwrap();
+        assert_eq!(state.notation, vec!["e4", "e5", "Nf3"]);
+        state.load("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        assert_eq!(state.board.to_fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+        assert_eq!(state.load("no/such/game.pgn").unwrap_err(), "Could not read no/such/game.pgn: no such file");
+        assert_eq!(state.load("missing.fen").unwrap_err(), "Could not read missing.fen: no such file");
+        assert!(state.load("1. e4 e9").unwrap_err().starts_with("Invalid PGN"));
+        state.load("1. e4 e5 1/2-1/2").unwrap();
+    }
+}
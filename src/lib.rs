@@ -1,6 +1,34 @@
 pub mod board;
+pub mod chess960;
+pub mod variant;
+pub mod crazyhouse;
+pub mod clock;
 pub mod pieces;
 pub mod coords;
+pub mod fen;
 pub mod moves;
+pub mod chess_move;
+pub mod notation;
+pub mod pgn;
+pub mod epd;
+pub mod puzzle;
+pub mod zobrist;
+pub mod book;
+pub mod database;
+pub mod eco;
+pub mod syzygy;
+pub mod eval;
+pub mod search;
+pub mod analysis;
+pub mod annotate;
+pub mod uci;
+pub mod engine;
+pub mod tournament;
+pub mod tui;
+pub mod cli;
+#[cfg(feature = "gui")]
 pub mod gui;
+#[cfg(feature = "gui")]
+pub mod theme;
+#[cfg(feature = "gui")]
 pub mod assets;
\ No newline at end of file
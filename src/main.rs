@@ -1,15 +1,98 @@
-use chess_engine::gui::{BoardState, BOARD_SIZE};
-use ggez::{event::run, GameResult};
+use std::path::PathBuf;
 
+use chess_engine::{assets::AssetManager, book::Book, clock::TimeControl, gui::{BoardState, BOARD_SIZE, MIN_BOARD_SIZE, MIN_WINDOW_WIDTH, WINDOW_WIDTH}, pieces::Color, puzzle::Trainer, theme::ThemeConfig, variant};
+use ggez::{event::run, GameError, GameResult};
 
+/// Themes file used when no `--themes` argument is given
+const THEMES_ENV: &str = "CHESS_THEMES";
+/// Where the puzzle rating is kept, by default `puzzle-rating.txt` beside the puzzles
+const PUZZLE_RATING_ENV: &str = "CHESS_PUZZLE_RATING";
 
 fn main() -> GameResult {
+    env_logger::init();
+    let mut time_control = None;
+    let mut asset_dirs = vec![];
+    let mut themes = std::env::var_os(THEMES_ENV).map(PathBuf::from);
+    let mut computer = None;
+    let mut book = None;
+    let mut chess960 = false;
+    let mut rules = None;
+    let mut game = None;
+    let mut puzzles = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clock" => {
+                let spec = args.next().ok_or_else(|| GameError::CustomError("--clock needs a time control, e.g. 180+2".to_string()))?;
+                let control = spec.parse::<TimeControl>().map_err(|e| GameError::CustomError(format!("Invalid time control {}: {}", spec, e)))?;
+                time_control = Some(control);
+            }
+            "--assets" => {
+                let dir = args.next().ok_or_else(|| GameError::CustomError("--assets needs a piece set directory".to_string()))?;
+                asset_dirs.push(PathBuf::from(dir));
+            }
+            "--themes" => {
+                let file = args.next().ok_or_else(|| GameError::CustomError("--themes needs a themes file".to_string()))?;
+                themes = Some(PathBuf::from(file));
+            }
+            "--computer" => {
+                computer = match args.next().as_deref() {
+                    Some("white") | Some("w") => Some(Color::White),
+                    Some("black") | Some("b") => Some(Color::Black),
+                    _ => return Err(GameError::CustomError("--computer needs the side it plays, white or black".to_string())),
+                };
+            }
+            "--book" => {
+                let file = args.next().ok_or_else(|| GameError::CustomError("--book needs a Polyglot book file".to_string()))?;
+                book = Some(Book::open(file).map_err(|e| GameError::ResourceLoadError(e.to_string()))?);
+            }
+            "--puzzles" => {
+                let file = args.next().ok_or_else(|| GameError::CustomError("--puzzles needs a Lichess puzzle CSV file".to_string()))?;
+                puzzles = Some(PathBuf::from(file));
+            }
+            "--chess960" => chess960 = true,
+            "--variant" => {
+                let name = args.next().ok_or_else(|| GameError::CustomError("--variant needs a variant name, e.g. atomic".to_string()))?;
+                rules = Some(variant::from_name(&name).ok_or_else(|| GameError::CustomError(format!("Unknown variant {}", name)))?);
+            }
+            _ if arg.starts_with("--") => return Err(GameError::CustomError(format!("Unknown argument {}", arg))),
+            // A PGN or FEN file to open
+            _ => game = Some(arg),
+        }
+    }
+
+    let assets = AssetManager::load(asset_dirs).map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+    let mut state = BoardState::with_assets(assets);
+    state.set_time_control(time_control);
+    state.set_computer(computer);
+    state.set_book(book);
+    if chess960 {
+        state.set_chess960(true);
+    }
+    if let Some(rules) = rules {
+        state.set_variant(rules);
+    }
+    if let Some(themes) = themes {
+        let config = ThemeConfig::from_file(&themes).map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+        state.add_themes(config).map_err(|e| GameError::ResourceLoadError(e.to_string()))?;
+    }
+    if let Some(game) = game {
+        state.load(&game).map_err(GameError::CustomError)?;
+    }
+    if let Some(csv) = puzzles {
+        let rating = std::env::var_os(PUZZLE_RATING_ENV).map(PathBuf::from).unwrap_or_else(|| csv.with_file_name("puzzle-rating.txt"));
+        let trainer = Trainer::open(&csv, rating).map_err(GameError::ResourceLoadError)?;
+        state.set_puzzles(trainer);
+    }
+
     let (ctx, event_loop) = ggez::ContextBuilder::new("chess", "Your Name")
         .window_setup(ggez::conf::WindowSetup::default().title("Chess"))
-        .window_mode(ggez::conf::WindowMode::default().dimensions(BOARD_SIZE, BOARD_SIZE))
+        .window_mode(ggez::conf::WindowMode::default()
+            .dimensions(WINDOW_WIDTH, BOARD_SIZE)
+            .resizable(true)
+            .min_dimensions(MIN_WINDOW_WIDTH, MIN_BOARD_SIZE))
         .build()?;
 
-    let state = BoardState::new();
-    println!("{}", state.board);
+    log::debug!("Starting position:\n{}", state.board);
     run(ctx, event_loop, state)
 }
@@ -1,351 +1,356 @@
 use crate::{
-    board::Board,
+    board::{back_rank, castling_files, Board},
+    chess_move::{Move, MoveList},
     coords::{Coords, MoveCoords},
     pieces::{Color, Piece, PieceType},
 };
 
+const STRAIGHT: [(i8, i8); 4] = [(0, -1), (0, 1), (-1, 0), (1, 0)];
+const DIAGONAL: [(i8, i8); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const KNIGHT: [(i8, i8); 8] = [(2, 1), (2, -1), (-2, 1), (-2, -1), (1, 2), (1, -2), (-1, 2), (-1, -2)];
+
+fn offset(square: Coords, (dx, dy): (i8, i8)) -> Option<Coords> {
+    let x = square.x as i8 + dx;
+    let y = square.y as i8 + dy;
+    if (0..8).contains(&x) && (0..8).contains(&y) {
+        Some(Coords::new(x as u8, y as u8))
+    } else {
+        None
+    }
+}
+
 impl Board {
+    /// Expands generated moves of `piece` into `MoveCoords`
+    fn move_coords(piece: Piece, list: &MoveList) -> Vec<MoveCoords> {
+        list.iter()
+            .map(|mv| MoveCoords {
+                piece,
+                from: mv.from(),
+                to: mv.to(),
+                takes: mv.is_capture(),
+                promotion: mv.promotion(),
+                king_side_castle: mv.is_king_side_castle(),
+                queen_side_castle: mv.is_queen_side_castle(),
+                drop: false,
+            })
+            .collect()
+    }
+
+    /// Generates moves for a piece of `color` on `from` and expands its moves
+    fn piece_moves(&self, piece_type: PieceType, from: Coords, color: Color) -> Vec<MoveCoords> {
+        let mut list = MoveList::new();
+        self.generate_piece_moves(piece_type, from, color, &mut list);
+        Board::move_coords(Piece { piece_type, color }, &list)
+    }
+
     pub fn pawn_moves(&self, pawn_start: Coords, color: Color) -> Vec<MoveCoords> {
-        let x = pawn_start.x;
-        let y = pawn_start.y;
-        let piece = Piece {
-            piece_type: PieceType::Pawn,
-            color,
-        };
-        let mut moves = vec![];
-        let y = match piece.color {
-            Color::White => {
-                // Promotion
-                if y == 1 && self.is_empty((x, 0)) {
-                    use PieceType::*;
-                    let mc = MoveCoords {
-                        piece,
-                        from: pawn_start,
-                        to: Coords::new(x, 0),
-                        ..Default::default()
-                    };
-                    moves.extend(vec![
-                        mc.promote(Queen),
-                        mc.promote(Rook),
-                        mc.promote(Bishop),
-                        mc.promote(Knight),
-                    ]);
+        self.piece_moves(PieceType::Pawn, pawn_start, color)
+    }
+    pub fn rook_moves(&self, rook_start: Coords, color: Color) -> Vec<MoveCoords> {
+        self.piece_moves(PieceType::Rook, rook_start, color)
+    }
+    pub fn bishop_moves(&self, bishop_start: Coords, color: Color) -> Vec<MoveCoords> {
+        self.piece_moves(PieceType::Bishop, bishop_start, color)
+    }
+    pub fn knight_moves(&self, knight_start: Coords, color: Color) -> Vec<MoveCoords> {
+        self.piece_moves(PieceType::Knight, knight_start, color)
+    }
+    pub fn queen_moves(&self, queen_start: Coords, color: Color) -> Vec<MoveCoords> {
+        self.piece_moves(PieceType::Queen, queen_start, color)
+    }
+    pub fn king_moves(&self, king_start: Coords, color: Color) -> Vec<MoveCoords> {
+        self.piece_moves(PieceType::King, king_start, color)
+    }
 
-                // Starting move 2 squares
-                } else if y == 6 && self.is_empty((x, 5)) && self.is_empty((x, 4)) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: pawn_start,
-                        to: Coords::new(x, 4),
-                        ..Default::default()
-                    });
+    /// Moves of a `piece_type` of `color` standing on `from`, which may still leave its king in check
+    pub fn generate_piece_moves(&self, piece_type: PieceType, from: Coords, color: Color, list: &mut MoveList) {
+        match piece_type {
+            PieceType::Pawn => self.generate_pawn_moves(from, color, list),
+            PieceType::Knight => self.generate_steps(from, color, &KNIGHT, list),
+            PieceType::Bishop => self.generate_slides(from, color, &DIAGONAL, list),
+            PieceType::Rook => self.generate_slides(from, color, &STRAIGHT, list),
+            PieceType::Queen => {
+                self.generate_slides(from, color, &STRAIGHT, list);
+                self.generate_slides(from, color, &DIAGONAL, list);
+            }
+            PieceType::King => {
+                self.generate_steps(from, color, &STRAIGHT, list);
+                self.generate_steps(from, color, &DIAGONAL, list);
+                // Castling moves the king to the g or c file, checked against the rights and attacks in `can_castle`
+                let home = back_rank(color);
+                if from.y == home {
+                    if self.can_castle(color, true) {
+                        list.push(Move::new(from, Coords::new(castling_files(true).0, home), Move::KING_CASTLE));
+                    }
+                    if self.can_castle(color, false) {
+                        list.push(Move::new(from, Coords::new(castling_files(false).0, home), Move::QUEEN_CASTLE));
+                    }
                 }
-                y - 1
             }
-            Color::Black => {
-                // Promotion
-                if y == 6 && self.is_empty((x, 7)) {
-                    use PieceType::*;
-                    let mc = MoveCoords {
-                        piece,
-                        from: pawn_start,
-                        to: Coords::new(x, 5),
-                        ..Default::default()
-                    };
-                    moves.extend(vec![
-                        mc.promote(Queen),
-                        mc.promote(Rook),
-                        mc.promote(Bishop),
-                        mc.promote(Knight),
-                    ]);
+            PieceType::Empty => (),
+        }
+    }
 
-                // Starting move 2 squares
-                } else if y == 1 && self.is_empty((x, 2)) && self.is_empty((x, 3)) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: pawn_start,
-                        to: Coords::new(x, 3),
-                        ..Default::default()
-                    });
+    fn generate_pawn_moves(&self, from: Coords, color: Color, list: &mut MoveList) {
+        let (forward, last_rank) = match color {
+            Color::White => (-1, 0),
+            Color::Black => (1, 7),
+        };
+        let one_step = match offset(from, (0, forward)) {
+            Some(one_step) => one_step,
+            None => return,
+        };
+        // Reaching the last rank promotes to any of the four pieces, or those the variant allows
+        let mut push = |to: Coords, capture: bool| {
+            if to.y == last_rank {
+                for promotion in self.variant.promotions() {
+                    list.push(Move::new(from, to, Move::promotion_flags(*promotion, capture)));
                 }
-                y + 1
+            } else {
+                list.push(Move::new(from, to, if capture { Move::CAPTURE } else { Move::QUIET }));
             }
         };
-        // Advance normally
-        if self.is_empty((x, y)) {
-            moves.push(MoveCoords {
-                piece,
-                from: pawn_start,
-                to: Coords::new(x, y),
-                ..Default::default()
-            });
+        if self.is_empty(one_step) {
+            push(one_step, false);
         }
-        // Take diagonally to the left
-        if x > 0 && !self.is_empty(Coords::new(x - 1, y)) {
-            moves.push(MoveCoords {
-                piece,
-                from: pawn_start,
-                to: Coords::new(x - 1, y),
-                takes: true,
-                ..Default::default()
-            });
+        for dx in [-1, 1] {
+            if let Some(to) = offset(from, (dx, forward)) {
+                if self.piece_color(to) == Some(color.opposite()) {
+                    push(to, true);
+                }
+            }
         }
-        // Take diagonally to the right
-        if x < 7 && !self.is_empty(Coords::new(x + 1, y)) {
-            moves.push(MoveCoords {
-                piece,
-                from: pawn_start,
-                to: Coords::new(x + 1, y),
-                takes: true,
-                ..Default::default()
-            });
+        // Starting move 2 squares
+        if self.variant.can_double_push(color, from.y) && self.is_empty(one_step) {
+            let two_steps = Coords::new(from.x, (from.y as i8 + 2 * forward) as u8);
+            if self.is_empty(two_steps) {
+                list.push(Move::new(from, two_steps, Move::DOUBLE_PUSH));
+            }
+        }
+        // Take en passant the pawn which just moved two squares past
+        if let Some(target) = self.en_passant.filter(|target| color == self.turn && target.y == one_step.y && target.x.abs_diff(from.x) == 1) {
+            list.push(Move::new(from, target, Move::EN_PASSANT));
         }
+    }
 
-        moves
+    /// Knight and king moves, one step in each direction
+    fn generate_steps(&self, from: Coords, color: Color, steps: &[(i8, i8)], list: &mut MoveList) {
+        for to in steps.iter().filter_map(|step| offset(from, *step)) {
+            match self.piece_color(to) {
+                None => list.push(Move::new(from, to, Move::QUIET)),
+                Some(other) if other != color => list.push(Move::new(from, to, Move::CAPTURE)),
+                Some(_) => (),
+            }
+        }
+    }
+
+    /// Bishop, rook and queen moves, sliding along each direction until blocked
+    fn generate_slides(&self, from: Coords, color: Color, directions: &[(i8, i8)], list: &mut MoveList) {
+        for direction in directions {
+            let mut square = from;
+            while let Some(to) = offset(square, *direction) {
+                match self.piece_color(to) {
+                    None => list.push(Move::new(from, to, Move::QUIET)),
+                    Some(other) => {
+                        if other != color {
+                            list.push(Move::new(from, to, Move::CAPTURE));
+                        }
+                        break;
+                    }
+                }
+                square = to;
+            }
+        }
     }
-    pub fn rook_moves(&self, rook_start: Coords, color: Color) -> Vec<MoveCoords> {
-        let x = rook_start.x;
-        let y = rook_start.y;
-        let piece = Piece {
-            piece_type: PieceType::Rook,
-            color,
-        };
-        let mut moves = vec![];
-        let x = x as i8;
-        let y = y as i8;
 
-        let mut up = vec![];
-        let mut down = vec![];
-        let mut left = vec![];
-        let mut right = vec![];
-        for i in 1..8 {
-            up.push((x, y - i));
-            down.push((x, y + i));
-            left.push((x - i, y));
-            right.push((x + i, y));
+    /// Moves for the side to move, which may still leave its king in check
+    pub fn generate_moves(&self, list: &mut MoveList) {
+        for from in Coords::all() {
+            let piece = self.get_piece(from);
+            if piece.piece_type != PieceType::Empty && piece.color == self.turn {
+                self.generate_piece_moves(piece.piece_type, from, piece.color, list);
+            }
+        }
+        if self.variant.has_pockets() {
+            self.generate_drops(list);
         }
-        let parse_line = |mut line: Vec<(i8, i8)>, moves: &mut Vec<MoveCoords>| {
-            line.retain(|(x, y)| *x >= 0 && *x < 8 && *y >= 0 && *y < 8);
-            let line = line.iter().map(|(x, y)| Coords::new(*x as u8, *y as u8));
-            for mv in line {
-                if self.is_empty(mv) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: rook_start,
-                        to: mv,
-                        ..Default::default()
-                    });
-                } else if self.piece_color(mv) != self.piece_color(rook_start) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: rook_start,
-                        to: mv,
-                        takes: true,
-                        ..Default::default()
-                    });
-                    break;
-                } else {
-                    break;
+    }
+
+    /// Drops of the pieces the side to move holds in hand onto empty squares, pawns not on the first or last rank
+    fn generate_drops(&self, list: &mut MoveList) {
+        let pocket = self.pockets[self.turn as usize];
+        for piece_type in [PieceType::Pawn, PieceType::Knight, PieceType::Bishop, PieceType::Rook, PieceType::Queen] {
+            if pocket[piece_type as usize] == 0 {
+                continue;
+            }
+            for to in Coords::all().filter(|to| self.is_empty(*to)) {
+                if piece_type == PieceType::Pawn && (to.y == 0 || to.y == 7) {
+                    continue;
                 }
+                list.push(Move::drop(piece_type, to));
             }
-        };
-        parse_line(up, &mut moves);
-        parse_line(down, &mut moves);
-        parse_line(left, &mut moves);
-        parse_line(right, &mut moves);
+        }
+    }
 
-        moves
+    /// Moves for the side to move which don't leave its own king in check, or are otherwise legal in the variant
+    pub fn generate_legal_moves(&self, list: &mut MoveList) {
+        self.generate_moves(list);
+        let scratch = self.position();
+        list.retain(|mv| {
+            let mut after = scratch.clone();
+            after.make_move(mv);
+            self.variant.is_legal(self, mv, &after)
+        });
+        self.variant.filter_moves(self, list);
     }
 
-    pub fn bishop_moves(&self, bishop_start: Coords, color: Color) -> Vec<MoveCoords> {
-        let x = bishop_start.x;
-        let y = bishop_start.y;
-        let piece = Piece {
-            piece_type: PieceType::Bishop,
-            color,
+    pub fn king_position(&self, color: Color) -> Option<Coords> {
+        Coords::all().find(|c| self.piece_present(Piece { piece_type: PieceType::King, color }, *c))
+    }
+
+    /// Whether a piece of color `by` attacks `square`, whether or not anything stands on it
+    pub fn is_attacked(&self, square: Coords, by: Color) -> bool {
+        use PieceType::*;
+        let (x, y) = (square.x as i8, square.y as i8);
+        let piece_at = |x: i8, y: i8| {
+            if (0..8).contains(&x) && (0..8).contains(&y) {
+                Some(self.state[y as usize][x as usize])
+            } else {
+                None
+            }
         };
-        let mut moves = vec![];
-        let x = x as i8;
-        let y = y as i8;
+        let attacker = |x: i8, y: i8, types: &[PieceType]| piece_at(x, y).is_some_and(|p| p.color == by && types.contains(&p.piece_type));
 
-        let mut top_left = vec![];
-        let mut top_right = vec![];
-        let mut bottom_left = vec![];
-        let mut bottom_right = vec![];
-        for i in 1..8 {
-            top_left.push((x - i, y - i));
-            top_right.push((x + i, y - i));
-            bottom_left.push((x - i, y + i));
-            bottom_right.push((x + i, y + i));
+        // Pawns attack towards the opponent, so look back the other way
+        let pawn_y = match by {
+            Color::White => y + 1,
+            Color::Black => y - 1,
+        };
+        if attacker(x - 1, pawn_y, &[Pawn]) || attacker(x + 1, pawn_y, &[Pawn]) {
+            return true;
         }
-        let parse_diag = |mut diag: Vec<(i8, i8)>, moves: &mut Vec<MoveCoords>| {
-            diag.retain(|(x, y)| *x >= 0 && *x < 8 && *y >= 0 && *y < 8);
-            let diag = diag.iter().map(|(x, y)| Coords::new(*x as u8, *y as u8));
-            for mv in diag {
-                if self.is_empty(mv) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: bishop_start,
-                        to: mv,
-                        ..Default::default()
-                    });
-                } else if self.piece_color(mv) != self.piece_color(bishop_start) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: bishop_start,
-                        to: mv,
-                        takes: true,
-                        ..Default::default()
-                    });
-                    break;
-                } else {
-                    break;
+        let knight = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+        if knight.iter().any(|(dx, dy)| attacker(x + dx, y + dy, &[Knight])) {
+            return true;
+        }
+        let straight = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+        let diagonal = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+        if straight.iter().chain(diagonal.iter()).any(|(dx, dy)| attacker(x + dx, y + dy, &[King])) {
+            return true;
+        }
+        for (directions, types) in [(straight, [Rook, Queen]), (diagonal, [Bishop, Queen])] {
+            for (dx, dy) in directions {
+                let (mut nx, mut ny) = (x + dx, y + dy);
+                while let Some(piece) = piece_at(nx, ny) {
+                    if piece.piece_type != Empty {
+                        if piece.color == by && types.contains(&piece.piece_type) {
+                            return true;
+                        }
+                        break;
+                    }
+                    nx += dx;
+                    ny += dy;
                 }
             }
-        };
-        parse_diag(top_left, &mut moves);
-        parse_diag(top_right, &mut moves);
-        parse_diag(bottom_left, &mut moves);
-        parse_diag(bottom_right, &mut moves);
+        }
+        false
+    }
 
-        moves
+    pub fn in_check(&self, color: Color) -> bool {
+        match self.king_position(color) {
+            Some(king) => self.is_attacked(king, color.opposite()),
+            None => false,
+        }
     }
-    pub fn knight_moves(&self, knight_start: Coords, color: Color) -> Vec<MoveCoords> {
 
-        let mut moves = vec![];
-        // 8 possible moves
-        let x = knight_start.x as i8;
-        let y = knight_start.y as i8;
-        let piece = Piece {
-            piece_type: PieceType::Knight,
-            color,
-        };
-        // 4 moves move 2 in x and 1 in y
-        // 4 moves move 2 in y and 1 in x
-        let possible_moves = vec![
-            (x + 2, y + 1),
-            (x + 2, y - 1),
-            (x - 2, y + 1),
-            (x - 2, y - 1),
-            (x + 1, y + 2),
-            (x + 1, y - 2),
-            (x - 1, y + 2),
-            (x - 1, y - 2),
-        ];
-        for (nx, ny) in possible_moves {
-            if nx < 0 || nx > 7 || ny < 0 || ny > 7 {
-                continue;
-            }
-            let new_coord = Coords::new(nx as u8, ny as u8);
-            if self.is_empty(new_coord) {
-                moves.push(MoveCoords {
-                    piece,
-                    from: knight_start,
-                    to: new_coord,
-                    ..Default::default()
-                });
-            } else if self.piece_color(knight_start) != self.piece_color(new_coord) {
-                moves.push(MoveCoords {
-                    piece,
-                    from: knight_start,
-                    to: new_coord,
-                    takes: true,
-                    ..Default::default()
-                });
-            }
+    /// Moves for the side to move which don't leave its own king in check
+    pub fn legal_moves(&self) -> Vec<MoveCoords> {
+        let mut list = MoveList::new();
+        self.generate_legal_moves(&mut list);
+        list.iter().map(|mv| self.decode_move(*mv)).collect()
+    }
+
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check(self.turn) && self.legal_moves().is_empty()
+    }
+
+    /// Number of move sequences `depth` plies deep, for checking the move generator against known counts
+    pub fn perft(&self, depth: u32) -> u64 {
+        self.position().perft_position(depth)
+    }
+
+    fn perft_position(&self, depth: u32) -> u64 {
+        if depth == 0 {
+            return 1;
         }
-        moves
+        let mut list = MoveList::new();
+        self.generate_legal_moves(&mut list);
+        if depth == 1 {
+            return list.len() as u64;
+        }
+        list.iter()
+            .map(|mv| {
+                let mut after = self.clone();
+                after.make_move(*mv);
+                after.perft_position(depth - 1)
+            })
+            .sum()
     }
 
-    pub fn queen_moves(&self, queen_start: Coords, color: Color) -> Vec<MoveCoords> {
-        let mut moves = vec![];
-        let piece = Piece {
-            piece_type: PieceType::Queen,
-            color,
-        };
-        moves.append(&mut self.rook_moves(queen_start, color));
-        moves.append(&mut self.bishop_moves(queen_start, color));
-        moves
-            .iter()
-            .map(|mc| MoveCoords {
-                piece,
-                from: queen_start,
-                to: mc.to,
-                takes: mc.takes,
-                promotion: mc.promotion,
-                ..Default::default()
+    /// Perft counts split by the first move
+    pub fn divide(&self, depth: u32) -> Vec<(MoveCoords, u64)> {
+        let mut list = MoveList::new();
+        self.generate_legal_moves(&mut list);
+        list.iter()
+            .map(|mv| {
+                let mut after = self.position();
+                after.make_move(*mv);
+                (self.decode_move(*mv), after.perft_position(depth.saturating_sub(1)))
             })
             .collect()
     }
-    pub fn king_moves(&self, king_start: Coords, color: Color) -> Vec<MoveCoords> {
-        let x = king_start.x;
-        let y = king_start.y;
-        let piece = Piece {
-            piece_type: PieceType::King,
-            color,
-        };
-        let mut moves = vec![];
-        for dx in -2i8..=2i8 {
-            for dy in -1i8..=1i8 {
-                // Not moving
-                if dx == 0 && dy == 0 {
-                    continue;
-                }
-                // Off the board x axis
-                if x == 0 && dx == -1 || x == 7 && dx == 1 {
-                    continue;
-                }
-                // Off the board y axis
-                if y == 0 && dy == -1 || y == 7 && dy == 1 {
-                    continue;
-                }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                let new_x = (x as i8 + dx) as u8;
-                let new_y = (y as i8 + dy) as u8;
-                let new_coord = Coords::new(new_x, new_y);
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
 
-                if dx == 2 && dy == 0 && self.can_castle(color, true) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: king_start,
-                        to: Coords::new(new_x, new_y),
-                        king_side_castle: true,
-                        ..Default::default()
-                    });
-                    continue;
-                } else if dx == -2 && dy == 0 && self.can_castle(color, false) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: king_start,
-                        to: Coords::new(new_x, new_y),
-                        queen_side_castle: true,
-                        ..Default::default()
-                    });
-                    continue;
-                }
+    #[test]
+    fn test_perft() {
+        let board = Board::new();
+        assert_eq!(board.perft(1), 20);
+        assert_eq!(board.perft(2), 400);
+        assert_eq!(board.perft(3), 8902);
+        // Castling, en passant and promotions
+        let kiwipete = Board::from_fen(KIWIPETE).unwrap();
+        assert_eq!(kiwipete.perft(1), 48);
+        assert_eq!(kiwipete.perft(2), 2039);
+        let board = Board::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(board.perft(3), 2812);
+        let board = Board::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(board.perft(2), 264);
+        let board = Board::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(board.perft(2), 1486);
+    }
 
-                // TODO Check if the king is in check
-                // TODO Check if the king is moving into check
-                // TODO Check if the king is castling through check
-                if self.is_empty(new_coord) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: king_start,
-                        to: new_coord,
-                        ..Default::default()
-                    });
-                } else if self.piece_color(new_coord) != self.piece_color(king_start) {
-                    moves.push(MoveCoords {
-                        piece,
-                        from: king_start,
-                        to: new_coord,
-                        takes: true,
-                        ..Default::default()
-                    });
-                }
-            }
-        }
-        moves
+    #[test]
+    fn test_en_passant() {
+        let mut board = Board::from_fen("4k3/8/8/2Pp4/8/8/8/4K3 w - d6 0 2").unwrap();
+        let mc = board.parse_san("cxd6").unwrap();
+        assert!(mc.takes);
+        board.apply(&mc);
+        assert!(board.is_empty(Coords::new(3, 3)));
+        assert_eq!(board.to_fen(), "4k3/8/3P4/8/8/8/8/4K3 b - - 0 2");
+    }
+
+    #[test]
+    fn test_no_castling_through_check() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/5r2/R3K2R w KQ - 0 1").unwrap();
+        assert!(board.parse_san("O-O").is_none());
+        assert!(board.parse_san("O-O-O").is_some());
+        let board = Board::from_fen("4k3/8/8/8/8/8/4r3/R3K2R w KQ - 0 1").unwrap();
+        assert!(board.parse_san("O-O-O").is_none());
     }
 }
 /*
@@ -0,0 +1,269 @@
+use crate::{
+    board::Board,
+    coords::{Coords, MoveCoords},
+    pieces::PieceType,
+};
+
+impl Board {
+    /// Standard algebraic notation for `mc`, played from the current position
+    pub fn san(&self, mc: &MoveCoords) -> String {
+        let mut san = if mc.drop {
+            format!("{}@{}", mc.piece.piece_type.letter(), mc.to)
+        } else if mc.king_side_castle {
+            "O-O".to_string()
+        } else if mc.queen_side_castle {
+            "O-O-O".to_string()
+        } else {
+            let mut san = String::new();
+            if mc.piece.piece_type == PieceType::Pawn {
+                if mc.takes {
+                    san.push(mc.from.col());
+                }
+            } else {
+                san.push(mc.piece.piece_type.letter());
+                san.push_str(&self.disambiguation(mc));
+            }
+            if mc.takes {
+                san.push('x');
+            }
+            san.push_str(&mc.to.to_string());
+            if let Some(promotion) = mc.promotion {
+                san.push('=');
+                san.push(promotion.letter());
+            }
+            san
+        };
+
+        let mut after = self.clone();
+        after.apply(mc);
+        if after.is_checkmate() {
+            san.push('#');
+        } else if after.in_check(after.turn) {
+            san.push('+');
+        }
+        san
+    }
+
+    /// The game so far in standard algebraic notation, one entry per ply
+    pub fn san_moves(&self) -> Vec<String> {
+        let mut board = self.replay(0);
+        let mut sans = Vec::with_capacity(self.moves.len());
+        for mc in self.moves.iter() {
+            sans.push(board.san(mc));
+            board.apply(mc);
+        }
+        sans
+    }
+
+    /// Finds the legal move written as `san`, accepting redundant disambiguation, `0-0` castling and `@e4` pawn drops
+    pub fn parse_san(&self, san: &str) -> Option<MoveCoords> {
+        let san = san.trim_end_matches(['+', '#', '!', '?']);
+        let legal = self.legal_moves();
+        match san {
+            "O-O" | "0-0" => return legal.into_iter().find(|mc| mc.king_side_castle),
+            "O-O-O" | "0-0-0" => return legal.into_iter().find(|mc| mc.queen_side_castle),
+            _ => (),
+        }
+        if let Some((piece, to)) = san.split_once('@') {
+            return self.find_drop(legal, piece, to);
+        }
+
+        let mut chars = san.chars().filter(|c| *c != 'x' && *c != '=' && *c != '-').collect::<Vec<_>>();
+        let promotion = match chars.last() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let promotion = PieceType::from_letter(*c)?;
+                chars.pop();
+                Some(promotion)
+            }
+            _ => None,
+        };
+        let piece_type = match chars.first() {
+            Some(c) if c.is_ascii_uppercase() => {
+                let piece_type = PieceType::from_letter(*c)?;
+                chars.remove(0);
+                piece_type
+            }
+            _ => PieceType::Pawn,
+        };
+        if chars.len() < 2 {
+            return None;
+        }
+        let to: Coords = chars.split_off(chars.len() - 2).iter().collect::<String>().parse().ok()?;
+        let from_file = chars.iter().find(|c| c.is_ascii_lowercase()).map(|c| *c as u8 - b'a');
+        let from_rank = chars.iter().find(|c| c.is_ascii_digit()).and_then(|c| c.to_digit(10)).map(|rank| 8 - rank as u8);
+
+        let mut candidates = legal.into_iter().filter(|mc| {
+            mc.piece.piece_type == piece_type
+                && mc.to == to
+                && mc.promotion == promotion
+                && !mc.king_side_castle
+                && !mc.queen_side_castle
+                && !mc.drop
+                && from_file.is_none_or(|x| mc.from.x == x)
+                && from_rank.is_none_or(|y| mc.from.y == y)
+        });
+        let mc = candidates.next()?;
+        match candidates.next() {
+            Some(_) => None,
+            None => Some(mc),
+        }
+    }
+
+    /// Finds the legal move written in UCI long algebraic notation, such as `e2e4`, `a7a8q` or `N@f3`
+    pub fn parse_uci(&self, uci: &str) -> Option<MoveCoords> {
+        if !(4..=5).contains(&uci.len()) || !uci.is_ascii() {
+            return None;
+        }
+        if &uci[1..2] == "@" {
+            return self.find_drop(self.legal_moves(), &uci[..1], &uci[2..]);
+        }
+        let from: Coords = uci[0..2].parse().ok()?;
+        let to: Coords = uci[2..4].parse().ok()?;
+        let promotion = match uci[4..].chars().next() {
+            Some(c) => Some(PieceType::from_letter(c.to_ascii_uppercase())?),
+            None => None,
+        };
+        let legal = self.legal_moves();
+        let castles = |mc: &MoveCoords| mc.king_side_castle || mc.queen_side_castle;
+        // A king move to the castling square is taken as castling only when the king has no plain move there,
+        // and in Chess960 notation castling is written as the king taking its own rook
+        legal
+            .iter()
+            .find(|mc| mc.from == from && mc.to == to && mc.promotion == promotion && !castles(mc))
+            .or_else(|| legal.iter().find(|mc| mc.from == from && promotion.is_none() && castles(mc) && (mc.to == to || self.castling_rook(mc) == Some(to))))
+            .copied()
+    }
+
+    /// UCI notation for Chess960 GUIs, which write castling as the king taking its own rook
+    pub fn uci_chess960(&self, mc: &MoveCoords) -> String {
+        match self.castling_rook(mc) {
+            Some(rook) => format!("{}{}", mc.from, rook),
+            None => mc.uci(),
+        }
+    }
+
+    /// Reads a move typed by a user, in either UCI or standard algebraic notation
+    pub fn parse_move(&self, text: &str) -> Option<MoveCoords> {
+        let text = text.trim();
+        self.parse_uci(text).or_else(|| self.parse_san(text))
+    }
+
+    /// The drop of the piece lettered `piece`, a pawn if empty, on the square `to`
+    fn find_drop(&self, legal: Vec<MoveCoords>, piece: &str, to: &str) -> Option<MoveCoords> {
+        let piece_type = match piece {
+            "" => PieceType::Pawn,
+            piece if piece.len() == 1 => PieceType::from_letter(piece.chars().next()?.to_ascii_uppercase())?,
+            _ => return None,
+        };
+        let to: Coords = to.parse().ok()?;
+        legal.into_iter().find(|mc| mc.drop && mc.piece.piece_type == piece_type && mc.to == to)
+    }
+
+    fn disambiguation(&self, mc: &MoveCoords) -> String {
+        let others = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| other.piece == mc.piece && other.to == mc.to && other.from != mc.from && !other.drop)
+            .collect::<Vec<_>>();
+        if others.is_empty() {
+            return String::new();
+        }
+        let (file, rank) = mc.from.std();
+        if others.iter().all(|other| other.from.x != mc.from.x) {
+            file.to_string()
+        } else if others.iter().all(|other| other.from.y != mc.from.y) {
+            rank.to_string()
+        } else {
+            format!("{}{}", file, rank)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{coords::Coords, pieces::Color};
+
+    fn play(board: &mut Board, moves: &[(&str, &str)]) {
+        for (from, to) in moves {
+            let from: Coords = from.parse().unwrap();
+            let to: Coords = to.parse().unwrap();
+            assert!(board.move_piece(from, to, None).is_ok(), "{}{} should be legal", from, to);
+        }
+    }
+
+    #[test]
+    fn test_san_moves() {
+        let mut board = Board::new();
+        play(&mut board, &[("e2", "e4"), ("e7", "e5"), ("g1", "f3"), ("b8", "c6"), ("f1", "c4"), ("g8", "f6"), ("e1", "g1")]);
+        assert_eq!(board.san_moves(), vec!["e4", "e5", "Nf3", "Nc6", "Bc4", "Nf6", "O-O"]);
+    }
+
+    #[test]
+    fn test_san_check_and_mate() {
+        let mut board = Board::new();
+        play(&mut board, &[("f2", "f3"), ("e7", "e5"), ("g2", "g4"), ("d8", "h4")]);
+        assert_eq!(board.san_moves().last().unwrap(), "Qh4#");
+        assert!(board.is_checkmate());
+    }
+
+    #[test]
+    fn test_san_disambiguation() {
+        let mut board = Board::new();
+        play(&mut board, &[("g1", "f3"), ("a7", "a6"), ("b1", "c3"), ("a6", "a5"), ("c3", "e4"), ("a5", "a4")]);
+        let mc = MoveCoords {
+            piece: board.get_piece("f3".parse::<Coords>().unwrap()),
+            from: "f3".parse().unwrap(),
+            to: "g5".parse().unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(board.san(&mc), "Nfg5");
+    }
+
+    #[test]
+    fn test_parse_san() {
+        let mut board = Board::new();
+        play(&mut board, &[("g1", "f3"), ("a7", "a6"), ("b1", "c3"), ("a6", "a5"), ("c3", "e4"), ("a5", "a4")]);
+        assert!(board.parse_san("Ng5").is_none());
+        let mc = board.parse_san("Nfg5").unwrap();
+        assert_eq!(mc.from, "f3".parse().unwrap());
+        assert_eq!(board.parse_san("Neg5").unwrap().from, "e4".parse().unwrap());
+        assert_eq!(board.parse_san("e3").unwrap().to, "e3".parse().unwrap());
+        assert!(board.parse_san("Ke2").is_none());
+        assert!(board.parse_san("Qh5").is_none());
+        assert!(board.parse_san("z9").is_none());
+        assert!(board.parse_san("").is_none());
+        for san in board.legal_moves().iter().map(|mc| board.san(mc)) {
+            assert_eq!(board.san(&board.parse_san(&san).unwrap()), san);
+        }
+    }
+
+    #[test]
+    fn test_uci() {
+        let board = Board::from_fen("r3k3/1P6/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let castle = board.parse_uci("e1g1").unwrap();
+        assert!(castle.king_side_castle);
+        assert_eq!(castle.uci(), "e1g1");
+        assert!(board.parse_uci("b7b8").is_none());
+        let promotion = board.parse_uci("b7a8n").unwrap();
+        assert_eq!(promotion.promotion, Some(PieceType::Knight));
+        assert!(promotion.takes);
+        assert_eq!(promotion.uci(), "b7a8n");
+        assert_eq!(board.san(&promotion), "bxa8=N");
+        assert!(board.parse_uci("e1e3").is_none());
+        assert!(board.parse_uci("z9a1").is_none());
+        assert_eq!(board.parse_move("b8=Q").unwrap().uci(), "b7b8q");
+        assert_eq!(board.parse_move(" Kf2 ").unwrap().uci(), "e1f2");
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut board = Board::new();
+        play(&mut board, &[("e2", "e4"), ("e7", "e5")]);
+        let replayed = board.replay(1);
+        assert_eq!(replayed.moves.len(), 1);
+        assert_eq!(replayed.turn, Color::Black);
+        assert!(replayed.is_empty("e2".parse::<Coords>().unwrap()));
+        assert!(!replayed.is_empty("e7".parse::<Coords>().unwrap()));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::{
+    board::Board,
+    coords::MoveCoords,
+    pieces::Color,
+    variant::{self, Standard},
+};
+
+pub struct Pgn {
+    pub headers: Vec<(String, String)>,
+    pub board: Board,
+    /// Notes written after each ply of `board.moves`, where there are any
+    pub annotations: Vec<Annotation>,
+}
+
+/// What is written after a move besides its SAN
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    /// Numeric annotation glyphs, such as 2 for `?` and 4 for `??`
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    /// Lines which could have been played instead, starting from the position before the move
+    pub variations: Vec<Vec<MoveCoords>>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        self.nags.is_empty() && self.comment.is_none() && self.variations.is_empty()
+    }
+}
+
+/// The move suffix standing for NAGs 1 to 6, `$n` for the others
+fn nag_text(nag: u8) -> String {
+    match nag {
+        1 => "!".to_string(),
+        2 => "?".to_string(),
+        3 => "!!".to_string(),
+        4 => "??".to_string(),
+        5 => "!?".to_string(),
+        6 => "?!".to_string(),
+        nag => format!("${}", nag),
+    }
+}
+
+/// Move numbers and SAN of `moves` played from `board`, the first one numbered even when Black plays it
+fn numbered_moves(board: &Board, moves: &[MoveCoords]) -> Vec<String> {
+    let mut board = board.clone();
+    let mut tokens = vec![];
+    for (i, mc) in moves.iter().enumerate() {
+        match board.turn {
+            Color::White => tokens.push(format!("{}.", board.fullmove_number)),
+            Color::Black if i == 0 => tokens.push(format!("{}...", board.fullmove_number)),
+            Color::Black => (),
+        }
+        tokens.push(board.san(mc));
+        board.apply(mc);
+    }
+    tokens
+}
+
+impl Pgn {
+    /// Wraps a game with the seven tag roster, taking the result from the final position
+    pub fn new(board: Board) -> Self {
+        let result = board.outcome().map(|outcome| outcome.result()).unwrap_or("*");
+        let mut pgn = Pgn { headers: vec![], board, annotations: vec![] };
+        for (name, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", result),
+        ] {
+            pgn.set_header(name, value);
+        }
+        pgn
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn result(&self) -> &str {
+        self.header("Result").unwrap_or("*")
+    }
+
+    /// Notes for the move at `ply`, counting from 0
+    pub fn annotation_mut(&mut self, ply: usize) -> &mut Annotation {
+        if self.annotations.len() <= ply {
+            self.annotations.resize(ply + 1, Annotation::default());
+        }
+        &mut self.annotations[ply]
+    }
+
+    /// Parses the first game in `pgn`
+    pub fn parse(pgn: &str) -> Result<Pgn, String> {
+        Pgn::parse_all(pgn)?.into_iter().next().ok_or_else(|| "No game found".to_string())
+    }
+
+    /// Parses every game in a PGN file, skipping comments, variations and NAGs
+    pub fn parse_all(pgn: &str) -> Result<Vec<Pgn>, String> {
+        let mut games = vec![];
+        let mut headers = vec![];
+        let mut movetext = String::new();
+        for line in pgn.lines() {
+            let line = line.trim();
+            if line.starts_with('[') && line.ends_with(']') {
+                if !movetext.trim().is_empty() {
+                    games.push(Pgn::from_parts(std::mem::take(&mut headers), &movetext)?);
+                    movetext.clear();
+                }
+                headers.push(parse_header(line)?);
+            } else if !line.starts_with('%') {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        }
+        if !headers.is_empty() || !movetext.trim().is_empty() {
+            games.push(Pgn::from_parts(headers, &movetext)?);
+        }
+        Ok(games)
+    }
+
+    fn from_parts(headers: Vec<(String, String)>, movetext: &str) -> Result<Pgn, String> {
+        let board = start_board(&headers)?;
+        let mut pgn = Pgn { headers, board, annotations: vec![] };
+        for token in movetext_tokens(movetext) {
+            match token.as_str() {
+                "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                    if pgn.header("Result").is_none() {
+                        pgn.set_header("Result", &token);
+                    }
+                    break;
+                }
+                san => {
+                    let mc = pgn
+                        .board
+                        .parse_san(san)
+                        .ok_or_else(|| format!("Illegal or ambiguous move {} at ply {}", san, pgn.board.moves.len() + 1))?;
+                    pgn.board.apply(&mc);
+                }
+            }
+        }
+        Ok(pgn)
+    }
+}
+
+impl std::fmt::Display for Pgn {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (name, value) in self.headers.iter() {
+            writeln!(f, "[{} \"{}\"]", name, value.replace('\\', "\\\\").replace('"', "\\\""))?;
+        }
+        let start = self.board.replay(0);
+        if self.header("Variant").is_none() && !start.is_standard() {
+            writeln!(f, "[Variant \"{}\"]", start.variant.name())?;
+        } else if self.header("Variant").is_none() && start.is_chess960() {
+            writeln!(f, "[Variant \"Chess960\"]")?;
+        }
+        if self.header("FEN").is_none() && start.to_fen() != Board::new_variant(start.variant).to_fen() {
+            writeln!(f, "[SetUp \"1\"]")?;
+            writeln!(f, "[FEN \"{}\"]", start.to_fen())?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = vec![];
+        let mut board = start;
+        // Black's move needs its number again after a comment or variation
+        let mut renumber = true;
+        for (i, mc) in self.board.moves.iter().enumerate() {
+            match board.turn {
+                Color::White => tokens.push(format!("{}.", board.fullmove_number)),
+                Color::Black if renumber => tokens.push(format!("{}...", board.fullmove_number)),
+                Color::Black => (),
+            }
+            let mut san = board.san(mc);
+            let annotation = self.annotations.get(i).filter(|annotation| !annotation.is_empty());
+            renumber = annotation.is_some_and(|annotation| annotation.comment.is_some() || !annotation.variations.is_empty());
+            if let Some(annotation) = annotation {
+                let (suffixes, glyphs): (Vec<_>, Vec<_>) = annotation.nags.iter().map(|nag| nag_text(*nag)).partition(|text| !text.starts_with('$'));
+                san.push_str(&suffixes.concat());
+                tokens.push(san);
+                tokens.extend(glyphs);
+                if let Some(comment) = &annotation.comment {
+                    let words = comment.replace('}', ")").split_whitespace().map(|word| word.to_string()).collect::<Vec<_>>();
+                    tokens.push("{".to_string() + words.first().map_or("", |word| word.as_str()));
+                    tokens.extend(words.into_iter().skip(1));
+                    tokens.last_mut().expect("opened above").push('}');
+                }
+                for variation in annotation.variations.iter().filter(|variation| !variation.is_empty()) {
+                    let mut moves = numbered_moves(&board, variation);
+                    moves[0].insert(0, '(');
+                    moves.last_mut().expect("not empty").push(')');
+                    tokens.extend(moves);
+                }
+            } else {
+                tokens.push(san);
+            }
+            board.apply(mc);
+        }
+        tokens.push(self.result().to_string());
+
+        // Export format keeps lines under 80 characters
+        let mut line_len = 0;
+        for token in tokens {
+            if line_len > 0 && line_len + 1 + token.len() > 79 {
+                writeln!(f)?;
+                line_len = 0;
+            } else if line_len > 0 {
+                write!(f, " ")?;
+                line_len += 1;
+            }
+            write!(f, "{}", token)?;
+            line_len += token.len();
+        }
+        writeln!(f)
+    }
+}
+
+/// The position a game starts from, given by its `Variant` and `FEN` headers
+pub(crate) fn start_board(headers: &[(String, String)]) -> Result<Board, String> {
+    let header = |name: &str| headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    // Variants this crate doesn't know, Chess960 among them, are played by the standard rules
+    let rules = header("Variant").and_then(variant::from_name).unwrap_or(&Standard);
+    match header("FEN") {
+        Some(fen) => Board::from_fen_variant(fen, rules).map_err(|e| format!("Invalid FEN header: {}", e)),
+        None => Ok(Board::new_variant(rules)),
+    }
+}
+
+fn parse_header(line: &str) -> Result<(String, String), String> {
+    let inner = &line[1..line.len() - 1];
+    let (name, value) = inner.split_once(' ').ok_or_else(|| format!("Invalid header {}", line))?;
+    let value = value.trim();
+    if !(value.len() >= 2 && value.starts_with('"') && value.ends_with('"')) {
+        return Err(format!("Invalid header {}", line));
+    }
+    let value = value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\");
+    Ok((name.to_string(), value))
+}
+
+fn push_token(token: &mut String, tokens: &mut Vec<String>) {
+    // Move numbers may be attached to the move, as in `12...Nf6`
+    let after_number = token.trim_start_matches(|c: char| c.is_ascii_digit());
+    let t = if after_number.starts_with('.') { after_number.trim_start_matches('.') } else { token.as_str() };
+    if !t.is_empty() && !t.starts_with('$') && !t.chars().all(|c| c == '!' || c == '?') {
+        tokens.push(t.to_string());
+    }
+    token.clear();
+}
+
+/// SAN moves and the result, without move numbers, comments, variations or NAGs
+fn movetext_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut chars = movetext.chars();
+    let mut depth = 0u32;
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                push_token(&mut token, &mut tokens);
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                push_token(&mut token, &mut tokens);
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => {
+                push_token(&mut token, &mut tokens);
+                depth += 1;
+            }
+            ')' => {
+                token.clear();
+                depth = depth.saturating_sub(1);
+            }
+            c if c.is_whitespace() => {
+                if depth == 0 {
+                    push_token(&mut token, &mut tokens);
+                }
+                token.clear();
+            }
+            c if depth == 0 => token.push(c),
+            _ => (),
+        }
+    }
+    push_token(&mut token, &mut tokens);
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = r#"[Event "Casual"]
+[Site "?"]
+[Date "2024.01.01"]
+[Round "1"]
+[White "Alice"]
+[Black "Bob"]
+[Result "0-1"]
+
+1. f3 {weak} e5 2. g4?! (2. e4 Nf6) Qh4# $4 0-1
+"#;
+
+    #[test]
+    fn test_parse_pgn() {
+        let pgn = Pgn::parse(GAME).unwrap();
+        assert_eq!(pgn.header("White"), Some("Alice"));
+        assert_eq!(pgn.result(), "0-1");
+        assert_eq!(pgn.board.san_moves(), vec!["f3", "e5", "g4", "Qh4#"]);
+        assert!(pgn.board.is_checkmate());
+    }
+
+    #[test]
+    fn test_write_pgn() {
+        let pgn = Pgn::parse(GAME).unwrap();
+        let written = pgn.to_string();
+        assert!(written.contains("[White \"Alice\"]"));
+        assert!(written.ends_with("\n1. f3 e5 2. g4 Qh4# 0-1\n"));
+        let reparsed = Pgn::parse(&written).unwrap();
+        assert_eq!(reparsed.board.to_fen(), pgn.board.to_fen());
+    }
+
+    #[test]
+    fn test_pgn_from_fen() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 30").unwrap();
+        let mc = board.parse_san("Kd7").unwrap();
+        board.apply(&mc);
+        let mut pgn = Pgn::new(board);
+        pgn.set_header("White", "Carol");
+        let written = pgn.to_string();
+        assert!(written.contains("[FEN \"4k3/8/8/8/8/8/4P3/4K3 b - - 0 30\"]"));
+        assert!(written.contains("30... Kd7 *"));
+        let reparsed = Pgn::parse(&written).unwrap();
+        assert_eq!(reparsed.board.moves.len(), 1);
+        assert!(!written.contains("Variant"));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut pgn = Pgn::parse(GAME).unwrap();
+        let board = pgn.board.replay(2);
+        let e4 = board.parse_san("e4").unwrap();
+        let annotation = pgn.annotation_mut(2);
+        annotation.nags = vec![6, 18];
+        annotation.comment = Some("Weakens {the} king".to_string());
+        annotation.variations.push(vec![e4]);
+        pgn.annotation_mut(3).nags.push(1);
+        let written = pgn.to_string();
+        assert!(written.ends_with("\n1. f3 e5 2. g4?! $18 {Weakens {the) king} (2. e4) 2... Qh4#! 0-1\n"), "{}", written);
+        let reparsed = Pgn::parse(&written).unwrap();
+        assert_eq!(reparsed.board.to_fen(), pgn.board.to_fen());
+        assert!(reparsed.annotations.is_empty());
+    }
+
+    #[test]
+    fn test_chess960_pgn() {
+        // Castling short from g1 keeps the king there and moves the rook from h1 to f1
+        let mut board = Board::from_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1").unwrap();
+        for san in ["f4", "f5", "Rf3", "Rf6", "O-O", "O-O"] {
+            let mc = board.parse_san(san).unwrap();
+            board.apply(&mc);
+        }
+        let written = Pgn::new(board.clone()).to_string();
+        assert!(written.contains("[Variant \"Chess960\"]"));
+        assert!(written.contains("[FEN \"bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1\"]"));
+        assert!(written.contains("3. O-O O-O"));
+        let reparsed = Pgn::parse(&written).unwrap();
+        assert_eq!(reparsed.board.to_fen(), board.to_fen());
+        assert_eq!(board.to_fen(), "bbqnnrk1/ppppp1pp/5r2/5p2/5P2/5R2/PPPPP1PP/BBQNNRK1 w - - 4 4");
+    }
+
+    #[test]
+    fn test_variant_pgn() {
+        let mut board = Board::new_variant(&crate::variant::Atomic);
+        // Taking on d2 blows up the king on e1
+        for san in ["e4", "d5", "exd5", "Qxd2"] {
+            let mc = board.parse_san(san).unwrap();
+            board.apply(&mc);
+        }
+        let written = Pgn::new(board.clone()).to_string();
+        assert!(written.contains("[Variant \"Atomic\"]"));
+        assert!(!written.contains("[FEN"));
+        assert!(written.contains("2. exd5 Qxd2 0-1"));
+        let reparsed = Pgn::parse(&written).unwrap();
+        assert_eq!(reparsed.board.variant.name(), "Atomic");
+        assert_eq!(reparsed.board.to_fen(), board.to_fen());
+
+        let board = Board::new_variant(&crate::variant::Horde);
+        let written = Pgn::new(board).to_string();
+        assert!(written.contains("[Variant \"Horde\"]"));
+        assert!(!written.contains("[FEN"));
+        assert_eq!(Pgn::parse(&written).unwrap().board.perft(1), 8);
+    }
+
+    #[test]
+    fn test_parse_all_and_errors() {
+        let two = format!("{}\n{}", GAME, GAME.replace("Alice", "Dave"));
+        let games = Pgn::parse_all(&two).unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[1].header("White"), Some("Dave"));
+        assert!(Pgn::parse("1. e4 e5 2. Ke3 *").is_err());
+        assert!(Pgn::parse("").is_err());
+    }
+}
@@ -43,6 +43,55 @@ impl Piece {
             color: Color::White,
         }
     }
+    /// FEN letter, upper case for white
+    pub fn fen_char(&self) -> char {
+        match self.color {
+            Color::White => self.piece_type.letter(),
+            Color::Black => self.piece_type.letter().to_ascii_lowercase(),
+        }
+    }
+    pub fn from_fen_char(c: char) -> Option<Self> {
+        let piece_type = PieceType::from_letter(c.to_ascii_uppercase())?;
+        if c.is_ascii_uppercase() {
+            Some(Piece::white(piece_type))
+        } else {
+            Some(Piece::black(piece_type))
+        }
+    }
+}
+
+impl PieceType {
+    pub fn letter(&self) -> char {
+        match self {
+            PieceType::Empty => ' ',
+            PieceType::Pawn => 'P',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Rook => 'R',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+    pub fn from_letter(c: char) -> Option<PieceType> {
+        match c {
+            'P' => Some(PieceType::Pawn),
+            'N' => Some(PieceType::Knight),
+            'B' => Some(PieceType::Bishop),
+            'R' => Some(PieceType::Rook),
+            'Q' => Some(PieceType::Queen),
+            'K' => Some(PieceType::King),
+            _ => None,
+        }
+    }
+}
+
+impl Color {
+    pub fn opposite(&self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
 }
 
 impl std::fmt::Display for PieceType {
@@ -0,0 +1,515 @@
+use std::collections::{HashMap, HashSet};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use crate::{board::{Board, Outcome}, coords::MoveCoords, pieces::Color};
+
+/// Rating a new solver starts from
+const START_RATING: f64 = 1500.0;
+/// Puzzles within this many points of the solver's rating are picked first
+const RATING_WINDOW: u32 = 150;
+
+/// A tactic from the Lichess puzzle database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Puzzle {
+    pub id: String,
+    /// Position before the opponent's move which sets up the puzzle
+    pub fen: String,
+    /// The opponent's first move, then the solution and the opponent's replies in turn, in UCI
+    pub moves: Vec<String>,
+    pub rating: u32,
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    /// Reads a `PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,...` line
+    pub fn parse_csv(line: &str) -> Result<Puzzle, String> {
+        let fields = line.trim().split(',').collect::<Vec<_>>();
+        if fields.len() < 4 {
+            return Err(format!("Puzzle needs an id, FEN, moves and rating: {}", line));
+        }
+        let moves = fields[2].split_whitespace().map(|m| m.to_string()).collect::<Vec<_>>();
+        if moves.len() < 2 {
+            return Err(format!("Puzzle {} has no solution", fields[0]));
+        }
+        Ok(Puzzle {
+            id: fields[0].to_string(),
+            fen: fields[1].to_string(),
+            moves,
+            rating: fields[3].parse().map_err(|_| format!("Invalid rating for puzzle {}", fields[0]))?,
+            themes: fields.get(7).map(|themes| themes.split_whitespace().map(|t| t.to_string()).collect()).unwrap_or_default(),
+        })
+    }
+
+    /// Sets up the position after the opponent's first move, checking the whole solution is legal
+    pub fn start(&self) -> Result<PuzzleRun, String> {
+        let mut board = Board::from_fen(&self.fen).map_err(|e| format!("Invalid FEN in puzzle {}: {}", self.id, e))?;
+        let mut replay = board.clone();
+        for uci in &self.moves {
+            let mc = replay.parse_uci(uci).ok_or_else(|| format!("Illegal move {} in puzzle {}", uci, self.id))?;
+            replay.apply(&mc);
+        }
+        board.apply(&board.parse_uci(&self.moves[0]).expect("checked above"));
+        Ok(PuzzleRun { puzzle: self.clone(), board, next: 1, failed: false })
+    }
+}
+
+/// Puzzles packed tightly enough to hold the whole Lichess database, around a hundred bytes each:
+/// ids and FENs share one string, moves take 16 bits and themes are numbers for their names
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PuzzleSet {
+    entries: Vec<PackedPuzzle>,
+    /// Ids, each followed by its FEN
+    text: String,
+    moves: Vec<u16>,
+    theme_ids: Vec<u16>,
+    themes: Vec<String>,
+    theme_index: HashMap<String, u16>,
+}
+
+/// Where a puzzle's id, FEN, moves and themes start in a `PuzzleSet`, and how long each is
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PackedPuzzle {
+    text: usize,
+    moves: usize,
+    themes: usize,
+    id_len: u8,
+    fen_len: u8,
+    move_count: u8,
+    theme_count: u8,
+    rating: u32,
+}
+
+impl PuzzleSet {
+    /// Every puzzle in a CSV file, with or without the header line, and the number of lines skipped as unreadable
+    pub fn parse(text: &str) -> (PuzzleSet, usize) {
+        PuzzleSet::read(text.as_bytes()).expect("reading from memory")
+    }
+
+    /// Reads puzzles from `input` a line at a time, packing each as it comes, skipping and
+    /// counting lines which aren't puzzles
+    pub fn read(mut input: impl BufRead) -> std::io::Result<(PuzzleSet, usize)> {
+        let (mut puzzles, mut skipped) = (PuzzleSet::default(), 0);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if input.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let text = String::from_utf8_lossy(&line);
+            if text.trim().is_empty() || text.starts_with("PuzzleId,") {
+                continue;
+            }
+            if let Err(e) = Puzzle::parse_csv(&text).and_then(|puzzle| puzzles.push(&puzzle)) {
+                log::debug!("Skipping puzzle: {}", e);
+                skipped += 1;
+            }
+        }
+        Ok((puzzles, skipped))
+    }
+
+    /// Adds `puzzle`, which needs moves in plain UCI and fields short enough to pack
+    pub fn push(&mut self, puzzle: &Puzzle) -> Result<(), String> {
+        let moves = puzzle.moves.iter().map(|uci| pack_uci(uci)).collect::<Option<Vec<_>>>().ok_or_else(|| format!("Puzzle {} has a move which isn't UCI", puzzle.id))?;
+        let too_long = || format!("Puzzle {} is too long to store", puzzle.id);
+        let entry = PackedPuzzle {
+            text: self.text.len(),
+            moves: self.moves.len(),
+            themes: self.theme_ids.len(),
+            id_len: puzzle.id.len().try_into().map_err(|_| too_long())?,
+            fen_len: puzzle.fen.len().try_into().map_err(|_| too_long())?,
+            move_count: moves.len().try_into().map_err(|_| too_long())?,
+            theme_count: puzzle.themes.len().try_into().map_err(|_| too_long())?,
+            rating: puzzle.rating,
+        };
+        let mut theme_ids = Vec::with_capacity(puzzle.themes.len());
+        for theme in &puzzle.themes {
+            let id = match self.theme_index.get(theme) {
+                Some(id) => *id,
+                None => {
+                    let id = u16::try_from(self.themes.len()).map_err(|_| format!("Too many puzzle themes at {}", theme))?;
+                    self.themes.push(theme.clone());
+                    self.theme_index.insert(theme.clone(), id);
+                    id
+                }
+            };
+            theme_ids.push(id);
+        }
+        self.text.push_str(&puzzle.id);
+        self.text.push_str(&puzzle.fen);
+        self.moves.extend(moves);
+        self.theme_ids.extend(theme_ids);
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn id(&self, index: usize) -> &str {
+        let entry = &self.entries[index];
+        &self.text[entry.text..entry.text + entry.id_len as usize]
+    }
+
+    pub fn rating(&self, index: usize) -> u32 {
+        self.entries[index].rating
+    }
+
+    /// Puzzle `index` unpacked
+    pub fn get(&self, index: usize) -> Puzzle {
+        let entry = &self.entries[index];
+        let fen = entry.text + entry.id_len as usize;
+        Puzzle {
+            id: self.id(index).to_string(),
+            fen: self.text[fen..fen + entry.fen_len as usize].to_string(),
+            moves: self.moves[entry.moves..entry.moves + entry.move_count as usize].iter().map(|mv| unpack_uci(*mv)).collect(),
+            rating: entry.rating,
+            themes: self.theme_ids[entry.themes..entry.themes + entry.theme_count as usize].iter().map(|id| self.themes[*id as usize].clone()).collect(),
+        }
+    }
+}
+
+const PROMOTIONS: &[u8] = b"nbrqk";
+
+/// Packs a UCI move such as `e7e8q` as its from square, to square and promotion in 6, 6 and 3 bits
+fn pack_uci(uci: &str) -> Option<u16> {
+    let square = |file: u8, rank: u8| (file.is_ascii_lowercase() && file <= b'h' && (b'1'..=b'8').contains(&rank)).then(|| (rank - b'1') as u16 * 8 + (file - b'a') as u16);
+    match uci.as_bytes() {
+        [from_file, from_rank, to_file, to_rank, promotion @ ..] if promotion.len() <= 1 => {
+            let promotion = match promotion.first() {
+                None => 0,
+                Some(piece) => PROMOTIONS.iter().position(|p| p == piece)? as u16 + 1,
+            };
+            Some(square(*from_file, *from_rank)? << 9 | square(*to_file, *to_rank)? << 3 | promotion)
+        }
+        _ => None,
+    }
+}
+
+fn unpack_uci(packed: u16) -> String {
+    let square = |index: u16| format!("{}{}", (b'a' + (index % 8) as u8) as char, index / 8 + 1);
+    let mut uci = square(packed >> 9) + &square(packed >> 3 & 63);
+    if packed & 7 != 0 {
+        uci.push(PROMOTIONS[(packed & 7) as usize - 1] as char);
+    }
+    uci
+}
+
+/// What the solver's move did
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attempt {
+    /// Right so far, and the opponent answers with this move
+    Correct(MoveCoords),
+    Solved,
+    /// Not the solution; the move is not played
+    Wrong,
+}
+
+/// A puzzle being solved
+#[derive(Clone)]
+pub struct PuzzleRun {
+    pub puzzle: Puzzle,
+    pub board: Board,
+    /// Index in `puzzle.moves` of the next move to play
+    next: usize,
+    /// Whether a wrong move was tried
+    pub failed: bool,
+}
+
+impl PuzzleRun {
+    /// The side the solver plays
+    pub fn player(&self) -> Color {
+        if self.next % 2 == 1 {
+            self.board.turn
+        } else {
+            self.board.turn.opposite()
+        }
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.next >= self.puzzle.moves.len()
+    }
+
+    /// The move the solution expects next
+    pub fn hint(&self) -> Option<MoveCoords> {
+        self.board.parse_uci(self.puzzle.moves.get(self.next)?)
+    }
+
+    /// Checks the solver's move, playing it and the opponent's reply when it is right. Any
+    /// checkmate counts, as there may be more than one.
+    pub fn try_move(&mut self, mc: &MoveCoords) -> Attempt {
+        if self.is_solved() {
+            return Attempt::Wrong;
+        }
+        let mut after = self.board.clone();
+        after.apply(mc);
+        let mates = matches!(after.outcome(), Some(Outcome::Checkmate { .. }));
+        if self.hint() != Some(*mc) && !mates {
+            self.failed = true;
+            return Attempt::Wrong;
+        }
+        self.board = after;
+        self.next += 1;
+        if mates {
+            self.next = self.puzzle.moves.len();
+        }
+        match self.hint() {
+            Some(reply) => {
+                self.board.apply(&reply);
+                self.next += 1;
+                Attempt::Correct(reply)
+            }
+            None => Attempt::Solved,
+        }
+    }
+}
+
+/// The solver's rating and the puzzles already tried, kept in a small text file
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleRating {
+    pub rating: f64,
+    /// Puzzle ids with whether they were solved, oldest first
+    pub history: Vec<(String, bool)>,
+    /// Ids in `history`, for checking puzzles against it quickly
+    tried: HashSet<String>,
+}
+
+impl Default for PuzzleRating {
+    fn default() -> Self {
+        PuzzleRating { rating: START_RATING, history: vec![], tried: HashSet::new() }
+    }
+}
+
+impl PuzzleRating {
+    /// Reads `rating 1500` followed by `<id> solved` or `<id> failed` lines, starting afresh when the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PuzzleRating::default()),
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        let mut rating = PuzzleRating::default();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["rating", value] => rating.rating = value.parse().map_err(|_| format!("Invalid rating in {}", path.display()))?,
+                [id, result @ ("solved" | "failed")] => rating.push(id, result == "solved"),
+                _ => return Err(format!("Unexpected line in {}: {}", path.display(), line)),
+            }
+        }
+        Ok(rating)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let mut text = format!("rating {:.1}\n", self.rating);
+        for (id, solved) in &self.history {
+            text += &format!("{} {}\n", id, if *solved { "solved" } else { "failed" });
+        }
+        std::fs::write(path, text).map_err(|e| format!("Could not save {}: {}", path.display(), e))
+    }
+
+    pub fn has_tried(&self, id: &str) -> bool {
+        self.tried.contains(id)
+    }
+
+    fn push(&mut self, id: &str, solved: bool) {
+        self.history.push((id.to_string(), solved));
+        self.tried.insert(id.to_string());
+    }
+
+    /// Elo update against the puzzle's rating, moving faster over the first few dozen puzzles
+    pub fn record(&mut self, puzzle: &Puzzle, solved: bool) {
+        let expected = 1.0 / (1.0 + 10f64.powf((puzzle.rating as f64 - self.rating) / 400.0));
+        let k = if self.history.len() < 30 { 40.0 } else { 20.0 };
+        self.rating += k * (if solved { 1.0 } else { 0.0 } - expected);
+        self.push(&puzzle.id, solved);
+    }
+}
+
+/// Serves puzzles near the solver's rating and keeps the rating up to date
+pub struct Trainer {
+    puzzles: PuzzleSet,
+    /// Puzzles found not to check out when they came up, never served again
+    broken: HashSet<usize>,
+    pub rating: PuzzleRating,
+    /// Where the rating is saved after each puzzle, if anywhere
+    path: Option<PathBuf>,
+    pub current: Option<PuzzleRun>,
+}
+
+impl Trainer {
+    pub fn new(puzzles: PuzzleSet, rating: PuzzleRating, path: Option<PathBuf>) -> Self {
+        Trainer { puzzles, broken: HashSet::new(), rating, path, current: None }
+    }
+
+    /// Loads the puzzles in a Lichess CSV file, and the rating saved at `rating_path`
+    pub fn open(csv: &Path, rating_path: PathBuf) -> Result<Self, String> {
+        let file = std::fs::File::open(csv).map_err(|e| format!("Could not read {}: {}", csv.display(), e))?;
+        let (puzzles, skipped) = PuzzleSet::read(std::io::BufReader::new(file)).map_err(|e| format!("Could not read {}: {}", csv.display(), e))?;
+        if skipped > 0 {
+            log::warn!("Skipped {} unreadable lines in {}", skipped, csv.display());
+        }
+        Ok(Trainer::new(puzzles, PuzzleRating::load(&rating_path)?, Some(rating_path)))
+    }
+
+    /// Starts a random untried puzzle close to the solver's rating, or the closest one there is,
+    /// dropping puzzles whose moves don't check out
+    pub fn next_puzzle(&mut self) -> Option<&PuzzleRun> {
+        let rating = self.rating.rating.max(0.0) as u32;
+        loop {
+            // One pass picks uniformly among the near puzzles while tracking the closest
+            let (mut near, mut pick, mut closest) = (0, None, None::<(u32, usize)>);
+            for i in (0..self.puzzles.len()).filter(|i| !self.broken.contains(i) && !self.rating.has_tried(self.puzzles.id(*i))) {
+                let distance = self.puzzles.rating(i).abs_diff(rating);
+                if distance <= RATING_WINDOW {
+                    near += 1;
+                    if fastrand::usize(..near) == 0 {
+                        pick = Some(i);
+                    }
+                }
+                if closest.is_none_or(|(best, _)| distance < best) {
+                    closest = Some((distance, i));
+                }
+            }
+            let Some(index) = pick.or(closest.map(|(_, i)| i)) else {
+                self.current = None;
+                return None;
+            };
+            match self.puzzles.get(index).start() {
+                Ok(run) => {
+                    self.current = Some(run);
+                    return self.current.as_ref();
+                }
+                Err(e) => {
+                    log::warn!("{}", e);
+                    self.broken.insert(index);
+                }
+            }
+        }
+    }
+
+    /// Plays the solver's move in the current puzzle, rating the puzzle as failed on the first
+    /// wrong move or as solved when finished without one
+    pub fn play(&mut self, mc: &MoveCoords) -> Option<Attempt> {
+        let run = self.current.as_mut()?;
+        let failed = run.failed;
+        let attempt = run.try_move(mc);
+        let rated = match attempt {
+            Attempt::Wrong if !failed => Some(false),
+            Attempt::Solved if !failed => Some(true),
+            _ => None,
+        };
+        if let Some(solved) = rated {
+            self.rating.record(&run.puzzle, solved);
+            if let Some(path) = &self.path {
+                if let Err(e) = self.rating.save(path) {
+                    log::warn!("{}", e);
+                }
+            }
+        }
+        Some(attempt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+00008,r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24,f2g3 e6e7 b2b1 b3c1 b1c1 h6c1,1807,75,95,8178,crushing hangingPiece long middlegame,https://lichess.org/787zsVup/black#48,
+0000D,5rk1/1p3ppp/pq3b2/8/8/1P1Q1N2/P4PPP/3R2K1 w - - 2 27,d3d6 f8d8 d6d8 f6d8,1426,74,96,37862,advantage endgame short,https://lichess.org/F8M8OS71#53,
+";
+
+    fn uci(run: &PuzzleRun, text: &str) -> MoveCoords {
+        run.board.parse_uci(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_solve() {
+        let (puzzles, skipped) = PuzzleSet::parse(CSV);
+        assert_eq!((puzzles.len(), skipped), (2, 0));
+        let puzzle = puzzles.get(0);
+        assert_eq!(puzzle, Puzzle::parse_csv(CSV.lines().nth(1).unwrap()).unwrap());
+        assert_eq!(puzzle.rating, 1807);
+        assert_eq!(puzzle.themes, vec!["crushing", "hangingPiece", "long", "middlegame"]);
+        let mut run = puzzle.start().unwrap();
+        assert_eq!(run.player(), Color::White);
+        assert_eq!(run.try_move(&uci(&run, "h6h7")), Attempt::Wrong);
+        assert!(run.failed);
+        for (mine, reply) in [("e6e7", "b2b1"), ("b3c1", "b1c1")] {
+            match run.try_move(&uci(&run, mine)) {
+                Attempt::Correct(mc) => assert_eq!(mc.uci(), reply),
+                attempt => panic!("{:?} after {}", attempt, mine),
+            }
+        }
+        assert_eq!(run.try_move(&uci(&run, "h6c1")), Attempt::Solved);
+        assert!(run.is_solved());
+        assert!(Puzzle::parse_csv("x,8/8/8/8/8/8/8/K6k w - - 0 1,a1a2,1500").is_err());
+        assert!(Puzzle::parse_csv("x,8/8/8/8/8/8/8/K6k w - - 0 1,a1a2 a1a3,1500").unwrap().start().is_err());
+    }
+
+    #[test]
+    fn test_packing() {
+        for uci in ["a1a2", "h8h1", "e7e8q", "g2g1n", "b7a8k"] {
+            assert_eq!(unpack_uci(pack_uci(uci).unwrap()), uci);
+        }
+        for bad in ["", "e2", "e2e9", "i1a1", "e7e8x", "e7e8qq", "N@f3"] {
+            assert_eq!(pack_uci(bad), None);
+        }
+        let (puzzles, skipped) = PuzzleSet::parse("x,8/8/8/8/8/8/8/K6k w - - 0 1,a1a2 P@a3,1500,,,,\n");
+        assert_eq!((puzzles.len(), skipped), (0, 1));
+    }
+
+    #[test]
+    fn test_skip_bad_lines() {
+        let csv = format!("{}broken,line\nbad,8/8/8/8/8/8/8/K6k w - - 0 1,a1a2 a1a3,1500,,,,\n", CSV);
+        let (puzzles, skipped) = PuzzleSet::parse(&csv);
+        assert_eq!((puzzles.len(), skipped), (3, 1));
+        // The puzzle with illegal moves is dropped when it comes up
+        let mut trainer = Trainer::new(puzzles, PuzzleRating::default(), None);
+        let mut served = vec![];
+        while let Some(run) = trainer.next_puzzle() {
+            served.push(run.puzzle.id.clone());
+            let id = run.puzzle.id.clone();
+            trainer.rating.push(&id, true);
+        }
+        served.sort();
+        assert_eq!(served, vec!["00008", "0000D"]);
+    }
+
+    #[test]
+    fn test_trainer_rating() {
+        let path = std::env::temp_dir().join(format!("chess-puzzle-rating-{}.txt", std::process::id()));
+        let mut trainer = Trainer::new(PuzzleSet::parse(CSV).0, PuzzleRating::load(&path).unwrap(), Some(path.clone()));
+        // The puzzle nearer 1500 comes first
+        let run = trainer.next_puzzle().unwrap();
+        assert_eq!(run.puzzle.id, "0000D");
+        assert_eq!(run.player(), Color::Black);
+        let (wrong, right) = (uci(run, "b6c7"), uci(run, "f8d8"));
+        assert_eq!(trainer.play(&wrong), Some(Attempt::Wrong));
+        let failed = trainer.rating.rating;
+        assert!(failed < START_RATING);
+        // Solving it afterwards doesn't count
+        assert!(matches!(trainer.play(&right), Some(Attempt::Correct(_))));
+        let last = uci(trainer.current.as_ref().unwrap(), "f6d8");
+        assert_eq!(trainer.play(&last), Some(Attempt::Solved));
+        assert_eq!(trainer.rating.rating, failed);
+
+        let run = trainer.next_puzzle().unwrap();
+        assert_eq!(run.puzzle.id, "00008");
+        for text in ["e6e7", "b3c1", "h6c1"] {
+            let mc = uci(trainer.current.as_ref().unwrap(), text);
+            trainer.play(&mc);
+        }
+        assert!(trainer.rating.rating > failed + 30.0);
+        assert!(trainer.next_puzzle().is_none());
+        let saved = PuzzleRating::load(&path).unwrap();
+        assert_eq!(saved.history, vec![("0000D".to_string(), false), ("00008".to_string(), true)]);
+        assert!((saved.rating - trainer.rating.rating).abs() < 0.1);
+        std::fs::remove_file(path).unwrap();
+    }
+}
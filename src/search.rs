@@ -0,0 +1,353 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::{Board, Outcome},
+    chess_move::{Move, MoveList},
+    coords::MoveCoords,
+    eval::{evaluate, piece_value},
+    syzygy::{Tablebase, Wdl},
+};
+
+/// Score of being checkmated at the root, mates further away score closer to zero
+pub const MATE: i32 = 100_000;
+const INFINITY: i32 = MATE + 1;
+/// Score of a tablebase win at the root, below any mate score
+pub const TB_WIN: i32 = MATE - 2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub depth: u32,
+    /// Stops deepening once this much time has passed, keeping the last finished depth
+    pub time: Option<Duration>,
+}
+
+impl Limits {
+    pub fn depth(depth: u32) -> Self {
+        Limits { depth, time: None }
+    }
+    pub fn time(time: Duration) -> Self {
+        Limits { depth: 64, time: Some(time) }
+    }
+    /// Searches until told to stop
+    pub fn infinite() -> Self {
+        Limits { depth: 64, time: None }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub best_move: Option<MoveCoords>,
+    /// Centipawns for the side to move, or a mate score
+    pub score: i32,
+    pub depth: u32,
+    pub nodes: u64,
+    /// Principal variation, starting with the best move
+    pub pv: Vec<MoveCoords>,
+    /// Positions looked up in the tablebases
+    pub tb_hits: u64,
+}
+
+/// Moves until mate for a mate score, negative when the side to move is getting mated
+pub fn mate_in(score: i32) -> Option<i32> {
+    if score.abs() < MATE - 1000 {
+        return None;
+    }
+    let plies = MATE - score.abs();
+    Some(if score > 0 { (plies + 1) / 2 } else { -(plies + 1) / 2 })
+}
+
+/// `+0.35` style pawns, or `#3` / `#-2` for mates
+pub fn format_score(score: i32) -> String {
+    match mate_in(score) {
+        Some(moves) => format!("#{}", moves),
+        None => format!("{:+.2}", score as f64 / 100.0),
+    }
+}
+
+struct Searcher<'a> {
+    nodes: u64,
+    deadline: Option<Instant>,
+    stopped: bool,
+    /// Set from outside to end the search early
+    stop: Option<&'a AtomicBool>,
+    /// Stopping waits until the first depth is done, so there's always a move
+    interruptible: bool,
+    tablebase: Option<&'a Tablebase>,
+    tb_hits: u64,
+    /// Moves at the root already taken by better lines
+    excluded: Vec<Move>,
+}
+
+impl Searcher<'_> {
+    fn out_of_time(&mut self) -> bool {
+        if self.interruptible && !self.stopped && self.nodes.is_multiple_of(256) {
+            self.stopped = self.deadline.is_some_and(|deadline| Instant::now() >= deadline) || self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed));
+        }
+        self.stopped
+    }
+
+    /// Negamax alpha-beta, filling `pv` with the best line found
+    fn alpha_beta(&mut self, board: &Board, depth: u32, ply: i32, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        self.nodes += 1;
+        if self.out_of_time() {
+            return 0;
+        }
+        if board.halfmove_clock >= 100 {
+            return 0;
+        }
+        if let Some(outcome) = board.variant.outcome(board) {
+            return outcome_score(outcome, board, ply);
+        }
+        // Right after a capture or pawn move the tablebase result is exact
+        if let Some(wdl) = self.probe(board, ply) {
+            return wdl;
+        }
+        let mut moves = MoveList::new();
+        board.generate_legal_moves(&mut moves);
+        if moves.is_empty() {
+            return outcome_score(board.variant.no_moves(board), board, ply);
+        }
+        if depth == 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+        order_moves(board, &mut moves, pv.first());
+
+        let mut line = vec![];
+        for mv in moves.iter() {
+            if ply == 0 && self.excluded.contains(mv) {
+                continue;
+            }
+            let mut after = board.clone();
+            after.make_move(*mv);
+            line.clear();
+            let score = -self.alpha_beta(&after, depth - 1, ply + 1, -beta, -alpha, &mut line);
+            if self.stopped {
+                return 0;
+            }
+            if score > alpha {
+                alpha = score;
+                pv.clear();
+                pv.push(*mv);
+                pv.extend_from_slice(&line);
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        alpha
+    }
+
+    /// Tablebase score of a position reached by a capture or pawn move, wins scoring lower the later they're found
+    fn probe(&mut self, board: &Board, ply: i32) -> Option<i32> {
+        let tablebase = self.tablebase?;
+        if ply == 0 || board.halfmove_clock != 0 || !tablebase.covers(board) {
+            return None;
+        }
+        let wdl = tablebase.probe_wdl(board)?;
+        self.tb_hits += 1;
+        Some(tb_score(wdl, ply))
+    }
+
+    /// Resolves captures so the static evaluation isn't taken in the middle of an exchange
+    fn quiescence(&mut self, board: &Board, ply: i32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+        // Variants can end the game on a capture, as when a king explodes
+        if let Some(outcome) = board.variant.outcome(board) {
+            return outcome_score(outcome, board, ply);
+        }
+        let stand_pat = evaluate(board);
+        if stand_pat >= beta {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+        let mut captures = MoveList::new();
+        board.generate_legal_moves(&mut captures);
+        captures.retain(|mv| mv.is_capture() || mv.promotion().is_some());
+        order_moves(board, &mut captures, None);
+        for mv in captures.iter() {
+            let mut after = board.clone();
+            after.make_move(*mv);
+            let score = -self.quiescence(&after, ply + 1, -beta, -alpha);
+            if self.out_of_time() {
+                return 0;
+            }
+            if score >= beta {
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+        alpha
+    }
+}
+
+/// Puts the previous best move first, then promotions and captures of valuable pieces by cheap ones
+fn order_moves(board: &Board, moves: &mut [Move], best: Option<&Move>) {
+    moves.sort_by_key(|mv| {
+        if Some(mv) == best {
+            return i32::MIN;
+        }
+        let promotion = mv.promotion().map(piece_value).unwrap_or(0);
+        let capture = if mv.is_capture() { 10 * piece_value(board.get_piece(mv.to()).piece_type) - piece_value(board.get_piece(mv.from()).piece_type) } else { 0 };
+        -(promotion + capture)
+    });
+}
+
+/// Expands a line of moves played from `board` into `MoveCoords`
+fn line_coords(board: &Board, line: &[Move]) -> Vec<MoveCoords> {
+    let mut board = board.clone();
+    line.iter()
+        .map(|mv| {
+            let mc = board.decode_move(*mv);
+            board.make_move(*mv);
+            mc
+        })
+        .collect()
+}
+
+/// Scores the game ending `ply` plies from the root for the side to move, like a mate
+fn outcome_score(outcome: Outcome, board: &Board, ply: i32) -> i32 {
+    match outcome.winner() {
+        Some(winner) if winner == board.turn => MATE - ply,
+        Some(_) => -MATE + ply,
+        None => 0,
+    }
+}
+
+/// Scores a tablebase result `ply` plies from the root, keeping the 50 move rule draws as draws
+fn tb_score(wdl: Wdl, ply: i32) -> i32 {
+    match wdl {
+        Wdl::Win => TB_WIN - ply,
+        Wdl::Loss => -TB_WIN + ply,
+        _ => 0,
+    }
+}
+
+/// Iterative deepening search of `board` within `limits`
+pub fn search(board: &Board, limits: Limits) -> SearchResult {
+    search_with(board, limits, None, None)
+}
+
+/// Like `search`, but playing the tablebase move at the root and scoring positions
+/// in the tree from the tablebases where they cover them. Setting `stop` ends the
+/// search as running out of time would
+pub fn search_with(board: &Board, limits: Limits, tablebase: Option<&Tablebase>, stop: Option<&AtomicBool>) -> SearchResult {
+    search_lines(board, limits, 1, tablebase, stop, |_| ()).into_iter().next().unwrap_or_default()
+}
+
+/// Like `search_with`, but finding the best `lines` moves, each with its own score and line,
+/// and passing them to `report` best first after every depth. Each line leaves out the first
+/// moves of those before it, and only a single line is taken from the tablebases at the root
+pub fn search_lines(board: &Board, limits: Limits, lines: usize, tablebase: Option<&Tablebase>, stop: Option<&AtomicBool>, mut report: impl FnMut(&[SearchResult])) -> Vec<SearchResult> {
+    if lines <= 1 {
+        if let Some((mc, probe)) = tablebase.and_then(|tablebase| tablebase.best_move(board)) {
+            let result = SearchResult { best_move: Some(mc), score: tb_score(probe.wdl, 0), depth: 1, nodes: 1, pv: vec![mc], tb_hits: 1 };
+            report(std::slice::from_ref(&result));
+            return vec![result];
+        }
+    }
+    let mut searcher = Searcher { nodes: 0, deadline: limits.time.map(|time| Instant::now() + time), stopped: false, stop, interruptible: false, tablebase, tb_hits: 0, excluded: vec![] };
+    // Searching a copy without the game history keeps each clone in the tree free of allocations
+    let position = board.position();
+    let mut root = MoveList::new();
+    position.generate_legal_moves(&mut root);
+    let mut pvs = vec![vec![]; lines.clamp(1, root.len().max(1))];
+    let mut results: Vec<SearchResult> = vec![];
+    for depth in 1..=limits.depth.max(1) {
+        searcher.excluded.clear();
+        let mut found = vec![];
+        for pv in pvs.iter_mut() {
+            let score = searcher.alpha_beta(&position, depth, 0, -INFINITY, INFINITY, pv);
+            if searcher.stopped {
+                break;
+            }
+            searcher.excluded.extend(pv.first());
+            let pv = line_coords(&position, pv);
+            found.push(SearchResult { best_move: pv.first().copied(), score, depth, nodes: searcher.nodes, pv, tb_hits: searcher.tb_hits });
+        }
+        // A depth cut short keeps the lines of the one before
+        if searcher.stopped && !results.is_empty() {
+            break;
+        }
+        results = found;
+        report(&results);
+        if searcher.stopped || results.iter().all(|result| mate_in(result.score).is_some()) {
+            break;
+        }
+        searcher.interruptible = true;
+    }
+    for result in results.iter_mut() {
+        result.nodes = searcher.nodes;
+        result.tb_hits = searcher.tb_hits;
+    }
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mate_in_one() {
+        let board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let result = search(&board, Limits::depth(3));
+        assert_eq!(result.best_move.unwrap().uci(), "a1a8");
+        assert_eq!(mate_in(result.score), Some(1));
+        assert_eq!(format_score(result.score), "#1");
+    }
+
+    #[test]
+    fn test_wins_material() {
+        // The queen on d5 is hanging to the knight
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 4").unwrap();
+        let result = search(&board, Limits::depth(2));
+        assert_eq!(result.best_move.unwrap().uci(), "c3d5");
+        assert!(result.score > 500);
+        assert_eq!(result.pv.first(), result.best_move.as_ref());
+    }
+
+    #[test]
+    fn test_tablebase() {
+        let (dir, _) = crate::syzygy::tests::write_tables("search");
+        let tablebase = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        let won = Board::from_fen("8/8/8/8/8/2k5/8/K6Q w - - 0 1").unwrap();
+        let result = search_with(&won, Limits::depth(3), Some(&tablebase), None);
+        assert_eq!((result.score, result.pv.len(), result.tb_hits), (TB_WIN, 1, 1));
+        // Taking the rook reaches a won pawn ending
+        let capture = Board::from_fen("8/8/8/8/8/2k4r/6P1/K7 w - - 0 1").unwrap();
+        let result = search_with(&capture, Limits::depth(2), Some(&tablebase), None);
+        assert_eq!(result.best_move.unwrap().uci(), "g2h3");
+        assert_eq!(result.score, TB_WIN - 1);
+        assert!(result.tb_hits > 0);
+        assert_eq!(search(&capture, Limits::depth(2)).tb_hits, 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_search_lines() {
+        // Taking the queen is best, and the other lines start with other moves
+        let board = Board::from_fen("rnb1kbnr/pppp1ppp/8/3q4/8/2N5/PPPP1PPP/R1BQKBNR w KQkq - 0 4").unwrap();
+        let mut reported = vec![];
+        let lines = search_lines(&board, Limits::depth(2), 3, None, None, |lines| reported.push(lines.len()));
+        assert_eq!(reported, vec![3, 3]);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].best_move.unwrap().uci(), "c3d5");
+        assert!(lines.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        let firsts = lines.iter().map(|line| line.best_move.unwrap()).collect::<Vec<_>>();
+        assert!(!firsts[1..].contains(&firsts[0]) && firsts[1] != firsts[2]);
+        assert_eq!(lines[0].score, search(&board, Limits::depth(2)).score);
+        // No more lines than moves
+        let board = Board::from_fen("7k/8/8/8/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(search_lines(&board, Limits::depth(1), 10, None, None, |_| ()).len(), 3);
+    }
+
+    #[test]
+    fn test_no_moves() {
+        let stalemate = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        let result = search(&stalemate, Limits::depth(2));
+        assert!(result.best_move.is_none());
+        assert_eq!(result.score, 0);
+        assert_eq!(format_score(35), "+0.35");
+        assert_eq!(mate_in(-MATE + 2), Some(-1));
+    }
+}
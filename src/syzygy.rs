@@ -0,0 +1,1235 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::{
+    board::Board,
+    chess_move::MoveList,
+    coords::{Coords, MoveCoords},
+    pieces::{Color, Piece, PieceType},
+};
+
+/// Most pieces, kings included, of any Syzygy table
+pub const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Table flags
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// Flags of each sub-table
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+/// Win, draw or loss for the side to move, with the 50 move rule taken into account
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss,
+    /// Lost, but saved by the 50 move rule
+    BlessedLoss,
+    Draw,
+    /// Won, but not before the 50 move rule draws it
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Wdl {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    /// -2 for a loss up to 2 for a win
+    pub fn value(self) -> i32 {
+        self as i32 - 2
+    }
+
+    fn signum(self) -> i32 {
+        self.value().signum()
+    }
+}
+
+impl std::ops::Neg for Wdl {
+    type Output = Wdl;
+
+    fn neg(self) -> Wdl {
+        Wdl::from_value(-self.value())
+    }
+}
+
+impl std::fmt::Display for Wdl {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            Wdl::Loss => "loss",
+            Wdl::BlessedLoss => "blessed loss",
+            Wdl::Draw => "draw",
+            Wdl::CursedWin => "cursed win",
+            Wdl::Win => "win",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// What the tablebases know about a position
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TablebaseProbe {
+    pub wdl: Wdl,
+    /// Plies until the next capture or pawn move with best play, negative when losing and 0 for draws.
+    /// `None` without the DTZ table for the position
+    pub dtz: Option<i32>,
+}
+
+#[derive(Debug)]
+pub enum TablebaseError {
+    Io(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for TablebaseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TablebaseError::Io(path, e) => write!(f, "could not read tablebase directory {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for TablebaseError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+enum Kind {
+    Wdl,
+    Dtz,
+}
+
+impl Kind {
+    fn extension(self) -> &'static str {
+        match self {
+            Kind::Wdl => "rtbw",
+            Kind::Dtz => "rtbz",
+        }
+    }
+}
+
+/// Why a table lookup gave no value
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Fail {
+    /// No table for the material, or a damaged one
+    Missing,
+    /// DTZ tables hold one side to move only, and this isn't it
+    ChangeStm,
+}
+
+type Tables = HashMap<(String, Kind), Option<Arc<Table>>>;
+
+/// Syzygy WDL and DTZ tables found in local directories, each file opened on first use
+pub struct Tablebase {
+    /// Paths of the table files by name, e.g. `KQvKR.rtbw`
+    files: HashMap<String, PathBuf>,
+    max_pieces: usize,
+    /// Tables opened so far, `None` for files that couldn't be read
+    tables: Mutex<Tables>,
+}
+
+impl Tablebase {
+    /// Finds the tables in `paths`, a list of directories separated like `PATH`
+    pub fn open(paths: &str) -> Result<Tablebase, TablebaseError> {
+        let mut files = HashMap::new();
+        for dir in std::env::split_paths(paths).filter(|dir| !dir.as_os_str().is_empty()) {
+            let entries = std::fs::read_dir(&dir).map_err(|e| TablebaseError::Io(dir.clone(), e))?;
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.ends_with(".rtbw") || name.ends_with(".rtbz") {
+                    files.entry(name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+        let max_pieces = files
+            .keys()
+            .filter(|name| name.ends_with(".rtbw"))
+            .map(|name| name.len() - ".rtbw".len() - 1)
+            .filter(|pieces| *pieces <= MAX_PIECES)
+            .max()
+            .unwrap_or(0);
+        log::debug!("Found {} tablebase files, up to {} pieces", files.len(), max_pieces);
+        Ok(Tablebase { files, max_pieces, tables: Mutex::new(HashMap::new()) })
+    }
+
+    /// Number of table files found
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Most pieces of any WDL table found, 0 without tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the position could be in the tables: standard rules, no castling rights and few enough pieces
+    pub fn covers(&self, board: &Board) -> bool {
+        let castling = &board.castling;
+        if !board.is_standard() || castling.white_king_side || castling.white_queen_side || castling.black_king_side || castling.black_queen_side {
+            return false;
+        }
+        let pieces = board.state.iter().flatten().filter(|piece| piece.piece_type != PieceType::Empty).count();
+        pieces <= self.max_pieces
+    }
+
+    /// Win, draw or loss for the side to move
+    pub fn probe_wdl(&self, board: &Board) -> Option<Wdl> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.search(&board.position(), false).ok().map(|(wdl, _)| wdl)
+    }
+
+    /// Distance to zeroing the 50 move counter in plies, see `TablebaseProbe::dtz`
+    pub fn probe_dtz(&self, board: &Board) -> Option<i32> {
+        if !self.covers(board) {
+            return None;
+        }
+        self.dtz(&board.position()).ok()
+    }
+
+    /// The move keeping the best result, winning fastest or losing slowest, with the probe of the position
+    pub fn best_move(&self, board: &Board) -> Option<(MoveCoords, TablebaseProbe)> {
+        if !self.covers(board) {
+            return None;
+        }
+        let board = board.position();
+        let mut best: Option<(MoveCoords, Wdl, i32)> = None;
+        for mc in board.legal_moves() {
+            let mut after = board.clone();
+            after.apply(&mc);
+            let wdl = -self.search(&after, false).ok()?.0;
+            let zeroing = mc.takes || mc.piece.piece_type == PieceType::Pawn;
+            let dtz = if after.is_checkmate() {
+                1
+            } else if zeroing {
+                dtz_before_zeroing(wdl)
+            } else {
+                let dtz = -self.dtz(&after).ok()?;
+                dtz + dtz.signum()
+            };
+            // Among moves with the same result, win in the fewest plies and lose in the most, so the lowest DTZ either way
+            let better = match best {
+                None => true,
+                Some((_, best_wdl, best_dtz)) => wdl > best_wdl || (wdl == best_wdl && wdl != Wdl::Draw && dtz < best_dtz),
+            };
+            if better {
+                best = Some((mc, wdl, dtz));
+            }
+        }
+        let (mc, _, _) = best?;
+        board.probe_tablebase(self).map(|probe| (mc, probe))
+    }
+
+    fn table(&self, name: &str, kind: Kind) -> Option<Arc<Table>> {
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        tables
+            .entry((name.to_string(), kind))
+            .or_insert_with(|| {
+                let path = self.files.get(&format!("{}.{}", name, kind.extension()))?;
+                let table = Table::open(path, name, kind);
+                if table.is_none() {
+                    log::warn!("Could not read tablebase file {}", path.display());
+                }
+                table.map(Arc::new)
+            })
+            .clone()
+    }
+
+    /// Looks the position up in its table, which for DTZ needs the result already known as `wdl`
+    fn probe_table(&self, board: &Board, kind: Kind, wdl: Wdl) -> Result<i32, Fail> {
+        let (white, black) = (material(board, Color::White), material(board, Color::Black));
+        if white.len() + black.len() == 2 {
+            return Ok(0);
+        }
+        // Files are only named with the stronger side first
+        let (table, black_stronger) = match self.table(&format!("{}v{}", white, black), kind) {
+            Some(table) => (table, false),
+            None => (self.table(&format!("{}v{}", black, white), kind).ok_or(Fail::Missing)?, true),
+        };
+        table.probe(board, black_stronger, wdl)
+    }
+
+    /// The result of the position, searching captures first as the tables don't cover en passant and may be
+    /// wrong where capturing is best. Also gives whether the best move captures, or pushes a pawn with `zeroing`
+    fn search(&self, board: &Board, zeroing: bool) -> Result<(Wdl, bool), Fail> {
+        let mut moves = MoveList::new();
+        board.generate_legal_moves(&mut moves);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in moves.iter() {
+            if !mv.is_capture() && (!zeroing || board.get_piece(mv.from()).piece_type != PieceType::Pawn) {
+                continue;
+            }
+            searched += 1;
+            let mut after = board.clone();
+            after.make_move(*mv);
+            let wdl = -self.search(&after, false)?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Ok((wdl, true));
+                }
+            }
+        }
+        // With every move searched the table isn't needed, and may be wrong because of en passant
+        let all_searched = searched > 0 && searched == moves.len();
+        let wdl = if all_searched { best } else { Wdl::from_value(self.probe_table(board, Kind::Wdl, Wdl::Draw)?) };
+        if best >= wdl {
+            // The table only stores "don't care" when a capture wins
+            return Ok((best, best > Wdl::Draw || all_searched));
+        }
+        Ok((wdl, false))
+    }
+
+    fn dtz(&self, board: &Board) -> Result<i32, Fail> {
+        let (wdl, zeroing_best) = self.search(board, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if zeroing_best {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+        match self.probe_table(board, Kind::Dtz, wdl) {
+            Ok(dtz) => {
+                let cursed = matches!(wdl, Wdl::BlessedLoss | Wdl::CursedWin);
+                return Ok((dtz + if cursed { 100 } else { 0 }) * wdl.signum());
+            }
+            Err(Fail::Missing) => return Err(Fail::Missing),
+            Err(Fail::ChangeStm) => (),
+        }
+        // The table holds the other side to move, so look one ply ahead for the quickest win
+        let mut moves = MoveList::new();
+        board.generate_legal_moves(&mut moves);
+        let mut min_dtz = i32::MAX;
+        for mv in moves.iter() {
+            let zeroing = mv.is_capture() || board.get_piece(mv.from()).piece_type == PieceType::Pawn;
+            let mut after = board.clone();
+            after.make_move(*mv);
+            // For zeroing moves the sign of the result is all that's needed
+            let mut dtz = if zeroing { -dtz_before_zeroing(self.search(&after, false)?.0) } else { -self.dtz(&after)? };
+            if dtz == 1 && after.is_checkmate() {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        // No legal moves means mate
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+}
+
+impl Board {
+    /// Result and distance to zeroing from the tablebases, `None` when the position isn't covered by them
+    pub fn probe_tablebase(&self, tablebase: &Tablebase) -> Option<TablebaseProbe> {
+        let wdl = tablebase.probe_wdl(self)?;
+        Some(TablebaseProbe { wdl, dtz: tablebase.probe_dtz(self) })
+    }
+}
+
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+/// Table file name part for one side, pieces in the order `KQRBNP`
+fn material(board: &Board, color: Color) -> String {
+    use PieceType::*;
+    let mut text = String::new();
+    for piece_type in [King, Queen, Rook, Bishop, Knight, Pawn] {
+        for piece in board.state.iter().flatten() {
+            if piece.piece_type == piece_type && piece.color == color {
+                text.push(piece_type.letter());
+            }
+        }
+    }
+    text
+}
+
+/// Piece code used in the tables: pawn 1 to king 6, plus 8 for black
+fn piece_code(piece: Piece) -> u8 {
+    piece.piece_type as u8 | if piece.color == Color::Black { 8 } else { 0 }
+}
+
+// Squares in the tables count from a1 = 0 to h8 = 63
+fn square(coords: Coords) -> u8 {
+    (7 - coords.y) * 8 + coords.x
+}
+
+fn file_of(square: u8) -> u8 {
+    square & 7
+}
+
+fn rank_of(square: u8) -> u8 {
+    square >> 3
+}
+
+/// Distance above the a1-h8 diagonal, negative below it
+fn off_diagonal(square: u8) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+/// Index tables for encoding positions, as the table generator builds them
+struct Encoding {
+    binomial: [[u64; 64]; MAX_PIECES],
+    /// Squares a2 to h7 numbered so the leading pawn has the highest
+    map_pawns: [u64; 64],
+    lead_pawn_idx: [[u64; 64]; MAX_PIECES],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+    /// Squares below the a1-h8 diagonal numbered 0 to 27
+    map_b1h1h7: [u64; 64],
+    /// The a1-d1-d4 triangle numbered 0 to 9, diagonal squares last
+    map_a1d1d4: [u64; 64],
+    /// The 462 placements of two kings with the first in the a1-d1-d4 triangle
+    map_kk: [[u64; 64]; 10],
+}
+
+fn encoding() -> &'static Encoding {
+    static ENCODING: OnceLock<Encoding> = OnceLock::new();
+    ENCODING.get_or_init(|| {
+        let mut e = Encoding {
+            binomial: [[0; 64]; MAX_PIECES],
+            map_pawns: [0; 64],
+            lead_pawn_idx: [[0; 64]; MAX_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_PIECES],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+        };
+        let mut code = 0;
+        for s in 0..64 {
+            if off_diagonal(s) < 0 {
+                e.map_b1h1h7[s as usize] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = vec![];
+        code = 0;
+        for s in 0..=27 {
+            if off_diagonal(s) < 0 && file_of(s) <= 3 {
+                e.map_a1d1d4[s as usize] = code;
+                code += 1;
+            } else if off_diagonal(s) == 0 && file_of(s) <= 3 {
+                diagonal.push(s);
+            }
+        }
+        for s in diagonal {
+            e.map_a1d1d4[s as usize] = code;
+            code += 1;
+        }
+
+        let mut both_on_diagonal = vec![];
+        code = 0;
+        for idx in 0..10 {
+            // b1 is numbered 0 as are the squares outside the triangle
+            for s1 in (0..=27u8).filter(|s1| e.map_a1d1d4[*s1 as usize] == idx && (idx != 0 || *s1 == 1)) {
+                for s2 in 0..64u8 {
+                    let touching = file_of(s1).abs_diff(file_of(s2)) <= 1 && rank_of(s1).abs_diff(rank_of(s2)) <= 1;
+                    if touching || (off_diagonal(s1) == 0 && off_diagonal(s2) > 0) {
+                        continue;
+                    }
+                    if off_diagonal(s1) == 0 && off_diagonal(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        e.map_kk[idx as usize][s2 as usize] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, s2) in both_on_diagonal {
+            e.map_kk[idx as usize][s2 as usize] = code;
+            code += 1;
+        }
+
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 } + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available = 47;
+        for lead_pawns in 1..MAX_PIECES - 1 {
+            for file in 0..4u8 {
+                let mut idx = 0;
+                for rank in 1..7u8 {
+                    let s = (rank * 8 + file) as usize;
+                    if lead_pawns == 1 {
+                        e.map_pawns[s] = available;
+                        e.map_pawns[s ^ 7] = available - 1;
+                        available = available.saturating_sub(2);
+                    }
+                    e.lead_pawn_idx[lead_pawns][s] = idx;
+                    idx += e.binomial[lead_pawns - 1][e.map_pawns[s] as usize];
+                }
+                e.lead_pawns_size[lead_pawns][file as usize] = idx;
+            }
+        }
+        e
+    })
+}
+
+/// Decoding state for the positions of one side to move and leading pawn file
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    /// Piece codes in the order the position is encoded
+    pieces: [u8; MAX_PIECES],
+    /// Sizes of the groups of pieces encoded together, zero terminated
+    group_len: [usize; MAX_PIECES + 1],
+    /// Multiplier of each group's index, the last one being the table size
+    group_idx: [u64; MAX_PIECES + 1],
+    block_size: u64,
+    span: u64,
+    sparse_index_size: usize,
+    blocks: u64,
+    block_length_size: usize,
+    min_sym_len: u8,
+    /// Offsets into the table head
+    lowest_sym: usize,
+    btree: usize,
+    sparse_index: usize,
+    block_length: usize,
+    /// Offset of the compressed blocks in the file
+    data: u64,
+    /// Lowest code of each symbol length, left aligned in 64 bits
+    base64: Vec<u64>,
+    /// Number of values each symbol expands to, less one
+    symlen: Vec<u8>,
+    /// DTZ value maps for each result
+    map_idx: [usize; 4],
+}
+
+/// The start of a table file, read as far as needed for the index and decoding tables
+struct Head {
+    file: File,
+    bytes: Vec<u8>,
+}
+
+impl Head {
+    fn get(&mut self, pos: usize, len: usize) -> Option<&[u8]> {
+        if pos + len > self.bytes.len() {
+            let start = self.bytes.len();
+            self.bytes.resize(pos + len, 0);
+            self.file.read_exact(&mut self.bytes[start..]).ok()?;
+        }
+        Some(&self.bytes[pos..pos + len])
+    }
+
+    fn u8(&mut self, pos: usize) -> Option<u8> {
+        Some(self.get(pos, 1)?[0])
+    }
+
+    fn u16(&mut self, pos: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.get(pos, 2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self, pos: usize) -> Option<u32> {
+        Some(u32::from_le_bytes(self.get(pos, 4)?.try_into().ok()?))
+    }
+}
+
+struct Table {
+    kind: Kind,
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    /// Pawns of the leading color, then of the other
+    pawn_count: [usize; 2],
+    piece_count: usize,
+    /// 1 or 2 sides to move, each with 1 or 4 leading pawn files
+    sides: usize,
+    files: usize,
+    items: Vec<PairsData>,
+    /// Offset of the DTZ value maps
+    map: usize,
+    head: Vec<u8>,
+    file: Mutex<File>,
+}
+
+impl Table {
+    fn open(path: &Path, name: &str, kind: Kind) -> Option<Table> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, letter: char| side.chars().filter(|c| *c == letter).count();
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        // The leading color has the fewest pawns, so compresses better
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let mut table = Table {
+            kind,
+            symmetric: white == black,
+            has_pawns: white_pawns + black_pawns > 0,
+            has_unique_pieces: "QRBNP".chars().any(|letter| count(white, letter) == 1 || count(black, letter) == 1),
+            pawn_count: if white_leads { [white_pawns, black_pawns] } else { [black_pawns, white_pawns] },
+            piece_count: white.len() + black.len(),
+            sides: 1,
+            files: 1,
+            items: vec![],
+            map: 0,
+            head: vec![],
+            file: Mutex::new(File::open(path).ok()?),
+        };
+        if table.piece_count > MAX_PIECES {
+            return None;
+        }
+        let mut head = Head { file: File::open(path).ok()?, bytes: vec![] };
+        table.read(&mut head)?;
+        table.head = head.bytes;
+        Some(table)
+    }
+
+    fn item(&self, side: usize, file: usize) -> &PairsData {
+        &self.items[file * self.sides + side % self.sides]
+    }
+
+    /// Reads the table layout following the magic number
+    fn read(&mut self, head: &mut Head) -> Option<()> {
+        let magic = if self.kind == Kind::Wdl { WDL_MAGIC } else { DTZ_MAGIC };
+        if head.get(0, 4)? != magic {
+            return None;
+        }
+        let flags = head.u8(4)?;
+        if (flags & HAS_PAWNS != 0) != self.has_pawns || (self.kind == Kind::Wdl && (flags & SPLIT != 0) == self.symmetric) {
+            return None;
+        }
+        let mut pos = 5;
+        self.sides = if self.kind == Kind::Wdl && !self.symmetric { 2 } else { 1 };
+        self.files = if self.has_pawns { 4 } else { 1 };
+        self.items = vec![PairsData::default(); self.sides * self.files];
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+
+        for file in 0..self.files {
+            let first = head.u8(pos)?;
+            let second = if both_pawns { head.u8(pos + 1)? } else { 0 };
+            let order = [[first & 0xf, if both_pawns { second & 0xf } else { 0xf }], [first >> 4, if both_pawns { second >> 4 } else { 0xf }]];
+            pos += 1 + both_pawns as usize;
+            for k in 0..self.piece_count {
+                let byte = head.u8(pos)?;
+                pos += 1;
+                for side in 0..self.sides {
+                    self.items[file * self.sides + side].pieces[k] = if side == 1 { byte >> 4 } else { byte & 0xf };
+                }
+            }
+            for (side, order) in order.into_iter().enumerate().take(self.sides) {
+                self.set_groups(file * self.sides + side, order, file)?;
+            }
+        }
+        pos += pos & 1;
+
+        for i in 0..self.items.len() {
+            pos = set_sizes(&mut self.items[i], head, pos)?;
+        }
+
+        if self.kind == Kind::Dtz {
+            self.map = pos;
+            for file in 0..self.files {
+                let flags = self.items[file].flags;
+                if flags & MAPPED == 0 {
+                    continue;
+                }
+                for i in 0..4 {
+                    if flags & WIDE != 0 {
+                        pos += pos & 1;
+                        self.items[file].map_idx[i] = (pos - self.map) / 2 + 1;
+                        pos += 2 * head.u16(pos)? as usize + 2;
+                    } else {
+                        self.items[file].map_idx[i] = pos - self.map + 1;
+                        pos += head.u8(pos)? as usize + 1;
+                    }
+                }
+            }
+            pos += pos & 1;
+        }
+
+        for item in self.items.iter_mut() {
+            item.sparse_index = pos;
+            pos += item.sparse_index_size * 6;
+        }
+        for item in self.items.iter_mut() {
+            item.block_length = pos;
+            pos += item.block_length_size * 2;
+        }
+        // Keep the indexes in memory, reading the compressed blocks as needed
+        head.get(0, pos)?;
+        let mut data = pos as u64;
+        for item in self.items.iter_mut() {
+            data = (data + 0x3f) & !0x3f;
+            item.data = data;
+            data += item.blocks * item.block_size;
+        }
+        Some(())
+    }
+
+    /// Splits the pieces into groups encoded together and works out each group's multiplier
+    fn set_groups(&mut self, item: usize, order: [u8; 2], file: usize) -> Option<()> {
+        let e = encoding();
+        let d = &mut self.items[item];
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        d.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if both_pawns { d.group_len[1] } else { 0 };
+        let mut idx: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if self.has_pawns {
+                    *e.lead_pawns_size.get(d.group_len[0])?.get(file)?
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= e.binomial.get(d.group_len[1])?[48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= e.binomial.get(d.group_len[next])?[free_squares];
+                free_squares = free_squares.checked_sub(d.group_len[next])?;
+                next += 1;
+            }
+            k += 1;
+            if k > MAX_PIECES + 16 {
+                return None;
+            }
+        }
+        d.group_idx[n] = idx;
+        Some(())
+    }
+
+    /// Value stored for the position: the WDL result, or the DTZ for the already known `wdl`
+    fn probe(&self, board: &Board, black_stronger: bool, wdl: Wdl) -> Result<i32, Fail> {
+        let (stm, tb_file, idx) = self.index(board, black_stronger)?;
+        let value = self.decompress(self.item(stm, tb_file), idx).ok_or(Fail::Missing)?;
+        Ok(match self.kind {
+            Kind::Wdl => value - 2,
+            Kind::Dtz => self.map_dtz(tb_file, value, wdl).ok_or(Fail::Missing)?,
+        })
+    }
+
+    /// Side to move and leading pawn file of the sub-table holding the position, and its index there
+    fn index(&self, board: &Board, black_stronger: bool) -> Result<(usize, usize, u64), Fail> {
+        let e = encoding();
+        // Symmetric tables only store White to move, and tables are stored for the stronger side as White
+        let symmetric_black_to_move = self.symmetric && board.turn == Color::Black;
+        let flip = symmetric_black_to_move || black_stronger;
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = flip as usize ^ (board.turn == Color::Black) as usize;
+
+        let mut on_board = vec![];
+        for s in 0..64 {
+            let coords = Coords::new(s % 8, 7 - s / 8);
+            let piece = board.get_piece(coords);
+            if piece.piece_type != PieceType::Empty {
+                debug_assert_eq!(square(coords), s);
+                on_board.push((s, piece_code(piece)));
+            }
+        }
+        if on_board.len() != self.piece_count {
+            return Err(Fail::Missing);
+        }
+
+        let mut squares = [0u8; MAX_PIECES];
+        let mut pieces = [0u8; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut lead_pawn = None;
+        let mut tb_file = 0;
+        if self.has_pawns {
+            // The leading pawns come first and are of the reference color
+            let pawn = self.item(0, 0).pieces[0] ^ flip_color;
+            for (s, code) in on_board.iter().filter(|(_, code)| *code == pawn) {
+                squares[size] = s ^ flip_squares;
+                pieces[size] = code ^ flip_color;
+                size += 1;
+            }
+            lead_pawns = size;
+            lead_pawn = Some(pawn);
+            let leading = (0..lead_pawns).fold(0, |best, i| if e.map_pawns[squares[i] as usize] > e.map_pawns[squares[best] as usize] { i } else { best });
+            squares.swap(0, leading);
+            tb_file = file_of(squares[0]).min(7 - file_of(squares[0])) as usize;
+        }
+
+        // DTZ tables store one side to move, except symmetric pawnless ones where the colors can simply be swapped
+        let stored = self.symmetric && !self.has_pawns || self.item(stm, tb_file).flags & STM == stm as u8;
+        if self.kind == Kind::Dtz && !stored {
+            return Err(Fail::ChangeStm);
+        }
+
+        for (s, code) in on_board.iter().filter(|(_, code)| Some(*code) != lead_pawn) {
+            squares[size] = s ^ flip_squares;
+            pieces[size] = code ^ flip_color;
+            size += 1;
+        }
+        let d = self.item(stm, tb_file);
+
+        // Put the pieces in the order they are encoded in
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|j| d.pieces[i] == pieces[*j]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror so the leading piece is on files a to d
+        if file_of(squares[0]) > 3 {
+            for s in squares[..size].iter_mut() {
+                *s ^= 7;
+            }
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = e.lead_pawn_idx[lead_pawns][squares[0] as usize];
+            squares[1..lead_pawns].sort_by_key(|s| e.map_pawns[*s as usize]);
+            for (i, s) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += e.binomial[i][e.map_pawns[*s as usize] as usize];
+            }
+        } else {
+            // Without pawns, also mirror the leading piece onto ranks 1 to 4 and below the a1-h8 diagonal
+            if rank_of(squares[0]) > 3 {
+                for s in squares[..size].iter_mut() {
+                    *s ^= 56;
+                }
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_diagonal(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for s in squares[i..size].iter_mut() {
+                        *s = ((*s >> 3) | (*s << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            if self.has_unique_pieces {
+                let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+                let adjust1 = (s1 > s0) as u64;
+                let adjust2 = (s2 > s0) as u64 + (s2 > s1) as u64;
+                idx = if off_diagonal(s0) != 0 {
+                    (e.map_a1d1d4[s0 as usize] * 63 + (s1 as u64 - adjust1)) * 62 + s2 as u64 - adjust2
+                } else if off_diagonal(s1) != 0 {
+                    (6 * 63 + rank_of(s0) as u64 * 28 + e.map_b1h1h7[s1 as usize]) * 62 + s2 as u64 - adjust2
+                } else if off_diagonal(s2) != 0 {
+                    6 * 63 * 62 + 4 * 28 * 62 + rank_of(s0) as u64 * 7 * 28 + (rank_of(s1) as u64 - adjust1) * 28 + e.map_b1h1h7[s2 as usize]
+                } else {
+                    6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 + rank_of(s0) as u64 * 7 * 6 + (rank_of(s1) as u64 - adjust1) * 6 + (rank_of(s2) as u64 - adjust2)
+                };
+            } else {
+                idx = e.map_kk[e.map_a1d1d4[squares[0] as usize] as usize][squares[1] as usize];
+            }
+        }
+
+        // The remaining groups, each in ascending square order and counting only the squares left free
+        idx *= d.group_idx[0];
+        let mut group_start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let group_end = group_start + d.group_len[next];
+            squares[group_start..group_end].sort_unstable();
+            let mut n = 0;
+            for i in group_start..group_end {
+                let adjust = squares[..group_start].iter().filter(|s| squares[i] > **s).count();
+                let square = squares[i] as usize - adjust - if remaining_pawns { 8 } else { 0 };
+                n += e.binomial[i - group_start + 1][square];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        Ok((stm, tb_file, idx))
+    }
+
+    /// Converts a stored DTZ value to plies
+    fn map_dtz(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        let d = self.item(0, file);
+        let mut value = value as usize;
+        if d.flags & MAPPED != 0 {
+            let idx = d.map_idx[WDL_MAP[(wdl.value() + 2) as usize]] + value;
+            value = if d.flags & WIDE != 0 {
+                u16::from_le_bytes(self.head.get(self.map + 2 * idx..self.map + 2 * idx + 2)?.try_into().ok()?) as usize
+            } else {
+                *self.head.get(self.map + idx)? as usize
+            };
+        }
+        // Values are stored in moves unless the table says plies
+        let in_moves = (wdl == Wdl::Win && d.flags & WIN_PLIES == 0) || (wdl == Wdl::Loss && d.flags & LOSS_PLIES == 0) || wdl == Wdl::CursedWin || wdl == Wdl::BlessedLoss;
+        Some(value as i32 * if in_moves { 2 } else { 1 } + 1)
+    }
+
+    fn head_u16(&self, pos: usize) -> Option<u16> {
+        Some(u16::from_le_bytes(self.head.get(pos..pos + 2)?.try_into().ok()?))
+    }
+
+    /// The value at `idx`, from the canonical Huffman coded, recursively paired symbols of its block
+    fn decompress(&self, d: &PairsData, idx: u64) -> Option<i32> {
+        if d.flags & SINGLE_VALUE != 0 {
+            return Some(d.min_sym_len as i32);
+        }
+        // The sparse index points at the block and offset of every `span`th value, which gets close
+        let k = (idx / d.span) as usize;
+        if k >= d.sparse_index_size {
+            return None;
+        }
+        let entry = self.head.get(d.sparse_index + 6 * k..d.sparse_index + 6 * k + 6)?;
+        let mut block = u32::from_le_bytes(entry[0..4].try_into().ok()?) as usize;
+        let mut offset = u16::from_le_bytes(entry[4..6].try_into().ok()?) as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+        let block_length = |block: usize| -> Option<i64> {
+            if block >= d.block_length_size {
+                return None;
+            }
+            Some(self.head_u16(d.block_length + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        let mut data = vec![0; d.block_size as usize];
+        {
+            let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+            file.seek(SeekFrom::Start(d.data + block as u64 * d.block_size)).ok()?;
+            let mut read = 0;
+            while read < data.len() {
+                match file.read(&mut data[read..]) {
+                    Ok(0) => break,
+                    Ok(n) => read += n,
+                    Err(_) => return None,
+                }
+            }
+        }
+        // Zero padding, so refilling the bit buffer past the end of the block reads nothing
+        data.extend_from_slice(&[0; 8]);
+        let word = |i: usize| -> u32 { data.get(i * 4..i * 4 + 4).map_or(0, |b| u32::from_be_bytes(b.try_into().unwrap())) };
+
+        let mut buf = (word(0) as u64) << 32 | word(1) as u64;
+        let mut next_word = 2;
+        let mut buf_size = 64;
+        let lowest_sym = |len: usize| self.head_u16(d.lowest_sym + 2 * len);
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buf < d.base64[len] {
+                len += 1;
+                if len >= d.base64.len() {
+                    return None;
+                }
+            }
+            sym = (buf - d.base64[len]).checked_shr((64 - len - d.min_sym_len as usize) as u32).unwrap_or(0) as usize;
+            sym += lowest_sym(len)? as usize;
+            let sym_len = *d.symlen.get(sym)? as i64;
+            if offset < sym_len + 1 {
+                break;
+            }
+            offset -= sym_len + 1;
+            let bits = len + d.min_sym_len as usize;
+            buf = buf.checked_shl(bits as u32).unwrap_or(0);
+            buf_size -= bits as i32;
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= (word(next_word) as u64) << (64 - buf_size);
+                next_word += 1;
+            }
+        }
+
+        // Expand the pair tree down to the single value at `offset`
+        while *d.symlen.get(sym)? != 0 {
+            let (left, right) = btree(&self.head, d.btree, sym)?;
+            let left_len = *d.symlen.get(left)? as i64;
+            if offset < left_len + 1 {
+                sym = left;
+            } else {
+                offset -= left_len + 1;
+                sym = right;
+            }
+        }
+        Some(btree(&self.head, d.btree, sym)?.0 as i32)
+    }
+}
+
+/// The two 12 bit halves of a symbol pair in the tree
+fn btree(head: &[u8], btree: usize, sym: usize) -> Option<(usize, usize)> {
+    let lr = head.get(btree + 3 * sym..btree + 3 * sym + 3)?;
+    let left = ((lr[1] as usize & 0xf) << 8) | lr[0] as usize;
+    let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+    Some((left, right))
+}
+
+/// Reads the block and Huffman code sizes of one sub-table, returning where the next one starts
+fn set_sizes(d: &mut PairsData, head: &mut Head, mut pos: usize) -> Option<usize> {
+    d.flags = head.u8(pos)?;
+    pos += 1;
+    if d.flags & SINGLE_VALUE != 0 {
+        // The only value is kept where the symbol length would be
+        d.min_sym_len = head.u8(pos)?;
+        return Some(pos + 1);
+    }
+    let table_size = d.group_idx[d.group_len.iter().position(|len| *len == 0)?];
+    d.block_size = 1u64.checked_shl(head.u8(pos)? as u32)?;
+    d.span = 1u64.checked_shl(head.u8(pos + 1)? as u32)?;
+    d.sparse_index_size = table_size.div_ceil(d.span) as usize;
+    let padding = head.u8(pos + 2)? as usize;
+    d.blocks = head.u32(pos + 3)? as u64;
+    d.block_length_size = d.blocks as usize + padding;
+    let max_sym_len = head.u8(pos + 7)?;
+    d.min_sym_len = head.u8(pos + 8)?;
+    if max_sym_len < d.min_sym_len || max_sym_len > 64 {
+        return None;
+    }
+    d.lowest_sym = pos + 9;
+    let lengths = (max_sym_len - d.min_sym_len + 1) as usize;
+    d.base64 = vec![0; lengths];
+    for i in (0..lengths - 1).rev() {
+        let lowest = head.u16(d.lowest_sym + 2 * i)? as u64;
+        let next_lowest = head.u16(d.lowest_sym + 2 * (i + 1))? as u64;
+        d.base64[i] = d.base64[i + 1].wrapping_add(lowest).wrapping_sub(next_lowest) / 2;
+    }
+    for (i, base) in d.base64.iter_mut().enumerate() {
+        *base = base.checked_shl((64 - i - d.min_sym_len as usize) as u32).unwrap_or(0);
+    }
+    pos = d.lowest_sym + 2 * lengths;
+    let symbols = head.u16(pos)? as usize;
+    pos += 2;
+    d.btree = pos;
+    let tree = head.get(d.btree, 3 * symbols)?.to_vec();
+    d.symlen = vec![0; symbols];
+    let mut visited = vec![false; symbols];
+    for sym in 0..symbols {
+        if !visited[sym] {
+            d.symlen[sym] = set_symlen(d, &tree, sym, &mut visited)?;
+        }
+    }
+    Some(d.btree + 3 * symbols + (symbols & 1))
+}
+
+fn set_symlen(d: &mut PairsData, tree: &[u8], sym: usize, visited: &mut [bool]) -> Option<u8> {
+    visited[sym] = true;
+    let (left, right) = btree(tree, 0, sym)?;
+    if right == 0xfff {
+        return Some(0);
+    }
+    for child in [left, right] {
+        if !*visited.get(child)? {
+            d.symlen[child] = set_symlen(d, tree, child, visited)?;
+        }
+    }
+    Some(d.symlen[left].wrapping_add(d.symlen[right]).wrapping_add(1))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Header of a three piece table with one sub-table per side to move, up to the sub-table sizes
+    fn header(magic: [u8; 4], flags: u8, files: usize, pieces: [u8; 3]) -> Vec<u8> {
+        let mut bytes = magic.to_vec();
+        bytes.push(flags);
+        for _ in 0..files {
+            bytes.push(0);
+            bytes.extend(pieces.iter().map(|code| code | code << 4));
+        }
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// A KQvK WDL table with a single result for White to move and Huffman coded losses
+    /// and blessed losses for Black, returning the stored values
+    fn write_kqvk(dir: &Path) -> Vec<u8> {
+        let mut bytes = header(WDL_MAGIC, SPLIT, 1, [5, 6, 14]);
+        bytes.extend([SINGLE_VALUE, 4]);
+        // One bit codes: 0 for a blessed loss, 1 for a pair of a loss then a blessed loss
+        bytes.extend([0, 12, 15, 0, 1, 0, 0, 0, 1, 1, 1, 0, 3, 0]);
+        bytes.extend([0, 0xf0, 0xff, 1, 0xf0, 0xff, 0, 0x10, 0, 0]);
+        let mut rng = fastrand::Rng::with_seed(7);
+        let (mut values, mut bits) = (vec![], vec![]);
+        while values.len() < 31332 {
+            let pair = rng.bool();
+            bits.push(pair);
+            values.extend(if pair { &[0, 1][..] } else { &[1][..] });
+        }
+        // Sparse index entry pointing half a span back from the block start, then the block length
+        bytes.extend([0, 0, 0, 0, 0, 0x40]);
+        bytes.extend((values.len() as u16 - 1).to_le_bytes());
+        bytes.resize(64, 0);
+        let mut block = vec![0u8; 4096];
+        for (i, bit) in bits.iter().enumerate() {
+            block[i / 8] |= (*bit as u8) << (7 - i % 8);
+        }
+        bytes.extend(block);
+        std::fs::write(dir.join("KQvK.rtbw"), bytes).unwrap();
+        values
+    }
+
+    /// Made up KQvK and KPvK tables in a fresh directory, with the stored values of Black's KQvK positions
+    pub(crate) fn write_tables(name: &str) -> (PathBuf, Vec<u8>) {
+        let dir = std::env::temp_dir().join(format!("chess-syzygy-test-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let values = write_kqvk(&dir);
+        // DTZ for White to move only, 4 moves
+        let mut dtz = header(DTZ_MAGIC, 0, 1, [5, 6, 14]);
+        dtz.extend([SINGLE_VALUE, 4]);
+        std::fs::write(dir.join("KQvK.rtbz"), dtz).unwrap();
+        // Wins for White to move and losses for Black whichever file the pawn is on
+        let mut kpvk = header(WDL_MAGIC, SPLIT | HAS_PAWNS, 4, [1, 6, 14]);
+        for _ in 0..4 {
+            kpvk.extend([SINGLE_VALUE, 4, SINGLE_VALUE, 0]);
+        }
+        std::fs::write(dir.join("KPvK.rtbw"), kpvk).unwrap();
+        (dir, values)
+    }
+
+    #[test]
+    fn test_encoding_tables() {
+        let e = encoding();
+        let mut kk = e.map_kk.iter().flatten().copied().filter(|code| *code > 0).collect::<Vec<_>>();
+        kk.sort();
+        kk.dedup();
+        assert_eq!(kk.len(), 461);
+        assert_eq!(kk.last(), Some(&461));
+        let mut triangle = (0..=27).filter(|s| file_of(*s) <= 3 && off_diagonal(*s) <= 0).map(|s| e.map_a1d1d4[s as usize]).collect::<Vec<_>>();
+        triangle.sort();
+        assert_eq!(triangle, (0..10).collect::<Vec<_>>());
+        assert_eq!((e.binomial[2][5], e.binomial[3][6], e.binomial[5][63]), (10, 20, 7028847));
+        assert_eq!((e.map_pawns[8], e.map_pawns[15], e.map_pawns[52]), (47, 46, 0));
+        assert_eq!(e.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn test_open() {
+        let (dir, _) = write_tables("open");
+        let tablebase = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        assert_eq!((tablebase.len(), tablebase.max_pieces()), (3, 3));
+        assert!(Tablebase::open("no/such/tablebases").is_err());
+        // Too many pieces, castling rights or a missing table
+        assert_eq!(Board::new().probe_tablebase(&tablebase), None);
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap().probe_tablebase(&tablebase), None);
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R w - - 0 1").unwrap().probe_tablebase(&tablebase), None);
+        let draw = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap().probe_tablebase(&tablebase);
+        assert_eq!(draw, Some(TablebaseProbe { wdl: Wdl::Draw, dtz: Some(0) }));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_decompress() {
+        let (dir, values) = write_tables("decompress");
+        let tablebase = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        let table = tablebase.table("KQvK", Kind::Wdl).unwrap();
+        for idx in (0..31332).step_by(7).chain([31331]) {
+            assert_eq!(table.decompress(table.item(1, 0), idx), Some(values[idx as usize] as i32));
+            assert_eq!(table.decompress(table.item(0, 0), idx), Some(4));
+        }
+
+        // Every placement of the pieces has its own index within the table
+        for (king, queen, other) in (0..64).flat_map(|k| (0..64).flat_map(move |q| (0..64).map(move |o| (k, q, o)))) {
+            if king == queen || king == other || queen == other {
+                continue;
+            }
+            let mut board = Board::from_fen("k7/8/8/8/8/8/8/K7 b - - 0 1").unwrap();
+            board.state = [[Piece::empty(); 8]; 8];
+            board.state[king / 8][king % 8] = Piece::white(PieceType::King);
+            board.state[queen / 8][queen % 8] = Piece::white(PieceType::Queen);
+            board.state[other / 8][other % 8] = Piece::black(PieceType::King);
+            let (stm, file, idx) = table.index(&board, false).unwrap();
+            assert_eq!((stm, file), (1, 0));
+            assert!(idx < 31332);
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_probe() {
+        let (dir, values) = write_tables("probe");
+        let tablebase = Tablebase::open(dir.to_str().unwrap()).unwrap();
+        let white = Board::from_fen("8/8/8/8/8/2k5/8/K6Q w - - 0 1").unwrap();
+        assert_eq!(white.probe_tablebase(&tablebase), Some(TablebaseProbe { wdl: Wdl::Win, dtz: Some(9) }));
+
+        let black = Board::from_fen("8/8/8/8/8/2k5/8/K6Q b - - 0 1").unwrap();
+        let table = tablebase.table("KQvK", Kind::Wdl).unwrap();
+        let (_, _, idx) = table.index(&black, false).unwrap();
+        let probe = black.probe_tablebase(&tablebase).unwrap();
+        assert_eq!(probe.wdl, Wdl::from_value(values[idx as usize] as i32 - 2));
+        // Black's DTZ isn't stored, so comes from White's after each king move
+        assert_eq!(probe.dtz, Some(-10));
+        // Capturing the queen draws
+        let capture = Board::from_fen("8/8/8/8/8/2k5/3Q4/K7 b - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&capture), Some(Wdl::Draw));
+
+        let (mc, probe) = tablebase.best_move(&white).unwrap();
+        assert_eq!(probe.wdl, Wdl::Win);
+        let mut after = white.clone();
+        after.apply(&mc);
+        assert!(tablebase.probe_wdl(&after).unwrap() < Wdl::Draw);
+
+        // Pawn tables are stored for the side with the pawn as White
+        let pawn = Board::from_fen("8/8/8/8/8/2k5/6P1/K7 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&pawn), Some(Wdl::Win));
+        let black_pawn = Board::from_fen("k7/6p1/2K5/8/8/8/8/8 w - - 0 1").unwrap();
+        assert_eq!(tablebase.probe_wdl(&black_pawn), Some(Wdl::Loss));
+        assert_eq!(tablebase.probe_dtz(&black_pawn), None);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
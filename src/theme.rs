@@ -0,0 +1,170 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// How the file and rank labels are drawn
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelStyle {
+    Hidden,
+    /// Files along the bottom edge and ranks along the left, in the corners of the squares
+    Corner,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    pub light_square: [u8; 3],
+    pub dark_square: [u8; 3],
+    /// RGBA overlay for the selected square
+    pub selected: [u8; 4],
+    /// RGBA overlay for the squares of the last move
+    pub last_move: [u8; 4],
+    /// Piece set directory, keeping the current set when unset
+    pub piece_set: Option<PathBuf>,
+    pub piece_scale: f32,
+    pub labels: LabelStyle,
+    /// Label color, defaulting to the color of the opposite square
+    pub label_color: Option<[u8; 3]>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::classic()
+    }
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Theme {
+            name: "classic".to_string(),
+            light_square: [240, 217, 181],
+            dark_square: [181, 136, 99],
+            selected: [255, 0, 0, 100],
+            last_move: [205, 210, 106, 130],
+            piece_set: None,
+            piece_scale: 3.7,
+            labels: LabelStyle::Corner,
+            label_color: Some([0, 0, 0]),
+        }
+    }
+    pub fn green() -> Self {
+        Theme {
+            name: "green".to_string(),
+            light_square: [238, 238, 210],
+            dark_square: [118, 150, 86],
+            selected: [246, 246, 105, 150],
+            last_move: [186, 202, 68, 150],
+            label_color: None,
+            ..Theme::classic()
+        }
+    }
+    pub fn blue() -> Self {
+        Theme {
+            name: "blue".to_string(),
+            light_square: [222, 227, 230],
+            dark_square: [140, 162, 173],
+            selected: [80, 130, 220, 120],
+            last_move: [155, 199, 0, 105],
+            label_color: None,
+            ..Theme::classic()
+        }
+    }
+    pub fn built_in() -> Vec<Theme> {
+        vec![Theme::classic(), Theme::green(), Theme::blue()]
+    }
+}
+
+/// Themes file, e.g.
+///
+/// ```toml
+/// theme = "walnut"
+///
+/// [[themes]]
+/// name = "walnut"
+/// light_square = [230, 200, 160]
+/// dark_square = [120, 80, 50]
+/// piece_set = "/home/me/pieces/walnut"
+/// labels = "hidden"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct ThemeConfig {
+    /// Name of the theme to start with
+    pub theme: Option<String>,
+    #[serde(default)]
+    pub themes: Vec<Theme>,
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, toml::de::Error),
+}
+
+impl std::fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ThemeError::Io(path, e) => write!(f, "could not read {}: {}", path.display(), e),
+            ThemeError::Parse(path, e) => write!(f, "invalid theme file {}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {}
+
+impl ThemeConfig {
+    pub fn from_file(path: &Path) -> Result<Self, ThemeError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ThemeError::Io(path.to_path_buf(), e))?;
+        let mut config: ThemeConfig = toml::from_str(&text).map_err(|e| ThemeError::Parse(path.to_path_buf(), e))?;
+        // Piece sets are relative to the themes file
+        let base = path.parent().unwrap_or(Path::new(""));
+        for theme in config.themes.iter_mut() {
+            if let Some(piece_set) = &theme.piece_set {
+                theme.piece_set = Some(base.join(piece_set));
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_theme_config() {
+        let config: ThemeConfig = toml::from_str(
+            r#"
+            theme = "dark"
+
+            [[themes]]
+            name = "dark"
+            light_square = [100, 100, 100]
+            dark_square = [40, 40, 40]
+            labels = "hidden"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.theme.as_deref(), Some("dark"));
+        let theme = &config.themes[0];
+        assert_eq!(theme.dark_square, [40, 40, 40]);
+        assert_eq!(theme.labels, LabelStyle::Hidden);
+        // Unset fields fall back to the classic theme
+        assert_eq!(theme.piece_scale, Theme::classic().piece_scale);
+        assert_eq!(theme.selected, Theme::classic().selected);
+    }
+
+    #[test]
+    fn test_theme_file_errors() {
+        assert!(matches!(ThemeConfig::from_file(Path::new("does/not/exist.toml")), Err(ThemeError::Io(..))));
+        let path = std::env::temp_dir().join(format!("chess-theme-test-{}.toml", std::process::id()));
+        std::fs::write(&path, "[[themes]]\nlight_square = \"white\"\npiece_set = \"pieces\"\n").unwrap();
+        let result = ThemeConfig::from_file(&path);
+        std::fs::write(&path, "[[themes]]\nname = \"mine\"\npiece_set = \"pieces\"\n").unwrap();
+        let config = ThemeConfig::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(result, Err(ThemeError::Parse(..))));
+        assert_eq!(config.themes[0].piece_set, Some(std::env::temp_dir().join("pieces")));
+    }
+}
@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+use crate::{
+    board::Board,
+    clock::{Bonus, Clock, TimeControl},
+    engine::{EngineConfig, UciEngine},
+    epd::Epd,
+    pgn::Pgn,
+    pieces::Color,
+};
+
+pub const USAGE: &str = "\
+Usage: chess-match --engine <engine> --engine <engine> [options]
+
+Plays the first engine against the second, each opening twice with colours swapped.
+An engine is `builtin` for this crate's own search, or `cmd=PATH` for a UCI program,
+followed by any of `name=NAME`, `arg=ARG` and `option.NAME=VALUE`.
+
+  --games N                             games to play (default 100)
+  --tc CONTROL                          clock for each side in PGN syntax, such as 10+0.1 (the default)
+  --depth N | --movetime MS             search to a fixed depth, or for a fixed time per move, instead
+  --openings <file.epd | file.pgn>      start from these positions in turn instead of the initial one
+  --concurrency N                       games played at once (default 1)
+  --sprt [elo0=0] [elo1=5] [alpha=0.05] [beta=0.05]
+                                        stop once it is clear whether the first engine is elo1 stronger or not
+  --maxplies N                          adjudicate a draw after N plies (default 400)
+  --pgnout FILE                         write every game to FILE";
+
+/// Time an engine may overrun its clock by before the answer is abandoned, after which it loses on time anyway
+const GRACE: Duration = Duration::from_secs(1);
+
+/// How long engines think about each move
+#[derive(Debug, Clone, PartialEq)]
+pub enum Timing {
+    Clock(TimeControl),
+    Depth(u32),
+    MoveTime(Duration),
+}
+
+/// Games won, drawn and lost by the first engine
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+/// Score expected from a rating difference of `elo`
+fn expected(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Scores are kept this far from 0 and 1 when turned into ratings, so a clean sweep reads as
+/// about 1200 Elo rather than infinite
+const MIN_RATIO: f64 = 0.001;
+
+/// Rating difference which would score `ratio`
+fn elo_from(ratio: f64) -> f64 {
+    let ratio = ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO);
+    400.0 * (ratio / (1.0 - ratio)).log10()
+}
+
+impl Score {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    pub fn add(&mut self, points: f64) {
+        match points {
+            p if p > 0.5 => self.wins += 1,
+            p if p < 0.5 => self.losses += 1,
+            _ => self.draws += 1,
+        }
+    }
+
+    /// Share of the points won, from 0 to 1
+    pub fn ratio(&self) -> f64 {
+        match self.games() {
+            0 => 0.5,
+            games => (self.wins as f64 + self.draws as f64 / 2.0) / games as f64,
+        }
+    }
+
+    /// Variance of the points of a single game
+    fn variance(&self) -> f64 {
+        let s = self.ratio();
+        let squares = self.wins as f64 * (1.0 - s).powi(2) + self.draws as f64 * (0.5 - s).powi(2) + self.losses as f64 * s.powi(2);
+        squares / self.games().max(1) as f64
+    }
+
+    pub fn elo(&self) -> f64 {
+        elo_from(self.ratio())
+    }
+
+    /// Half the width of the 95% confidence interval around `elo`
+    pub fn elo_margin(&self) -> f64 {
+        let deviation = (self.variance() / self.games().max(1) as f64).sqrt() * 1.959964;
+        (elo_from(self.ratio() + deviation) - elo_from(self.ratio() - deviation)) / 2.0
+    }
+}
+
+/// `W - L - D [ratio] games`, as cutechess prints it
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} - {} - {} [{:.3}] {}", self.wins, self.losses, self.draws, self.ratio(), self.games())
+    }
+}
+
+/// Sequential probability ratio test of the first engine being `elo1` stronger (H1) against
+/// being only `elo0` stronger (H0), wrongly accepting H1 with probability `alpha` and H0 with `beta`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    fn default() -> Self {
+        Sprt { elo0: 0.0, elo1: 5.0, alpha: 0.05, beta: 0.05 }
+    }
+}
+
+impl Sprt {
+    /// Log-likelihood ratios at which H0 and H1 are accepted
+    pub fn bounds(&self) -> (f64, f64) {
+        ((self.beta / (1.0 - self.alpha)).ln(), ((1.0 - self.beta) / self.alpha).ln())
+    }
+
+    /// Log-likelihood ratio of H1 to H0, with the points of each game taken as normally distributed
+    pub fn llr(&self, score: &Score) -> f64 {
+        let variance = score.variance();
+        if variance == 0.0 {
+            return 0.0;
+        }
+        let (s0, s1) = (expected(self.elo0), expected(self.elo1));
+        (s1 - s0) * (2.0 * score.ratio() - s0 - s1) / (2.0 * variance / score.games() as f64)
+    }
+
+    /// `Some(true)` once H1 is accepted, `Some(false)` for H0
+    pub fn decision(&self, score: &Score) -> Option<bool> {
+        let (lower, upper) = self.bounds();
+        match self.llr(score) {
+            llr if llr >= upper => Some(true),
+            llr if llr <= lower => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MatchConfig {
+    pub engines: [EngineConfig; 2],
+    /// Played in turn, each twice; the initial position when empty
+    pub openings: Vec<Board>,
+    pub games: usize,
+    pub timing: Timing,
+    pub concurrency: usize,
+    pub sprt: Option<Sprt>,
+    /// Games this long are drawn
+    pub max_plies: usize,
+}
+
+impl MatchConfig {
+    pub fn new(engines: [EngineConfig; 2]) -> Self {
+        MatchConfig { engines, openings: vec![], games: 100, timing: Timing::Clock("10+0.1".parse().expect("valid time control")), concurrency: 1, sprt: None, max_plies: 400 }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameEnd {
+    pub winner: Option<Color>,
+    pub reason: String,
+}
+
+impl GameEnd {
+    fn win(winner: Color, reason: String) -> Self {
+        GameEnd { winner: Some(winner), reason }
+    }
+
+    fn draw(reason: &str) -> Self {
+        GameEnd { winner: None, reason: reason.to_string() }
+    }
+}
+
+/// A finished game of a match
+#[derive(Clone)]
+pub struct GameRecord {
+    /// Counting from 1
+    pub round: usize,
+    /// Whether the first engine had White
+    pub first_white: bool,
+    pub end: GameEnd,
+    pub board: Board,
+}
+
+impl GameRecord {
+    /// Points for the first engine
+    pub fn points(&self) -> f64 {
+        match self.end.winner {
+            None => 0.5,
+            Some(Color::White) if self.first_white => 1.0,
+            Some(Color::Black) if !self.first_white => 1.0,
+            Some(_) => 0.0,
+        }
+    }
+
+    pub fn pgn(&self, config: &MatchConfig) -> Pgn {
+        let mut pgn = Pgn::new(self.board.clone());
+        let (white, black) = if self.first_white { (0, 1) } else { (1, 0) };
+        pgn.set_header("Event", "chess-match");
+        pgn.set_header("Round", &self.round.to_string());
+        pgn.set_header("White", &config.engines[white].name);
+        pgn.set_header("Black", &config.engines[black].name);
+        pgn.set_header(
+            "Result",
+            match self.end.winner {
+                Some(Color::White) => "1-0",
+                Some(Color::Black) => "0-1",
+                None => "1/2-1/2",
+            },
+        );
+        if let Timing::Clock(control) = &config.timing {
+            pgn.set_header("TimeControl", &control.to_string());
+        }
+        pgn.set_header("Termination", &self.end.reason);
+        pgn
+    }
+}
+
+/// Positions from an EPD file, or the final positions of the games in a PGN file
+pub fn load_openings(path: &str) -> Result<Vec<Board>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    if path.to_lowercase().ends_with(".pgn") {
+        return Ok(Pgn::parse_all(&text)?.into_iter().map(|pgn| pgn.board).collect());
+    }
+    Ok(Epd::parse_all(&text)?.into_iter().map(|epd| epd.board).collect())
+}
+
+/// The `go` arguments for the side to move, with how long to wait for its answer
+fn go_command(timing: &Timing, clock: &Clock) -> (String, Duration) {
+    match timing {
+        // Fixed depths have no clock to run out, so only a hung engine is given up on
+        Timing::Depth(depth) => (format!("depth {}", depth), Duration::from_secs(3600)),
+        Timing::MoveTime(time) => (format!("movetime {}", time.as_millis()), *time + GRACE),
+        Timing::Clock(_) => {
+            let increment = |color| match clock.period(color).bonus {
+                Bonus::Increment(increment) => increment.as_millis(),
+                _ => 0,
+            };
+            let mut go = format!(
+                "wtime {} btime {} winc {} binc {}",
+                clock.remaining(Color::White).as_millis(),
+                clock.remaining(Color::Black).as_millis(),
+                increment(Color::White),
+                increment(Color::Black)
+            );
+            if let Some(moves) = clock.moves_to_go(clock.turn()) {
+                go += &format!(" movestogo {}", moves);
+            }
+            (go, clock.remaining(clock.turn()) + GRACE)
+        }
+    }
+}
+
+/// Ends games the rules engine leaves running: repetitions, the fifty-move rule, bare kings and overlong games
+fn adjudicate(board: &Board, seen: &HashMap<u64, u32>, plies: usize, max_plies: usize) -> Option<GameEnd> {
+    if let Some(outcome) = board.outcome() {
+        return Some(GameEnd { winner: outcome.winner(), reason: outcome.to_string() });
+    }
+    if seen.get(&board.polyglot_key()).is_some_and(|count| *count >= 3) {
+        return Some(GameEnd::draw("Draw by threefold repetition"));
+    }
+    if board.halfmove_clock >= 100 {
+        return Some(GameEnd::draw("Draw by the fifty-move rule"));
+    }
+    if !board.has_mating_material(Color::White) && !board.has_mating_material(Color::Black) {
+        return Some(GameEnd::draw("Draw by insufficient material"));
+    }
+    if plies >= max_plies {
+        return Some(GameEnd::draw("Draw by adjudication, game too long"));
+    }
+    None
+}
+
+/// Plays one game from `opening`, the side whose engine fails or errs losing it
+pub fn play_game(white: &mut UciEngine, black: &mut UciEngine, opening: &Board, timing: &Timing, max_plies: usize) -> (Board, GameEnd) {
+    let mut board = opening.clone();
+    let mut clock = Clock::new(match timing {
+        Timing::Clock(control) => control.clone(),
+        _ => TimeControl::sudden_death(Duration::ZERO),
+    });
+    clock.set_turn(board.turn);
+    clock.start();
+    let mut seen = HashMap::new();
+    let mut plies = 0;
+    loop {
+        *seen.entry(board.polyglot_key()).or_insert(0) += 1;
+        if let Some(end) = adjudicate(&board, &seen, plies, max_plies) {
+            return (board, end);
+        }
+        let turn = board.turn;
+        let engine = if turn == Color::White { &mut *white } else { &mut *black };
+        let (go, timeout) = go_command(timing, &clock);
+        let started = Instant::now();
+        let reply = engine.best_move(&board, &go, timeout);
+        if matches!(timing, Timing::Clock(_)) {
+            clock.tick(started.elapsed());
+            if clock.flagged().is_some() {
+                let outcome = board.timeout(turn);
+                return (board, GameEnd { winner: outcome.winner(), reason: outcome.to_string() });
+            }
+        }
+        match reply {
+            Ok(mc) => board.apply(&mc),
+            Err(e) => return (board, GameEnd::win(turn.opposite(), e)),
+        }
+        clock.press();
+        plies += 1;
+    }
+}
+
+/// Plays the match on `config.concurrency` threads, calling `report` as each game finishes with the
+/// score so far. Stops early once the SPRT reaches a decision.
+pub fn run_match(config: &MatchConfig, mut report: impl FnMut(&GameRecord, &Score) -> Result<(), String>) -> Result<Score, String> {
+    let openings = if config.openings.is_empty() { vec![Board::new()] } else { config.openings.clone() };
+    let next = Arc::new(AtomicUsize::new(0));
+    let stop = Arc::new(AtomicBool::new(false));
+    let (sender, results) = mpsc::channel();
+    let mut workers = vec![];
+    for _ in 0..config.concurrency.clamp(1, config.games.max(1)) {
+        let (config, openings, next, stop, sender) = (config.clone(), openings.clone(), next.clone(), stop.clone(), sender.clone());
+        workers.push(std::thread::spawn(move || {
+            let mut engines = match (config.engines[0].start(), config.engines[1].start()) {
+                (Ok(first), Ok(second)) => [first, second],
+                (Err(e), _) | (_, Err(e)) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+            while !stop.load(Ordering::Relaxed) {
+                let round = next.fetch_add(1, Ordering::Relaxed);
+                if round >= config.games {
+                    break;
+                }
+                // Each opening is played twice in a row, with colours swapped
+                let opening = &openings[round / 2 % openings.len()];
+                let first_white = round % 2 == 0;
+                let [first, second] = &mut engines;
+                let (white, black) = if first_white { (first, second) } else { (second, first) };
+                if let Err(e) = white.new_game().and_then(|_| black.new_game()) {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+                let (board, end) = play_game(white, black, opening, &config.timing, config.max_plies);
+                if sender.send(Ok(GameRecord { round: round + 1, first_white, end, board })).is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+    drop(sender);
+
+    let mut score = Score::default();
+    let mut outcome = Ok(());
+    for result in results {
+        let record = match result {
+            Ok(record) => record,
+            Err(e) => {
+                outcome = Err(e);
+                stop.store(true, Ordering::Relaxed);
+                continue;
+            }
+        };
+        score.add(record.points());
+        if let Err(e) = report(&record, &score) {
+            outcome = Err(e);
+            stop.store(true, Ordering::Relaxed);
+        }
+        if config.sprt.is_some_and(|sprt| sprt.decision(&score).is_some()) {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+    for worker in workers {
+        let _ = worker.join();
+    }
+    outcome.map(|_| score)
+}
+
+/// Groups `--flag value...` arguments, the values being everything up to the next flag
+fn split_flags(args: &[String]) -> Result<Vec<(&str, &[String])>, String> {
+    let mut groups = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        let name = args[i].as_str();
+        if !name.starts_with("--") {
+            return Err(format!("Unexpected argument {}\n{}", name, USAGE));
+        }
+        let end = args[i + 1..].iter().position(|arg| arg.starts_with("--")).map_or(args.len(), |n| i + 1 + n);
+        groups.push((name, &args[i + 1..end]));
+        i = end;
+    }
+    Ok(groups)
+}
+
+fn single<'a>(name: &str, values: &'a [String]) -> Result<&'a str, String> {
+    match values {
+        [value] => Ok(value),
+        _ => Err(format!("{} needs one value", name)),
+    }
+}
+
+fn number<T: std::str::FromStr>(name: &str, values: &[String]) -> Result<T, String> {
+    single(name, values)?.parse().map_err(|_| format!("Invalid number for {}", name))
+}
+
+fn parse_sprt(values: &[String]) -> Result<Sprt, String> {
+    let mut sprt = Sprt::default();
+    for value in values {
+        let (key, number) = value.split_once('=').ok_or_else(|| format!("Expected key=value for --sprt, got {}", value))?;
+        let number = number.parse::<f64>().map_err(|_| format!("Invalid number {}", number))?;
+        match key {
+            "elo0" => sprt.elo0 = number,
+            "elo1" => sprt.elo1 = number,
+            "alpha" => sprt.alpha = number,
+            "beta" => sprt.beta = number,
+            _ => return Err(format!("Unknown SPRT setting {}", key)),
+        }
+    }
+    if sprt.elo0 >= sprt.elo1 || !(0.0..1.0).contains(&sprt.alpha) || !(0.0..1.0).contains(&sprt.beta) || sprt.alpha == 0.0 || sprt.beta == 0.0 {
+        return Err("The SPRT needs elo0 < elo1 and alpha and beta between 0 and 1".to_string());
+    }
+    Ok(sprt)
+}
+
+/// Runs a match from command line arguments (without the program name), printing each game and the running score
+pub fn run(args: &[String], output: &mut impl Write) -> Result<(), String> {
+    let io = |e: std::io::Error| e.to_string();
+    let mut engines = vec![];
+    let mut pgn_out = None;
+    let mut config = MatchConfig::new([EngineConfig::builtin(), EngineConfig::builtin()]);
+    for (name, values) in split_flags(args)? {
+        match name {
+            "--engine" => engines.push(EngineConfig::parse(values)?),
+            "--games" => config.games = number(name, values)?,
+            "--tc" => config.timing = Timing::Clock(single(name, values)?.parse().map_err(|e| format!("Invalid time control: {}", e))?),
+            "--depth" => config.timing = Timing::Depth(number(name, values)?),
+            "--movetime" => config.timing = Timing::MoveTime(Duration::from_millis(number(name, values)?)),
+            "--openings" => config.openings = load_openings(single(name, values)?)?,
+            "--concurrency" => config.concurrency = number(name, values)?,
+            "--sprt" => config.sprt = Some(parse_sprt(values)?),
+            "--maxplies" => config.max_plies = number(name, values)?,
+            "--pgnout" => pgn_out = Some(std::fs::File::create(single(name, values)?).map_err(|e| format!("Could not create the PGN file: {}", e))?),
+            "--help" | "-h" => return writeln!(output, "{}", USAGE).map_err(io),
+            _ => return Err(format!("Unknown option {}\n{}", name, USAGE)),
+        }
+    }
+    config.engines = match <[EngineConfig; 2]>::try_from(engines) {
+        Ok(engines) => engines,
+        Err(_) => return Err(format!("A match needs two engines\n{}", USAGE)),
+    };
+    let names = format!("{} vs {}", config.engines[0].name, config.engines[1].name);
+    let score = run_match(&config, |record, score| {
+        let pgn = record.pgn(&config);
+        let (white, black) = (pgn.header("White").unwrap_or("?"), pgn.header("Black").unwrap_or("?"));
+        writeln!(output, "Finished game {} ({} vs {}): {} {{{}}}", record.round, white, black, pgn.result(), record.end.reason).map_err(io)?;
+        writeln!(output, "Score of {}: {}", names, score).map_err(io)?;
+        if let Some(sprt) = config.sprt {
+            let (lower, upper) = sprt.bounds();
+            writeln!(output, "SPRT: llr {:.2} ({:.2}, {:.2}) [{}, {}]", sprt.llr(score), lower, upper, sprt.elo0, sprt.elo1).map_err(io)?;
+        }
+        if let Some(file) = &mut pgn_out {
+            writeln!(file, "{}", pgn).map_err(io)?;
+        }
+        Ok(())
+    })?;
+    writeln!(output, "Elo difference: {:.1} +/- {:.1}", score.elo(), score.elo_margin()).map_err(io)?;
+    if let Some(sprt) = config.sprt {
+        let verdict = match sprt.decision(&score) {
+            Some(true) => "H1 accepted",
+            Some(false) => "H0 accepted",
+            None => "no decision",
+        };
+        writeln!(output, "SPRT: {}", verdict).map_err(io)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_score_and_sprt() {
+        let even = Score { wins: 30, draws: 40, losses: 30 };
+        assert_eq!(even.ratio(), 0.5);
+        assert_eq!(even.elo(), 0.0);
+        assert_eq!(even.to_string(), "30 - 30 - 40 [0.500] 100");
+        let better = Score { wins: 45, draws: 30, losses: 25 };
+        assert!((better.elo() - 70.4).abs() < 0.1);
+        assert!(better.elo_margin() > 30.0 && better.elo_margin() < 80.0);
+        // Sweeps and lopsided scores stay finite
+        for score in [Score { wins: 10, draws: 0, losses: 0 }, Score { wins: 0, draws: 0, losses: 10 }, Score { wins: 9, draws: 1, losses: 0 }] {
+            assert!(score.elo().is_finite() && score.elo_margin().is_finite(), "{}", score);
+        }
+        assert_eq!(Score { wins: 0, draws: 10, losses: 0 }.elo_margin(), 0.0);
+
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert!((upper - 2.944).abs() < 0.001 && (lower + 2.944).abs() < 0.001);
+        assert_eq!(sprt.decision(&Score::default()), None);
+        assert_eq!(sprt.decision(&better), None);
+        assert_eq!(sprt.decision(&Score { wins: 450, draws: 300, losses: 250 }), Some(true));
+        assert_eq!(sprt.decision(&Score { wins: 250, draws: 300, losses: 450 }), Some(false));
+        assert!(parse_sprt(&args("elo0=5 elo1=0")).is_err());
+        assert_eq!(parse_sprt(&args("elo1=10 alpha=0.1")).unwrap(), Sprt { elo1: 10.0, alpha: 0.1, ..Sprt::default() });
+    }
+
+    #[test]
+    fn test_adjudication() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K2N w - - 0 1").unwrap();
+        let mut seen = HashMap::new();
+        assert_eq!(adjudicate(&board, &seen, 0, 400), Some(GameEnd::draw("Draw by insufficient material")));
+        board = Board::new();
+        assert_eq!(adjudicate(&board, &seen, 400, 400), Some(GameEnd::draw("Draw by adjudication, game too long")));
+        seen.insert(board.polyglot_key(), 3);
+        assert_eq!(adjudicate(&board, &seen, 8, 400), Some(GameEnd::draw("Draw by threefold repetition")));
+    }
+
+    #[test]
+    fn test_match() {
+        let mut config = MatchConfig::new([EngineConfig { name: "a".to_string(), ..EngineConfig::builtin() }, EngineConfig { name: "b".to_string(), ..EngineConfig::builtin() }]);
+        config.games = 4;
+        config.concurrency = 2;
+        config.timing = Timing::Depth(1);
+        config.max_plies = 20;
+        config.openings = vec![Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap()];
+        let mut rounds = vec![];
+        let score = run_match(&config, |record, _| {
+            rounds.push(record.round);
+            assert_eq!(record.board.moves.len(), 1, "{:?}", record.end);
+            Ok(())
+        })
+        .unwrap();
+        rounds.sort();
+        assert_eq!(rounds, vec![1, 2, 3, 4]);
+        // Whoever has White mates at once
+        assert_eq!(score, Score { wins: 2, draws: 0, losses: 2 });
+    }
+
+    #[test]
+    fn test_run() {
+        let mut output = vec![];
+        run(&args("--engine builtin name=new --engine name=old --games 2 --movetime 20 --maxplies 6 --sprt elo1=10"), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Finished game 2 (old vs new): 1/2-1/2 {Draw by adjudication, game too long}"), "{}", output);
+        assert!(output.contains("Score of new vs old: 0 - 0 - 2 [0.500] 2\nSPRT: llr 0.00 (-2.94, 2.94) [0, 10]"), "{}", output);
+        assert!(output.ends_with("Elo difference: 0.0 +/- 0.0\nSPRT: no decision\n"), "{}", output);
+        assert!(run(&args("--engine builtin"), &mut vec![]).is_err());
+        assert!(run(&args("--engine builtin --engine builtin --tc x"), &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::{
+    board::Board,
+    book::{Book, BookSelection},
+    coords::Coords,
+    pgn::Pgn,
+    pieces::{Color, Piece, PieceType},
+    search::{format_score, search, Limits},
+    variant,
+};
+
+const RESET: &str = "\x1b[0m";
+const LIGHT_SQUARE: &str = "\x1b[48;5;180m";
+const DARK_SQUARE: &str = "\x1b[48;5;137m";
+const LAST_MOVE: &str = "\x1b[48;5;143m";
+const WHITE_PIECE: &str = "\x1b[1;97m";
+const BLACK_PIECE: &str = "\x1b[1;30m";
+
+const HELP: &str = "\
+Enter moves in SAN (Nf3, exd5, O-O, e8=Q, N@f3) or UCI (g1f3, e7e8q). Commands:
+  moves [square]     list legal moves, optionally only those from a square
+  undo / redo        take back or replay a move (a full turn against the engine)
+  new                start a new game
+  variant [NAME]     show the rules played, or start a game of standard, King of the Hill,
+                     Three-check, Atomic, Antichess, Horde, Racing Kings or Crazyhouse
+  engine white|black|off
+                     choose the side the engine plays
+  go                 let the engine play the side to move
+  depth N / time S   search to a fixed depth or for S seconds
+  fen [FEN]          show the position as FEN, or set it up
+  pgn                show the game as PGN
+  flip               turn the board around
+  help / quit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderStyle {
+    /// ANSI colored squares, otherwise plain text
+    pub color: bool,
+    /// Chess glyphs, otherwise FEN letters
+    pub unicode: bool,
+    /// Black at the bottom
+    pub flipped: bool,
+}
+
+impl Default for RenderStyle {
+    fn default() -> Self {
+        RenderStyle { color: true, unicode: true, flipped: false }
+    }
+}
+
+fn piece_symbol(piece: Piece, style: RenderStyle) -> String {
+    match (piece.piece_type, style.unicode, style.color) {
+        (PieceType::Empty, _, _) => " ".to_string(),
+        // The filled glyphs read best on colored squares, with the piece color from the foreground
+        (_, true, true) => Piece::black(piece.piece_type).to_string(),
+        (_, true, false) => piece.to_string(),
+        (_, false, _) => piece.fen_char().to_string(),
+    }
+}
+
+/// The board as text with rank and file labels, highlighting the last move when colored
+pub fn render(board: &Board, style: RenderStyle) -> String {
+    let last_move = board.moves.last();
+    let order = |i: u8| if style.flipped { 7 - i } else { i };
+    let mut out = String::new();
+    for row in (0..8).map(order) {
+        out.push_str(&format!("{} ", 8 - row));
+        for col in (0..8).map(order) {
+            let coords = Coords::new(col, row);
+            let piece = board.get_piece(coords);
+            let symbol = piece_symbol(piece, style);
+            if style.color {
+                let background = if last_move.is_some_and(|mc| mc.from == coords || mc.to == coords) {
+                    LAST_MOVE
+                } else if (row + col) % 2 == 0 {
+                    LIGHT_SQUARE
+                } else {
+                    DARK_SQUARE
+                };
+                let foreground = if piece.color == Color::White { WHITE_PIECE } else { BLACK_PIECE };
+                out.push_str(&format!("{}{} {} {}", background, foreground, symbol, RESET));
+            } else if piece.piece_type == PieceType::Empty {
+                out.push_str(if (row + col) % 2 == 0 { " . " } else { " : " });
+            } else {
+                out.push_str(&format!(" {} ", symbol));
+            }
+        }
+        out.push('\n');
+    }
+    out.push_str("  ");
+    for col in (0..8).map(order) {
+        out.push_str(&format!(" {} ", (b'a' + col) as char));
+    }
+    out.push('\n');
+    if board.variant.has_pockets() {
+        out.push_str(&format!("In hand: [{}]\n", board.pocket_fen()));
+    }
+    out
+}
+
+/// A game in the terminal, reading moves and commands line by line
+pub struct Tui {
+    pub board: Board,
+    pub style: RenderStyle,
+    /// Side played by the engine
+    pub engine: Option<Color>,
+    pub limits: Limits,
+    /// Opening book the engine plays from while it has moves for the position
+    pub book: Option<Book>,
+}
+
+impl Default for Tui {
+    fn default() -> Self {
+        Tui::new()
+    }
+}
+
+impl Tui {
+    pub fn new() -> Self {
+        Tui { board: Board::new(), style: RenderStyle::default(), engine: None, limits: Limits::depth(3), book: None }
+    }
+
+    /// Plays until `quit` or the end of the input
+    pub fn run(&mut self, input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+        self.engine_turn(output)?;
+        self.show(output)?;
+        self.prompt(output)?;
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if !line.is_empty() && !self.command(line, output)? {
+                break;
+            }
+            self.prompt(output)?;
+        }
+        Ok(())
+    }
+
+    /// Handles one line of input, returning `false` to quit
+    fn command(&mut self, line: &str, output: &mut impl Write) -> io::Result<bool> {
+        let (command, argument) = match line.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (line, ""),
+        };
+        match command {
+            "quit" | "exit" | "q" => return Ok(false),
+            "help" | "?" => writeln!(output, "{}", HELP)?,
+            "moves" | "legal" => self.list_moves(argument, output)?,
+            "undo" => {
+                // Against the engine, take back its reply as well
+                let plies = if self.engine.is_some() && self.board.moves.len() > 1 { 2 } else { 1 };
+                for _ in 0..plies {
+                    self.board.undo_move();
+                }
+                self.show(output)?;
+            }
+            "redo" => {
+                // Against the engine, replay its reply as well, or let it answer when it had none
+                if self.board.redo_move().is_some() && self.engine == Some(self.board.turn) && self.board.redo_move().is_none() {
+                    self.engine_move(output)?;
+                }
+                self.show(output)?;
+            }
+            "new" => {
+                self.board = Board::new_variant(self.board.variant);
+                self.engine_turn(output)?;
+                self.show(output)?;
+            }
+            "variant" if argument.is_empty() => writeln!(output, "{}", self.board.variant.name())?,
+            "variant" => match variant::from_name(argument) {
+                Some(rules) => {
+                    self.board = Board::new_variant(rules);
+                    self.engine_turn(output)?;
+                    self.show(output)?;
+                }
+                None => writeln!(output, "Unknown variant {}", argument)?,
+            },
+            "engine" => {
+                self.engine = match argument {
+                    "white" | "w" => Some(Color::White),
+                    "black" | "b" => Some(Color::Black),
+                    "off" | "none" => None,
+                    _ => {
+                        writeln!(output, "Usage: engine white|black|off")?;
+                        return Ok(true);
+                    }
+                };
+                self.engine_turn(output)?;
+                self.show(output)?;
+            }
+            "go" => {
+                self.engine_move(output)?;
+                self.show(output)?;
+            }
+            "depth" => match argument.parse::<u32>() {
+                Ok(depth) if depth > 0 => self.limits = Limits::depth(depth),
+                _ => writeln!(output, "Usage: depth N")?,
+            },
+            "time" => match argument.parse::<f64>() {
+                Ok(secs) if secs > 0.0 => self.limits = Limits::time(Duration::from_secs_f64(secs)),
+                _ => writeln!(output, "Usage: time SECONDS")?,
+            },
+            "fen" if argument.is_empty() => writeln!(output, "{}", self.board.to_fen())?,
+            "fen" => match Board::from_fen_variant(argument, self.board.variant) {
+                Ok(board) => {
+                    self.board = board;
+                    self.engine_turn(output)?;
+                    self.show(output)?;
+                }
+                Err(e) => writeln!(output, "{}", e)?,
+            },
+            "pgn" => write!(output, "{}", Pgn::new(self.board.clone()))?,
+            "flip" => {
+                self.style.flipped = !self.style.flipped;
+                self.show(output)?;
+            }
+            "board" => self.show(output)?,
+            _ => self.user_move(line, output)?,
+        }
+        Ok(true)
+    }
+
+    fn list_moves(&self, square: &str, output: &mut impl Write) -> io::Result<()> {
+        let from = match square {
+            "" => None,
+            square => match square.parse::<Coords>().ok() {
+                Some(from) => Some(from),
+                None => return writeln!(output, "Not a square: {}", square),
+            },
+        };
+        let moves = self
+            .board
+            .legal_moves()
+            .iter()
+            .filter(|mc| from.is_none_or(|from| mc.from == from))
+            .map(|mc| self.board.san(mc))
+            .collect::<Vec<_>>();
+        if moves.is_empty() {
+            writeln!(output, "No legal moves")
+        } else {
+            writeln!(output, "{}", moves.join(" "))
+        }
+    }
+
+    fn user_move(&mut self, text: &str, output: &mut impl Write) -> io::Result<()> {
+        if self.board.outcome().is_some() {
+            return writeln!(output, "The game is over, type `new` to play again");
+        }
+        let mc = match self.board.parse_move(text) {
+            Some(mc) => mc,
+            None => return writeln!(output, "Illegal or unknown move: {} (type `help` for commands)", text),
+        };
+        self.board.apply(&mc);
+        self.board.undone.clear();
+        self.engine_turn(output)?;
+        self.show(output)
+    }
+
+    /// Lets the engine reply when it plays the side to move
+    fn engine_turn(&mut self, output: &mut impl Write) -> io::Result<()> {
+        if self.engine == Some(self.board.turn) {
+            self.engine_move(output)?;
+        }
+        Ok(())
+    }
+
+    fn engine_move(&mut self, output: &mut impl Write) -> io::Result<()> {
+        if self.board.outcome().is_some() {
+            return Ok(());
+        }
+        if let Some(mc) = self.book.as_ref().and_then(|book| book.choose(&self.board, BookSelection::Weighted)) {
+            writeln!(output, "Engine plays {} (book)", self.board.san(&mc))?;
+            self.board.apply(&mc);
+            self.board.undone.clear();
+            return Ok(());
+        }
+        let result = search(&self.board, self.limits);
+        if let Some(mc) = result.best_move {
+            writeln!(output, "Engine plays {} ({}, depth {})", self.board.san(&mc), format_score(result.score), result.depth)?;
+            self.board.apply(&mc);
+            self.board.undone.clear();
+        }
+        Ok(())
+    }
+
+    fn show(&self, output: &mut impl Write) -> io::Result<()> {
+        writeln!(output)?;
+        write!(output, "{}", render(&self.board, self.style))?;
+        if let Some(outcome) = self.board.outcome() {
+            writeln!(output, "{} ({})", outcome, outcome.result())?;
+        } else if self.board.in_check(self.board.turn) {
+            writeln!(output, "Check!")?;
+        }
+        Ok(())
+    }
+
+    fn prompt(&self, output: &mut impl Write) -> io::Result<()> {
+        let side = match self.board.turn {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        write!(output, "{} {}> ", self.board.fullmove_number, side)?;
+        output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(tui: &mut Tui, input: &str) -> String {
+        let mut output = vec![];
+        tui.run(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let plain = RenderStyle { color: false, unicode: false, flipped: false };
+        let text = render(&Board::new(), plain);
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "8  r  n  b  q  k  b  n  r ");
+        assert_eq!(lines[4], "4  .  :  .  :  .  :  .  : ");
+        assert_eq!(lines[8], "   a  b  c  d  e  f  g  h ");
+        let flipped = render(&Board::new(), RenderStyle { flipped: true, ..plain });
+        assert!(flipped.starts_with("1  R  N  B  K  Q  B  N  R "));
+        assert!(render(&Board::new(), RenderStyle::default()).contains(LIGHT_SQUARE));
+    }
+
+    #[test]
+    fn test_moves_and_commands() {
+        let mut tui = Tui::new();
+        let output = play(&mut tui, "e4\ne7e5\nmoves g1\nNc3\nundo\nfoo\nquit\nd4\n");
+        assert!(output.contains("Ne2 Nh3 Nf3"));
+        assert!(output.contains("Illegal or unknown move: foo"));
+        assert_eq!(tui.board.san_moves(), vec!["e4", "e5"]);
+    }
+
+    #[test]
+    fn test_engine_reply() {
+        let mut tui = Tui::new();
+        tui.limits = Limits::depth(1);
+        let output = play(&mut tui, "engine black\ne4\nundo\n");
+        assert!(output.contains("Engine plays"));
+        // Undo against the engine takes back a full turn, and redo plays it again
+        assert!(tui.board.moves.is_empty());
+        let reply = output.lines().find(|line| line.contains("Engine plays")).unwrap().to_string();
+        play(&mut tui, "redo
+");
+        assert_eq!(tui.board.moves.len(), 2);
+        assert_eq!(tui.board.turn, Color::White);
+        assert!(reply.contains(&format!("Engine plays {} ", tui.board.san_moves()[1])));
+
+        let mut tui = Tui::new();
+        tui.board = Board::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        tui.engine = Some(Color::White);
+        let output = play(&mut tui, "");
+        assert!(output.contains("Engine plays Ra8# (#1, depth 1)"));
+        assert!(output.contains("White wins by checkmate (1-0)"));
+    }
+
+    #[test]
+    fn test_variant() {
+        let mut tui = Tui::new();
+        let output = play(&mut tui, "variant king of the hill\nvariant\ne3\na6\nKe2\na5\nKd3\na4\nKe4\nnew\nvariant xiangqi\n");
+        assert!(output.contains("King of the Hill\n"));
+        assert!(output.contains("White wins, king reached the centre (1-0)"));
+        assert!(output.contains("Unknown variant xiangqi"));
+        assert!(tui.board.moves.is_empty());
+        assert_eq!(tui.board.variant.name(), "King of the Hill");
+    }
+
+    #[test]
+    fn test_engine_book() {
+        let mut tui = Tui::new();
+        tui.book = Some(Book::from_games(&Pgn::parse_all("[Result \"0-1\"]\n\n1. e4 c5 0-1\n").unwrap(), 2));
+        tui.engine = Some(Color::Black);
+        tui.limits = Limits::depth(1);
+        let output = play(&mut tui, "e4\n");
+        assert!(output.contains("Engine plays c5 (book)"));
+        // Out of book the engine searches
+        let output = play(&mut tui, "Nf3\n");
+        assert!(output.contains("Engine plays") && !output.contains("Engine plays c5"));
+        assert_eq!(tui.board.moves.len(), 4);
+    }
+}
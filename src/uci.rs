@@ -0,0 +1,336 @@
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::{
+    board::Board,
+    coords::MoveCoords,
+    pieces::Color,
+    search::{mate_in, search_lines, Limits, SearchResult},
+    syzygy::Tablebase,
+};
+
+const NAME: &str = "chess-engine";
+const MAX_MULTI_PV: usize = 64;
+
+/// Universal Chess Interface front-end, answering a GUI over stdin and stdout
+pub struct Uci<W: Write + Send + 'static> {
+    pub board: Board,
+    pub tablebase: Option<Arc<Tablebase>>,
+    /// Set by the `UCI_Chess960` option, writing castling as the king taking its rook
+    pub chess960: bool,
+    /// Lines searched and reported at once, set by the `MultiPV` option
+    pub multi_pv: usize,
+    output: Arc<Mutex<W>>,
+    /// The running search and its stop flag
+    searching: Option<(JoinHandle<()>, Arc<AtomicBool>)>,
+}
+
+impl<W: Write + Send + 'static> Uci<W> {
+    pub fn new(output: W) -> Self {
+        Uci { board: Board::new(), tablebase: None, chess960: false, multi_pv: 1, output: Arc::new(Mutex::new(output)), searching: None }
+    }
+
+    /// Handles commands until `quit` or the end of `input`
+    pub fn run(&mut self, input: impl BufRead) -> io::Result<()> {
+        for line in input.lines() {
+            if !self.command(line?.trim())? {
+                break;
+            }
+        }
+        self.stop();
+        Ok(())
+    }
+
+    fn send(&self, line: &str) -> io::Result<()> {
+        send(&self.output, line)
+    }
+
+    /// Runs one command, returning false on `quit`
+    pub fn command(&mut self, line: &str) -> io::Result<bool> {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("uci") => {
+                self.send(&format!("id name {}", NAME))?;
+                self.send("option name SyzygyPath type string default <empty>")?;
+                self.send("option name UCI_Chess960 type check default false")?;
+                self.send(&format!("option name MultiPV type spin default 1 min 1 max {}", MAX_MULTI_PV))?;
+                self.send("uciok")?;
+            }
+            Some("isready") => self.send("readyok")?,
+            Some("ucinewgame") => {
+                self.stop();
+                self.board = Board::new();
+            }
+            Some("setoption") => self.set_option(&words.collect::<Vec<_>>())?,
+            Some("position") => {
+                self.stop();
+                if let Err(e) = self.position(&words.collect::<Vec<_>>()) {
+                    self.send(&format!("info string {}", e))?;
+                }
+            }
+            Some("go") => {
+                self.stop();
+                self.go(&words.collect::<Vec<_>>());
+            }
+            Some("stop") => self.stop(),
+            Some("quit") => return Ok(false),
+            Some(command) => log::debug!("Ignoring UCI command {}", command),
+            None => (),
+        }
+        Ok(true)
+    }
+
+    fn set_option(&mut self, words: &[&str]) -> io::Result<()> {
+        if words.first() != Some(&"name") {
+            log::debug!("Ignoring setoption without a name");
+            return Ok(());
+        }
+        let value_at = words.iter().position(|word| *word == "value");
+        let name = words[1..value_at.unwrap_or(words.len())].join(" ");
+        let value = value_at.map(|i| words[i + 1..].join(" ")).unwrap_or_default();
+        if name.eq_ignore_ascii_case("UCI_Chess960") {
+            self.chess960 = value == "true";
+            return Ok(());
+        }
+        if name.eq_ignore_ascii_case("MultiPV") {
+            self.multi_pv = value.parse::<usize>().unwrap_or(1).clamp(1, MAX_MULTI_PV);
+            return Ok(());
+        }
+        if !name.eq_ignore_ascii_case("SyzygyPath") {
+            log::debug!("Ignoring unknown option {}", name);
+            return Ok(());
+        }
+        self.stop();
+        if value.is_empty() || value == "<empty>" {
+            self.tablebase = None;
+            return Ok(());
+        }
+        match Tablebase::open(&value) {
+            Ok(tablebase) => {
+                self.send(&format!("info string found {} tablebase files, up to {} pieces", tablebase.len(), tablebase.max_pieces()))?;
+                self.tablebase = Some(Arc::new(tablebase));
+            }
+            Err(e) => {
+                self.send(&format!("info string {}", e))?;
+                self.tablebase = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// `startpos` or `fen <FEN>`, then optionally `moves` in UCI notation
+    fn position(&mut self, words: &[&str]) -> Result<(), String> {
+        let moves_at = words.iter().position(|word| *word == "moves").unwrap_or(words.len());
+        let mut board = match words.first() {
+            Some(&"startpos") => Board::new(),
+            Some(&"fen") => Board::from_fen(&words[1..moves_at].join(" "))?,
+            _ => return Err("position needs startpos or fen".to_string()),
+        };
+        for uci in words.iter().skip(moves_at + 1) {
+            let mc = board.parse_uci(uci).ok_or_else(|| format!("illegal move {}", uci))?;
+            board.apply(&mc);
+        }
+        self.board = board;
+        Ok(())
+    }
+
+    /// Starts searching the current position in the background, sending `info` for each depth and `bestmove` when done
+    fn go(&mut self, words: &[&str]) {
+        let limits = limits(words, self.board.turn);
+        let board = self.board.position();
+        let tablebase = self.tablebase.clone();
+        let output = self.output.clone();
+        let chess960 = self.chess960;
+        let multi_pv = self.multi_pv;
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopped = stop.clone();
+        let handle = std::thread::spawn(move || {
+            let lines = search_lines(&board, limits, multi_pv, tablebase.as_deref(), Some(&stopped), |lines| {
+                for (i, line) in lines.iter().enumerate() {
+                    if let Err(e) = send(&output, &info(&board, line, i + 1, chess960)) {
+                        log::warn!("Could not send search info: {}", e);
+                    }
+                }
+            });
+            let best = lines.first().and_then(|line| line.best_move).map_or("0000".to_string(), |mc| move_text(&board, &mc, chess960));
+            if let Err(e) = send(&output, &format!("bestmove {}", best)) {
+                log::warn!("Could not send the search result: {}", e);
+            }
+        });
+        self.searching = Some((handle, stop));
+    }
+
+    /// Ends the running search, waiting for its `bestmove`
+    fn stop(&mut self) {
+        if let Some((handle, stop)) = self.searching.take() {
+            stop.store(true, Ordering::Relaxed);
+            let _ = handle.join();
+        }
+    }
+}
+
+fn send<W: Write>(output: &Mutex<W>, line: &str) -> io::Result<()> {
+    let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+    writeln!(output, "{}", line)?;
+    output.flush()
+}
+
+/// Search limits of a `go` command, sharing out the clock time when given one
+fn limits(words: &[&str], turn: Color) -> Limits {
+    let value = |name: &str| words.iter().position(|word| *word == name).and_then(|i| words.get(i + 1)).and_then(|value| value.parse::<u64>().ok());
+    if let Some(depth) = value("depth") {
+        return Limits::depth(depth as u32);
+    }
+    if let Some(time) = value("movetime") {
+        return Limits::time(Duration::from_millis(time));
+    }
+    let (time, increment) = match turn {
+        Color::White => (value("wtime"), value("winc")),
+        Color::Black => (value("btime"), value("binc")),
+    };
+    match time {
+        Some(time) => {
+            let moves = value("movestogo").unwrap_or(30).max(1);
+            // Leave some time to spare so the clock never runs out
+            let share = time / moves + increment.unwrap_or(0) * 3 / 4;
+            Limits::time(Duration::from_millis(share.min(time.saturating_sub(50)).max(1)))
+        }
+        // `infinite` and no limits at all search until `stop`
+        None => Limits::infinite(),
+    }
+}
+
+fn move_text(board: &Board, mc: &MoveCoords, chess960: bool) -> String {
+    if chess960 {
+        board.uci_chess960(mc)
+    } else {
+        mc.uci()
+    }
+}
+
+/// The `info` line for the `multi_pv`th best line of a finished depth
+fn info(board: &Board, result: &SearchResult, multi_pv: usize, chess960: bool) -> String {
+    let score = match mate_in(result.score) {
+        Some(moves) => format!("mate {}", moves),
+        None => format!("cp {}", result.score),
+    };
+    let mut board = board.clone();
+    let pv = result
+        .pv
+        .iter()
+        .map(|mc| {
+            let text = move_text(&board, mc, chess960);
+            board.apply(mc);
+            text
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    format!("info depth {} multipv {} score {} nodes {} tbhits {} pv {}", result.depth, multi_pv, score, result.nodes, result.tb_hits, pv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output shared with the test while the front-end writes to it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(commands: &str) -> String {
+        let output = Shared::default();
+        let mut uci = Uci::new(output.clone());
+        uci.run(commands.as_bytes()).unwrap();
+        let text = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        text
+    }
+
+    #[test]
+    fn test_handshake() {
+        let output = run("uci\nisready\nquit\n");
+        assert!(output.contains("option name SyzygyPath type string"));
+        assert!(output.ends_with("uciok\nreadyok\n"));
+    }
+
+    #[test]
+    fn test_go() {
+        let output = run("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1\ngo depth 3\n");
+        assert!(output.contains("score mate 1"));
+        assert!(output.ends_with("bestmove a1a8\n"));
+
+        let output = run("position startpos moves e2e4 e7e5 g1f3\ngo depth 1\n");
+        let best = output.lines().last().unwrap().strip_prefix("bestmove ").unwrap();
+        let mut board = Board::new();
+        for uci in ["e2e4", "e7e5", "g1f3"] {
+            board.apply(&board.parse_uci(uci).unwrap());
+        }
+        assert!(board.parse_uci(best).is_some());
+
+        // Searches without limits run until told to stop
+        let output = run("position startpos\ngo infinite\nstop\n");
+        assert!(output.lines().last().unwrap().starts_with("bestmove "));
+        assert!(run("position startpos moves e2e5\n").contains("info string illegal move e2e5"));
+    }
+
+    #[test]
+    fn test_chess960() {
+        // Castling given as the king taking its rook
+        let position = "position fen 3k4/8/8/8/8/8/8/RK5R w HA - 0 1 moves b1a1\n";
+        assert!(!run(position).contains("info string"));
+        let output = run("setoption name UCI_Chess960 value true\nposition fen 3k4/8/8/8/8/8/8/RK5R w HA - 0 1\ngo depth 1\n");
+        let best = output.lines().last().unwrap().strip_prefix("bestmove ").unwrap().to_string();
+        let board = Board::from_fen("3k4/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+        let mc = board.parse_uci(&best).unwrap();
+        assert_eq!(board.uci_chess960(&mc), best);
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        let output = run("setoption name MultiPV value 3\nposition startpos\ngo depth 2\n");
+        for depth in 1..=2 {
+            for line in 1..=3 {
+                assert!(output.contains(&format!("info depth {} multipv {} score cp ", depth, line)), "{}", output);
+            }
+        }
+        assert!(!output.contains("multipv 4"));
+        let pvs = output.lines().filter(|line| line.starts_with("info depth 2")).map(|line| line.split(" pv ").nth(1).unwrap().split(' ').next().unwrap()).collect::<Vec<_>>();
+        assert_eq!(pvs.len(), 3);
+        assert!(output.ends_with(&format!("bestmove {}\n", pvs[0])));
+    }
+
+    #[test]
+    fn test_malformed_setoption() {
+        // Lines without an option name are ignored rather than ending the engine
+        assert!(run("setoption value 3\nsetoption\nsetoption name\nisready\n").ends_with("readyok\n"));
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(limits(&["depth", "5"], Color::White), Limits::depth(5));
+        assert_eq!(limits(&["movetime", "200"], Color::Black), Limits::time(Duration::from_millis(200)));
+        assert_eq!(limits(&["wtime", "60000", "btime", "3000", "binc", "1000"], Color::Black), Limits::time(Duration::from_millis(850)));
+        assert_eq!(limits(&["wtime", "60000", "movestogo", "10"], Color::White), Limits::time(Duration::from_millis(6000)));
+    }
+
+    #[test]
+    fn test_syzygy_path() {
+        let (dir, _) = crate::syzygy::tests::write_tables("uci");
+        let output = run(&format!("setoption name SyzygyPath value {}\nposition fen 8/8/8/8/8/2k5/8/K6Q w - - 0 1\ngo depth 5\n", dir.display()));
+        assert!(output.contains("info string found 3 tablebase files, up to 3 pieces"));
+        assert!(output.contains(&format!("score cp {} ", crate::search::TB_WIN)));
+        assert!(output.contains("tbhits 1"));
+        assert!(run("setoption name SyzygyPath value no/such/tablebases\n").contains("info string could not read tablebase directory"));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
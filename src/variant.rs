@@ -0,0 +1,505 @@
+use crate::{
+    board::{Board, Outcome},
+    crazyhouse::Crazyhouse,
+    chess_move::{Move, MoveList},
+    coords::Coords,
+    fen::STARTING_FEN,
+    pieces::{Color, Piece, PieceType},
+};
+
+/// Rules a game is played by, as hooks into the standard move generator and game end checks
+pub trait Variant: Sync {
+    /// Name as in the PGN `Variant` tag
+    fn name(&self) -> &'static str;
+
+    fn start_fen(&self) -> &'static str {
+        STARTING_FEN
+    }
+
+    /// Checks a position read from FEN has the pieces the rules need
+    fn validate(&self, board: &Board) -> Result<(), &'static str> {
+        one_king_each(board)?;
+        if board.in_check(board.turn.opposite()) {
+            return Err("Side not to move is in check");
+        }
+        Ok(())
+    }
+
+    /// Whether a pawn of `color` on row `y` may move two squares
+    fn can_double_push(&self, color: Color, y: u8) -> bool {
+        match color {
+            Color::White => y == 6,
+            Color::Black => y == 1,
+        }
+    }
+
+    /// Pieces a pawn may promote to
+    fn promotions(&self) -> &'static [PieceType] {
+        &[PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight]
+    }
+
+    /// Whether the side to move in `before` may play `mv`, reaching `after`. Normally it may not leave its king in check
+    fn is_legal(&self, before: &Board, _mv: Move, after: &Board) -> bool {
+        !after.in_check(before.turn)
+    }
+
+    /// Narrows down the moves which passed `is_legal`, as when captures are forced
+    fn filter_moves(&self, _board: &Board, _list: &mut MoveList) {}
+
+    /// Side effects of `mv`, which took `captured`, once its pieces have moved and the turn has passed
+    fn after_move(&self, _board: &mut Board, _mv: Move, _captured: Piece) {}
+
+    /// Whether pieces in hand may be dropped on the board
+    fn has_pockets(&self) -> bool {
+        false
+    }
+
+    /// Checks either side needs to give to win, if giving check wins
+    fn check_limit(&self) -> Option<u8> {
+        None
+    }
+
+    /// A win or draw by the rules of the variant, checked before looking for legal moves
+    fn outcome(&self, _board: &Board) -> Option<Outcome> {
+        None
+    }
+
+    /// Result when the side to move has no legal moves
+    fn no_moves(&self, board: &Board) -> Outcome {
+        if board.in_check(board.turn) {
+            Outcome::Checkmate { winner: board.turn.opposite() }
+        } else {
+            Outcome::Stalemate
+        }
+    }
+
+    /// Adjusts the standard evaluation `score`, in centipawns for White, to what matters in the variant
+    fn evaluate_white(&self, _board: &Board, score: i32) -> i32 {
+        score
+    }
+}
+
+fn count(board: &Board, piece: Piece) -> usize {
+    board.state.iter().flatten().filter(|p| **p == piece).count()
+}
+
+fn one_king_each(board: &Board) -> Result<(), &'static str> {
+    for color in [Color::White, Color::Black] {
+        if count(board, Piece { piece_type: PieceType::King, color }) != 1 {
+            return Err("FEN needs exactly one king per side");
+        }
+    }
+    Ok(())
+}
+
+fn has_pieces(board: &Board, color: Color) -> bool {
+    board.state.iter().flatten().any(|p| p.piece_type != PieceType::Empty && p.color == color)
+}
+
+fn win(winner: Color, reason: &'static str) -> Option<Outcome> {
+    Some(Outcome::Variant { winner: Some(winner), reason })
+}
+
+pub struct Standard;
+
+impl Variant for Standard {
+    fn name(&self) -> &'static str {
+        "Standard"
+    }
+}
+
+/// Bringing the king to one of the four centre squares wins
+pub struct KingOfTheHill;
+
+/// Steps from `square` to the nearest centre square
+fn centre_distance(square: Coords) -> i32 {
+    (square.x as i32 * 2 - 7).abs().max((square.y as i32 * 2 - 7).abs()) / 2
+}
+
+impl Variant for KingOfTheHill {
+    fn name(&self) -> &'static str {
+        "King of the Hill"
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        let color = [Color::White, Color::Black].into_iter().find(|color| board.king_position(*color).is_some_and(|king| centre_distance(king) == 0))?;
+        win(color, "king reached the centre")
+    }
+
+    fn evaluate_white(&self, board: &Board, score: i32) -> i32 {
+        let closeness = |color| board.king_position(color).map_or(0, |king| 3 - centre_distance(king));
+        score + 30 * (closeness(Color::White) - closeness(Color::Black))
+    }
+}
+
+/// Giving check for the third time wins
+pub struct ThreeCheck;
+
+impl Variant for ThreeCheck {
+    fn name(&self) -> &'static str {
+        "Three-check"
+    }
+
+    fn after_move(&self, board: &mut Board, _mv: Move, _captured: Piece) {
+        if board.in_check(board.turn) {
+            board.checks[board.turn.opposite() as usize] += 1;
+        }
+    }
+
+    fn check_limit(&self) -> Option<u8> {
+        Some(3)
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        let color = [Color::White, Color::Black].into_iter().find(|color| board.checks[*color as usize] >= 3)?;
+        win(color, "three checks")
+    }
+
+    fn evaluate_white(&self, board: &Board, score: i32) -> i32 {
+        score + 150 * (board.checks[0] as i32 - board.checks[1] as i32)
+    }
+}
+
+/// Captures explode, taking out the capturing piece and every piece but pawns next to the square
+pub struct Atomic;
+
+fn kings_touch(board: &Board) -> bool {
+    match (board.king_position(Color::White), board.king_position(Color::Black)) {
+        (Some(white), Some(black)) => white.x.abs_diff(black.x) <= 1 && white.y.abs_diff(black.y) <= 1,
+        _ => false,
+    }
+}
+
+impl Variant for Atomic {
+    fn name(&self) -> &'static str {
+        "Atomic"
+    }
+
+    fn validate(&self, board: &Board) -> Result<(), &'static str> {
+        one_king_each(board)?;
+        if board.in_check(board.turn.opposite()) && !kings_touch(board) {
+            return Err("Side not to move is in check");
+        }
+        Ok(())
+    }
+
+    /// Kings can't capture, as they'd blow up with it. Exploding the enemy king wins even from check,
+    /// and kings next to each other can't give check as neither may take the other
+    fn is_legal(&self, before: &Board, mv: Move, after: &Board) -> bool {
+        let us = before.turn;
+        if mv.is_capture() && before.get_piece(mv.from()).piece_type == PieceType::King {
+            return false;
+        }
+        if after.king_position(us).is_none() {
+            return false;
+        }
+        after.king_position(us.opposite()).is_none() || kings_touch(after) || !after.in_check(us)
+    }
+
+    fn after_move(&self, board: &mut Board, mv: Move, _captured: Piece) {
+        if !mv.is_capture() {
+            return;
+        }
+        let to = mv.to();
+        board.state[to.y as usize][to.x as usize] = Piece::empty();
+        for square in Coords::all().filter(|square| square.x.abs_diff(to.x) <= 1 && square.y.abs_diff(to.y) <= 1) {
+            if !matches!(board.get_piece(square).piece_type, PieceType::Pawn | PieceType::Empty) {
+                board.state[square.y as usize][square.x as usize] = Piece::empty();
+                board.castling.touch(square);
+            }
+        }
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        let color = [Color::White, Color::Black].into_iter().find(|color| board.king_position(*color).is_none())?;
+        win(color.opposite(), "king exploded")
+    }
+
+    fn no_moves(&self, board: &Board) -> Outcome {
+        if board.in_check(board.turn) && !kings_touch(board) {
+            Outcome::Checkmate { winner: board.turn.opposite() }
+        } else {
+            Outcome::Stalemate
+        }
+    }
+}
+
+/// Captures are forced and losing every piece, or having no moves, wins. The king is just another piece
+pub struct Antichess;
+
+impl Variant for Antichess {
+    fn name(&self) -> &'static str {
+        "Antichess"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1"
+    }
+
+    fn validate(&self, _board: &Board) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn promotions(&self) -> &'static [PieceType] {
+        &[PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight, PieceType::King]
+    }
+
+    fn is_legal(&self, _before: &Board, _mv: Move, _after: &Board) -> bool {
+        true
+    }
+
+    fn filter_moves(&self, _board: &Board, list: &mut MoveList) {
+        list.retain(|mv| !mv.is_king_side_castle() && !mv.is_queen_side_castle());
+        if list.iter().any(|mv| mv.is_capture()) {
+            list.retain(|mv| mv.is_capture());
+        }
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        let color = [board.turn, board.turn.opposite()].into_iter().find(|color| !has_pieces(board, *color))?;
+        win(color, "all pieces lost")
+    }
+
+    fn no_moves(&self, board: &Board) -> Outcome {
+        Outcome::Variant { winner: Some(board.turn), reason: "no moves left" }
+    }
+
+    fn evaluate_white(&self, _board: &Board, score: i32) -> i32 {
+        -score
+    }
+}
+
+/// White's 36 pawns and no king against a normal army. Black wins by taking every pawn
+pub struct Horde;
+
+impl Variant for Horde {
+    fn name(&self) -> &'static str {
+        "Horde"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1"
+    }
+
+    fn validate(&self, board: &Board) -> Result<(), &'static str> {
+        if count(board, Piece::black(PieceType::King)) != 1 || count(board, Piece::white(PieceType::King)) != 0 {
+            return Err("Horde needs a black king and no white one");
+        }
+        if board.in_check(board.turn.opposite()) {
+            return Err("Side not to move is in check");
+        }
+        Ok(())
+    }
+
+    /// White's pawns on the first rank may move two squares too
+    fn can_double_push(&self, color: Color, y: u8) -> bool {
+        match color {
+            Color::White => y == 6 || y == 7,
+            Color::Black => y == 1,
+        }
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        if has_pieces(board, Color::White) {
+            return None;
+        }
+        win(Color::Black, "the horde is destroyed")
+    }
+}
+
+/// Both sides start on the first two ranks and race their kings to the eighth, giving check being illegal.
+/// Black reaching it straight after White still draws
+pub struct RacingKings;
+
+impl Variant for RacingKings {
+    fn name(&self) -> &'static str {
+        "Racing Kings"
+    }
+
+    fn start_fen(&self) -> &'static str {
+        "8/8/8/8/8/8/krbnNBRK/qrbnNBRQ w - - 0 1"
+    }
+
+    fn validate(&self, board: &Board) -> Result<(), &'static str> {
+        Standard.validate(board)?;
+        if board.in_check(board.turn) {
+            return Err("Neither king may be in check in Racing Kings");
+        }
+        Ok(())
+    }
+
+    fn is_legal(&self, before: &Board, _mv: Move, after: &Board) -> bool {
+        !after.in_check(before.turn) && !after.in_check(before.turn.opposite())
+    }
+
+    fn outcome(&self, board: &Board) -> Option<Outcome> {
+        let home = |color| board.king_position(color).is_some_and(|king| king.y == 0);
+        match (home(Color::White), home(Color::Black)) {
+            (true, true) => Some(Outcome::Variant { winner: None, reason: "both kings reached the eighth rank" }),
+            (false, true) => win(Color::Black, "king reached the eighth rank"),
+            (true, false) => {
+                // Black gets one more move to draw by reaching the eighth rank as well
+                let catch_up = board.turn == Color::Black && board.legal_moves().iter().any(|mc| mc.piece.piece_type == PieceType::King && mc.to.y == 0);
+                if catch_up {
+                    None
+                } else {
+                    win(Color::White, "king reached the eighth rank")
+                }
+            }
+            (false, false) => None,
+        }
+    }
+
+    fn evaluate_white(&self, board: &Board, score: i32) -> i32 {
+        let rank = |color| board.king_position(color).map_or(0, |king| 7 - king.y as i32);
+        score + 60 * (rank(Color::White) - rank(Color::Black))
+    }
+}
+
+/// Every variant which can be played on its own, standard chess first. Bughouse needs a partner board, so it is left out
+pub static VARIANTS: [&dyn Variant; 8] = [&Standard, &KingOfTheHill, &ThreeCheck, &Atomic, &Antichess, &Horde, &RacingKings, &Crazyhouse];
+
+/// Finds a variant by name, ignoring case, spaces and dashes so `king-of-the-hill` and `racingkings` work too
+pub fn from_name(name: &str) -> Option<&'static dyn Variant> {
+    let key = |name: &str| name.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_ascii_lowercase();
+    let name = match key(name).as_str() {
+        "3check" => "threecheck".to_string(),
+        "koth" => "kingofthehill".to_string(),
+        name => name.to_string(),
+    };
+    VARIANTS.iter().copied().find(|variant| key(variant.name()) == name)
+}
+
+impl Board {
+    /// Start position of `variant`
+    pub fn new_variant(variant: &'static dyn Variant) -> Board {
+        let mut board = Board::from_fen_variant(variant.start_fen(), variant).expect("start positions are valid");
+        if variant.start_fen() == STARTING_FEN {
+            board.start_fen = None;
+        }
+        board
+    }
+
+    /// Whether the game is played by the standard rules, which Chess960 also follows
+    pub fn is_standard(&self) -> bool {
+        self.variant.name() == Standard.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for uci in moves {
+            let mc = board.parse_uci(uci).unwrap_or_else(|| panic!("{} is illegal in {}", uci, board.to_fen()));
+            board.apply(&mc);
+        }
+    }
+
+    #[test]
+    fn test_names() {
+        for variant in VARIANTS {
+            assert_eq!(from_name(variant.name()).unwrap().name(), variant.name());
+            let board = Board::new_variant(variant);
+            assert_eq!(board.variant.name(), variant.name());
+            assert!(board.outcome().is_none());
+        }
+        assert_eq!(from_name("king-of-the-hill").unwrap().name(), "King of the Hill");
+        assert_eq!(from_name("3check").unwrap().name(), "Three-check");
+        assert!(from_name("shogi").is_none());
+        assert!(from_name("bughouse").is_none());
+        assert_eq!(Board::new_variant(&Horde).perft(1), 8);
+        assert_eq!(Board::new_variant(&RacingKings).perft(1), 21);
+        assert_eq!(Board::new_variant(&Antichess).perft(2), 400);
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let mut board = Board::from_fen_variant("4k3/8/8/8/8/4K3/8/8 w - - 0 1", &KingOfTheHill).unwrap();
+        play(&mut board, &["e3e4"]);
+        assert_eq!(board.outcome(), Some(Outcome::Variant { winner: Some(Color::White), reason: "king reached the centre" }));
+        assert_eq!(board.outcome().unwrap().to_string(), "White wins, king reached the centre");
+        let board = Board::from_fen_variant("4k3/8/8/8/8/4K3/8/8 w - - 0 1", &KingOfTheHill).unwrap();
+        assert!(board.king_position(Color::White).is_some_and(|king| centre_distance(king) == 1));
+        let result = crate::search::search(&board, crate::search::Limits::depth(2));
+        assert_eq!(crate::search::mate_in(result.score), Some(1));
+    }
+
+    #[test]
+    fn test_three_check() {
+        let mut board = Board::new_variant(&ThreeCheck);
+        assert_eq!(board.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 3+3 0 1");
+        play(&mut board, &["e2e4", "e7e5", "d1h5", "b8c6", "h5f7", "e8f7"]);
+        assert_eq!(board.checks, [1, 0]);
+        assert_eq!(board.to_fen(), "r1bq1bnr/pppp1kpp/2n5/4p3/4P3/8/PPPP1PPP/RNB1KBNR w KQ - 2+3 0 4");
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/8/4K2R w - - 1+3 0 1", &ThreeCheck).unwrap();
+        assert_eq!(board.checks, [2, 0]);
+        let mut after = board.clone();
+        play(&mut after, &["h1h8"]);
+        assert_eq!(after.outcome().unwrap().winner(), Some(Color::White));
+        assert_eq!(after.replay(0).checks, [2, 0]);
+    }
+
+    #[test]
+    fn test_atomic() {
+        // Taking on d7 blows up the king next to it
+        let mut board = Board::from_fen_variant("3qk3/3p4/8/8/8/8/8/3QK3 w - - 0 1", &Atomic).unwrap();
+        play(&mut board, &["d1d7"]);
+        assert_eq!(board.to_fen(), "8/8/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(board.outcome().unwrap().winner(), Some(Color::White));
+        // Kings can't capture, nor may a capture blow up one's own king
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/3p4/3QK3 w - - 0 1", &Atomic).unwrap();
+        assert!(board.parse_uci("e1d2").is_none());
+        assert!(board.parse_uci("d1d2").is_none());
+        // Next to the enemy king there's no check
+        let board = Board::from_fen_variant("8/8/8/8/8/3k4/3K4/7r w - - 0 1", &Atomic).unwrap();
+        assert!(board.parse_uci("d2c1").is_none());
+        assert!(board.parse_uci("d2c2").is_some());
+    }
+
+    #[test]
+    fn test_antichess() {
+        let mut board = Board::new_variant(&Antichess);
+        play(&mut board, &["e2e3", "b7b5"]);
+        // Taking is forced
+        assert_eq!(board.legal_moves().iter().map(|mc| mc.uci()).collect::<Vec<_>>(), ["f1b5"]);
+        let board = Board::from_fen_variant("8/8/8/8/8/8/1p6/8 b - - 0 1", &Antichess).unwrap();
+        assert!(board.legal_moves().iter().any(|mc| mc.promotion == Some(PieceType::King)));
+        let mut board = Board::from_fen_variant("8/8/8/8/8/8/p7/1R6 b - - 0 1", &Antichess).unwrap();
+        play(&mut board, &["a2b1q"]);
+        assert_eq!(board.outcome().unwrap().winner(), Some(Color::White));
+        // Stalemated wins
+        let board = Board::from_fen_variant("8/8/8/8/8/p7/P7/8 w - - 0 1", &Antichess).unwrap();
+        assert_eq!(board.outcome().unwrap().winner(), Some(Color::White));
+    }
+
+    #[test]
+    fn test_horde() {
+        let mut board = Board::new_variant(&Horde);
+        assert!(board.parse_uci("a1a3").is_none());
+        play(&mut board, &["a4a5"]);
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/8/P7 w - - 0 1", &Horde).unwrap();
+        assert!(board.parse_uci("a1a3").is_some());
+        let board = Board::from_fen_variant("4k3/8/8/8/8/8/8/8 w - - 0 1", &Horde).unwrap();
+        assert_eq!(board.outcome().unwrap().winner(), Some(Color::Black));
+        assert!(Board::from_fen_variant("4k3/8/8/8/8/8/8/4K3 w - - 0 1", &Horde).is_err());
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/P7 w - - 0 1").is_err());
+    }
+
+    #[test]
+    fn test_racing_kings() {
+        let board = Board::new_variant(&RacingKings);
+        // No move may give check
+        assert!(board.parse_uci("e2c3").is_none());
+        let mut board = Board::from_fen_variant("8/1K6/8/8/8/8/8/k7 w - - 0 1", &RacingKings).unwrap();
+        play(&mut board, &["b7b8"]);
+        assert_eq!(board.outcome().unwrap().winner(), Some(Color::White));
+        // Black one step behind still gets to draw
+        let mut board = Board::from_fen_variant("8/1K5k/8/8/8/8/8/8 w - - 0 1", &RacingKings).unwrap();
+        play(&mut board, &["b7b8"]);
+        assert!(board.outcome().is_none());
+        play(&mut board, &["h7h8"]);
+        assert_eq!(board.outcome(), Some(Outcome::Variant { winner: None, reason: "both kings reached the eighth rank" }));
+    }
+}